@@ -0,0 +1,30 @@
+//! Exports a block height range to the local files, resuming the previous export to the same path
+//!
+//! ```bash
+//! $ cargo run --example export -- ndjson blocks.ndjson --mainnet --start-block-height 88444526 --end-block-height 88445525
+//! ```
+
+use near_lake_framework::{ExportFormat, LakeBuilder};
+
+fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let (Some(format), Some(path)) = (args.next(), args.next()) else {
+        anyhow::bail!("Usage: export <ndjson|directory> <path> [lake options, see --help]");
+    };
+    let format = match format.as_str() {
+        "ndjson" => ExportFormat::Ndjson,
+        "directory" => ExportFormat::Directory,
+        _ => anyhow::bail!(
+            "Unknown format `{}`, expected `ndjson` or `directory`",
+            format
+        ),
+    };
+
+    let lake = LakeBuilder::from_args_iter(args)?.build()?;
+    let summary = tokio::runtime::Runtime::new()?.block_on(lake.export(format, &path))?;
+    eprintln!(
+        "Exported {} blocks to {} in {:?}",
+        summary.blocks_processed, path, summary.elapsed
+    );
+    Ok(())
+}
@@ -30,7 +30,7 @@ async fn handle_block(mut block: near_lake_primitives::block::Block) -> anyhow::
         .events() // fetching all the events that occurred in the block
         .filter(|event| event.standard() == "nep171")
         .filter(|event| event.event() == "nft_mint") // filter them by "nft_mint" event only
-        .filter_map(|event| parse_event(event))
+        .filter_map(parse_event)
         .collect();
     // Indexing lines END
 
@@ -75,17 +75,13 @@ fn parse_event(event: &near_lake_primitives::events::Event) -> Option<NFTReceipt
     };
 
     if let Some(event_data) = event.data() {
-        if let Some(nfts) = marketplace
+        marketplace
             .convert_event_data_to_nfts(event_data.clone(), event.related_receipt_receiver_id())
-        {
-            Some(NFTReceipt {
+            .map(|nfts| NFTReceipt {
                 receipt_id: event.related_receipt_id().to_string(),
                 marketplace_name: marketplace.name(),
                 nfts,
             })
-        } else {
-            None
-        }
     } else {
         None
     }
@@ -135,7 +131,7 @@ impl Marketplace {
                     .map(|token_id| {
                         format!(
                             "https://paras.id/token/{}::{}/{}",
-                            receiver_id.to_string(),
+                            receiver_id,
                             token_id.split(":").collect::<Vec<&str>>()[0],
                             token_id,
                         )
@@ -179,7 +175,7 @@ struct NFTReceipt {
 
 // We are allowing the dead_code lint because not all fields of the structures are used
 // However, they are printed to the terminal for debugging purposes.
-#[allow(dead_code)]
+#[allow(dead_code, clippy::upper_case_acronyms)]
 #[derive(Debug)]
 struct NFT {
     owner: String,
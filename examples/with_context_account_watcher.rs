@@ -0,0 +1,46 @@
+//! This example shows how to use a context AccountWatcherContext with the Lake Framework.
+//! It is going to follow the NEAR Social contract and print the transactions, receipts and events
+//! related to it without filtering the block in the handler.
+use near_lake_account_watcher::{AccountWatcherContext, AccountWatcherContextBuilder};
+use near_lake_framework::near_lake_primitives;
+
+const CONTRACT_ID: &str = "social.near";
+
+fn main() -> anyhow::Result<()> {
+    println!("Starting...");
+    // Building the AccountWatcherContext context.
+    // It will pre-compute the transactions, receipts and events related to the watched account
+    // before the handler is called.
+    let account_watcher_ctx = AccountWatcherContextBuilder::default()
+        .watch_account(CONTRACT_ID.parse()?)
+        .build()?;
+    // Lake Framework start boilerplate
+    near_lake_framework::LakeBuilder::default()
+        .mainnet()
+        .start_block_height(88444526)
+        .build()?
+        // developer-defined async function that handles each block
+        .run_with_context(print_watched_block, &account_watcher_ctx)?;
+    Ok(())
+}
+
+async fn print_watched_block(
+    block: near_lake_primitives::block::Block,
+    ctx: &AccountWatcherContext,
+) -> anyhow::Result<()> {
+    // The context has been updated before this function is called.
+    let block_height = block.block_height();
+    if let Some(watched_block) = ctx.get_watched_block(block_height) {
+        if !watched_block.is_empty() {
+            println!(
+                "Block #{:?}: {} transactions, {} receipts, {} events",
+                block_height,
+                watched_block.transactions.len(),
+                watched_block.receipts.len(),
+                watched_block.events.len(),
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,320 @@
+//! Helpers to track the incoming deposits to a set of deposit addresses, e.g. for the exchanges.
+//!
+//! *Note*: NEAR Lake contains the final blocks only, so every deposit extracted from the block
+//! is final. The deposits are considered confirmed only if the receipt that has transferred the funds
+//! has been executed successfully. The refunds of the failed outgoing transfers are not the deposits.
+//! ```no_run
+//! use near_lake_account_watcher::deposits::DepositTracker;
+//! # use near_lake_framework::near_lake_primitives::block::Block;
+//!
+//! async fn handle_block(mut block: Block, tracker: &DepositTracker) -> anyhow::Result<()> {
+//!     for deposit in tracker.deposits(&mut block) {
+//!         println!(
+//!             "{} {:?} from {} to {} (memo: {:?})",
+//!             deposit.amount, deposit.asset, deposit.sender_id, deposit.receiver_id, deposit.memo,
+//!         );
+//!     }
+//!     Ok(())
+//! }
+//! ```
+use std::collections::HashSet;
+
+use near_lake_framework::{
+    near_indexer_primitives::{
+        near_primitives::types::{AccountId, Balance, BlockHeight},
+        CryptoHash,
+    },
+    near_lake_primitives::{actions::ActionMetaDataExt, block::Block, receipts::ExecutionStatus},
+};
+
+/// The asset of the [Deposit]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DepositAsset {
+    /// Native NEAR tokens (in yoctoNEAR)
+    Native,
+    /// NEP-141 fungible token issued by the given contract
+    FungibleToken { contract_id: AccountId },
+}
+
+/// Confirmed incoming transfer to one of the deposit addresses
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Deposit {
+    pub block_height: BlockHeight,
+    /// The id of the receipt that has transferred the funds
+    pub receipt_id: CryptoHash,
+    pub sender_id: AccountId,
+    /// The deposit address the funds were transferred to
+    pub receiver_id: AccountId,
+    pub asset: DepositAsset,
+    pub amount: Balance,
+    /// The memo of the NEP-141 transfer if provided
+    pub memo: Option<String>,
+}
+
+/// Extracts the confirmed incoming native and NEP-141 transfers to the given deposit addresses from the blocks
+#[derive(Clone, Debug, Default)]
+pub struct DepositTracker {
+    deposit_addresses: HashSet<AccountId>,
+}
+
+impl DepositTracker {
+    /// Creates the tracker for the given deposit addresses
+    pub fn new(deposit_addresses: impl IntoIterator<Item = AccountId>) -> Self {
+        Self {
+            deposit_addresses: deposit_addresses.into_iter().collect(),
+        }
+    }
+
+    /// Adds a deposit address to track
+    pub fn add_deposit_address(&mut self, account_id: AccountId) {
+        self.deposit_addresses.insert(account_id);
+    }
+
+    /// Returns true if the account is one of the deposit addresses
+    pub fn is_deposit_address(&self, account_id: &AccountId) -> bool {
+        self.deposit_addresses.contains(account_id)
+    }
+
+    /// Returns the confirmed incoming transfers to the deposit addresses in the block
+    pub fn deposits(&self, block: &mut Block) -> Vec<Deposit> {
+        let block_height = block.block_height();
+
+        let native_deposits = block
+            .actions()
+            .filter_map(|action| action.as_transfer())
+            .filter(|transfer| self.is_deposit_address(transfer.receiver_id()))
+            .map(|transfer| Deposit {
+                block_height,
+                receipt_id: transfer.receipt_id(),
+                sender_id: transfer.predecessor_id().clone(),
+                receiver_id: transfer.receiver_id().clone(),
+                asset: DepositAsset::Native,
+                amount: transfer.deposit(),
+                memo: None,
+            })
+            .collect::<Vec<Deposit>>();
+
+        let fungible_token_deposits = block
+            .events()
+            .filter(|event| event.standard() == "nep141" && event.event() == "ft_transfer")
+            .flat_map(|event| {
+                let contract_id = event.related_receipt_receiver_id().clone();
+                let receipt_id = event.related_receipt_id();
+                event
+                    .data()
+                    .and_then(|data| data.as_array())
+                    .into_iter()
+                    .flatten()
+                    .filter_map(move |transfer| {
+                        let receiver_id: AccountId =
+                            transfer.get("new_owner_id")?.as_str()?.parse().ok()?;
+                        Some(Deposit {
+                            block_height,
+                            receipt_id,
+                            sender_id: transfer.get("old_owner_id")?.as_str()?.parse().ok()?,
+                            receiver_id,
+                            asset: DepositAsset::FungibleToken {
+                                contract_id: contract_id.clone(),
+                            },
+                            amount: transfer.get("amount")?.as_str()?.parse().ok()?,
+                            memo: transfer
+                                .get("memo")
+                                .and_then(|memo| memo.as_str())
+                                .map(str::to_string),
+                        })
+                    })
+            })
+            .filter(|deposit| self.is_deposit_address(&deposit.receiver_id))
+            .collect::<Vec<Deposit>>();
+
+        native_deposits
+            .into_iter()
+            .chain(fungible_token_deposits)
+            .filter(|deposit| {
+                // the refunds of the failed outgoing transfers come from `system`, they are not the deposits
+                matches!(
+                    block
+                        .receipt_by_id(&deposit.receipt_id)
+                        .filter(|receipt| !receipt.is_refund())
+                        .map(|receipt| receipt.status()),
+                    Some(ExecutionStatus::SuccessValue(_) | ExecutionStatus::SuccessReceiptId(_))
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use near_lake_framework::near_indexer_primitives::StreamerMessage;
+
+    const DEPOSIT_ADDRESS: &str = "deposit.near";
+
+    fn receipt_id(name: &str) -> CryptoHash {
+        CryptoHash::hash_bytes(name.as_bytes())
+    }
+
+    fn success() -> serde_json::Value {
+        serde_json::json!({ "SuccessValue": "" })
+    }
+
+    fn failure() -> serde_json::Value {
+        serde_json::json!({ "Failure": { "ActionError": { "index": 0, "kind": "DelegateActionExpired" } } })
+    }
+
+    /// The executed receipt of the given actions in the format of the NEAR Lake shard files
+    fn receipt_execution_outcome(
+        name: &str,
+        predecessor_id: &str,
+        receiver_id: &str,
+        actions: serde_json::Value,
+        status: serde_json::Value,
+        logs: Vec<String>,
+    ) -> serde_json::Value {
+        let receipt_id = receipt_id(name).to_string();
+        serde_json::json!({
+            "execution_outcome": {
+                "proof": [],
+                "block_hash": "95K8Je1iAVqieVU8ZuGgSdbvYs8T9rL6ER1XnRekMGbj",
+                "id": receipt_id,
+                "outcome": {
+                    "logs": logs,
+                    "receipt_ids": [],
+                    "gas_burnt": 0,
+                    "tokens_burnt": "0",
+                    "executor_id": receiver_id,
+                    "status": status,
+                    "metadata": { "version": 3, "gas_profile": [] }
+                }
+            },
+            "receipt": {
+                "predecessor_id": predecessor_id,
+                "receiver_id": receiver_id,
+                "receipt_id": receipt_id,
+                "receipt": {
+                    "Action": {
+                        "signer_id": predecessor_id,
+                        "signer_public_key": "ed25519:8Rn4FJeeRYcrLbcrAQNFVgvbZ2FCEQjgydbXwqBwF1ib",
+                        "gas_price": "100000000",
+                        "output_data_receivers": [],
+                        "input_data_ids": [],
+                        "actions": actions
+                    }
+                }
+            }
+        })
+    }
+
+    fn transfer(
+        name: &str,
+        predecessor_id: &str,
+        deposit: u128,
+        status: serde_json::Value,
+    ) -> serde_json::Value {
+        receipt_execution_outcome(
+            name,
+            predecessor_id,
+            DEPOSIT_ADDRESS,
+            serde_json::json!([{ "Transfer": { "deposit": deposit.to_string() } }]),
+            status,
+            vec![],
+        )
+    }
+
+    fn block(receipt_execution_outcomes: Vec<serde_json::Value>) -> Block {
+        let streamer_message: StreamerMessage = serde_json::from_value(serde_json::json!({
+            "block": serde_json::from_str::<serde_json::Value>(include_str!(
+                "../../lake-framework/blocks/000000879765/block.json"
+            ))
+            .unwrap(),
+            "shards": [{
+                "shard_id": 0,
+                "chunk": null,
+                "receipt_execution_outcomes": receipt_execution_outcomes,
+                "state_changes": []
+            }]
+        }))
+        .unwrap();
+        streamer_message.into()
+    }
+
+    fn tracker() -> DepositTracker {
+        DepositTracker::new([DEPOSIT_ADDRESS.parse().unwrap()])
+    }
+
+    #[test]
+    fn extracts_native_deposits() {
+        let mut block = block(vec![transfer("deposit", "alice.near", 1000, success())]);
+
+        assert_eq!(
+            tracker().deposits(&mut block),
+            vec![Deposit {
+                block_height: 879765,
+                receipt_id: receipt_id("deposit"),
+                sender_id: "alice.near".parse().unwrap(),
+                receiver_id: DEPOSIT_ADDRESS.parse().unwrap(),
+                asset: DepositAsset::Native,
+                amount: 1000,
+                memo: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn skips_refunds() {
+        let mut block = block(vec![transfer("refund", "system", 1000, success())]);
+
+        assert!(tracker().deposits(&mut block).is_empty());
+    }
+
+    #[test]
+    fn skips_failed_receipts() {
+        let mut block = block(vec![transfer("failed", "alice.near", 1000, failure())]);
+
+        assert!(tracker().deposits(&mut block).is_empty());
+    }
+
+    #[test]
+    fn extracts_every_fungible_token_transfer_of_the_event() {
+        let event = serde_json::json!({
+            "standard": "nep141",
+            "version": "1.0.0",
+            "event": "ft_transfer",
+            "data": [
+                { "old_owner_id": "alice.near", "new_owner_id": DEPOSIT_ADDRESS, "amount": "10", "memo": "order 1" },
+                { "old_owner_id": "alice.near", "new_owner_id": "bob.near", "amount": "20" },
+                { "old_owner_id": "alice.near", "new_owner_id": DEPOSIT_ADDRESS, "amount": "30" }
+            ]
+        });
+        let mut block = block(vec![receipt_execution_outcome(
+            "ft_transfer",
+            "alice.near",
+            "token.near",
+            serde_json::json!([{
+                "FunctionCall": { "method_name": "ft_transfer", "args": "e30=", "gas": 30000000000000u64, "deposit": "1" }
+            }]),
+            success(),
+            vec![format!("EVENT_JSON:{event}")],
+        )]);
+
+        let fungible_token_deposit = |amount, memo: Option<&str>| Deposit {
+            block_height: 879765,
+            receipt_id: receipt_id("ft_transfer"),
+            sender_id: "alice.near".parse().unwrap(),
+            receiver_id: DEPOSIT_ADDRESS.parse().unwrap(),
+            asset: DepositAsset::FungibleToken {
+                contract_id: "token.near".parse().unwrap(),
+            },
+            amount,
+            memo: memo.map(str::to_string),
+        };
+        assert_eq!(
+            tracker().deposits(&mut block),
+            vec![
+                fungible_token_deposit(10, Some("order 1")),
+                fungible_token_deposit(30, None),
+            ]
+        );
+    }
+}
@@ -0,0 +1,162 @@
+#![doc = include_str!("../README.md")]
+#[macro_use]
+extern crate derive_builder;
+
+use cached::{Cached, SizedCache};
+use near_lake_framework::{
+    near_indexer_primitives::near_primitives::types::{AccountId, BlockHeight},
+    near_lake_primitives::{block::Block, Event, Receipt, Transaction},
+    LakeContextExt,
+};
+
+pub mod deposits;
+pub mod router;
+pub mod rules;
+
+type Cache = SizedCache<BlockHeight, WatchedBlock>;
+
+/// The pattern to match the accounts against
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AccountPattern {
+    /// Matches the given account only
+    Exact(AccountId),
+    /// Matches every account that ends with the given suffix, e.g. `.pool.near`
+    Suffix(String),
+}
+
+impl AccountPattern {
+    /// Returns true if the account matches the pattern
+    /// ```
+    /// use near_lake_account_watcher::AccountPattern;
+    ///
+    /// let pattern = AccountPattern::Suffix(".pool.near".to_string());
+    /// assert!(pattern.matches(&"astro-stakers.pool.near".parse().unwrap()));
+    /// assert!(!pattern.matches(&"alice.near".parse().unwrap()));
+    /// ```
+    pub fn matches(&self, account_id: &AccountId) -> bool {
+        match self {
+            Self::Exact(watched_account_id) => watched_account_id == account_id,
+            Self::Suffix(suffix) => account_id.as_str().ends_with(suffix.as_str()),
+        }
+    }
+}
+
+/// The transactions, receipts and events of a block related to the watched accounts
+#[derive(Clone, Debug, Default)]
+pub struct WatchedBlock {
+    /// Transactions signed by or sent to the watched accounts
+    pub transactions: Vec<Transaction>,
+    /// Receipts received or sent by the watched accounts
+    pub receipts: Vec<Receipt>,
+    /// Events emitted by the watched accounts
+    pub events: Vec<Event>,
+}
+
+impl WatchedBlock {
+    /// Returns true if nothing in the block is related to the watched accounts
+    pub fn is_empty(&self) -> bool {
+        self.transactions.is_empty() && self.receipts.is_empty() && self.events.is_empty()
+    }
+}
+
+/// Picks the transactions, receipts and events related to the watched accounts from the block
+pub(crate) fn select(block: &mut Block, is_watched: impl Fn(&AccountId) -> bool) -> WatchedBlock {
+    WatchedBlock {
+        transactions: block
+            .transactions()
+            .filter(|tx| is_watched(tx.signer_id()) || is_watched(tx.receiver_id()))
+            .cloned()
+            .collect(),
+        receipts: block
+            .receipts()
+            .filter(|receipt| {
+                is_watched(receipt.receiver_id()) || is_watched(receipt.predecessor_id())
+            })
+            .cloned()
+            .collect(),
+        events: block
+            .events()
+            .filter(|event| is_watched(event.related_receipt_receiver_id()))
+            .cloned()
+            .collect(),
+    }
+}
+
+#[derive(Debug, Builder)]
+#[builder(pattern = "owned")]
+pub struct AccountWatcherContext {
+    #[builder(
+        setter(custom = true, name = "cache_size"),
+        default = "std::sync::RwLock::new(Cache::with_size(100))"
+    )]
+    cache: std::sync::RwLock<Cache>,
+    #[builder(setter(custom = true, name = "watch_accounts"))]
+    patterns: Vec<AccountPattern>,
+}
+
+impl AccountWatcherContextBuilder {
+    /// Sets how many recent blocks to keep the pre-computed data for. Default is 100.
+    /// Increase it if you run the Lake Framework with the concurrency higher than that.
+    pub fn cache_size(mut self, value: usize) -> Self {
+        self.cache = Some(std::sync::RwLock::new(Cache::with_size(value)));
+        self
+    }
+
+    /// Stores the Vec of [AccountId](near_lake_framework::near_indexer_primitives::near_primitives::types::AccountId) to watch for.
+    /// **Warning**: This method overrides the previously added accounts and suffixes.
+    pub fn watch_accounts(mut self, account_ids: Vec<AccountId>) -> Self {
+        self.patterns = Some(account_ids.into_iter().map(AccountPattern::Exact).collect());
+        self
+    }
+
+    /// Adds an account to the watching list.
+    /// **Warning**: This method appends to the previous value.
+    pub fn watch_account(self, account_id: AccountId) -> Self {
+        self.watch(AccountPattern::Exact(account_id))
+    }
+
+    /// Adds an account suffix (e.g. `.pool.near`) to the watching list.
+    /// **Warning**: This method appends to the previous value.
+    pub fn watch_suffix(self, suffix: impl Into<String>) -> Self {
+        self.watch(AccountPattern::Suffix(suffix.into()))
+    }
+
+    /// Adds an [AccountPattern] to the watching list.
+    /// **Warning**: This method appends to the previous value.
+    pub fn watch(mut self, pattern: AccountPattern) -> Self {
+        self.patterns.get_or_insert_with(Vec::new).push(pattern);
+        self
+    }
+}
+
+impl LakeContextExt for AccountWatcherContext {
+    /// Scans the [near_lake_primitives::Block](near_lake_framework::near_lake_primitives::block::Block)
+    /// and stores the transactions, receipts and events related to the watched accounts.
+    fn execute_before_run(&self, block: &mut Block) {
+        let watched_block = select(block, |account_id| self.is_watched(account_id));
+
+        let mut cache = self.cache.write().unwrap();
+        cache.cache_set(block.block_height(), watched_block);
+    }
+
+    /// We don't need to do anything after the run.
+    fn execute_after_run(&self) {}
+}
+
+impl AccountWatcherContext {
+    /// Returns true if the account matches any of the watched accounts or suffixes.
+    pub fn is_watched(&self, account_id: &AccountId) -> bool {
+        self.patterns
+            .iter()
+            .any(|pattern| pattern.matches(account_id))
+    }
+
+    /// Returns the transactions, receipts and events related to the watched accounts for a given block height.
+    /// If the block hasn't been processed yet (or has been evicted from the cache), it returns None.
+    pub fn get_watched_block(&self, block_height: BlockHeight) -> Option<WatchedBlock> {
+        // **Note**: [cached::SizedCache] updates metadata on every cache access. That's why
+        // we need to use a write lock here.
+        let mut cache = self.cache.write().unwrap();
+        cache.cache_get(&block_height).cloned()
+    }
+}
@@ -0,0 +1,231 @@
+//! Routing of the blocks to multiple named sub-handlers, each interested in its own accounts and events.
+//!
+//! It allows hosting many small indexers in one process on a single stream: every block is fetched once,
+//! and each sub-handler receives only the parts of it matching its [Route].
+use std::future::Future;
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use near_lake_framework::{
+    near_indexer_primitives::near_primitives::types::{AccountId, BlockHeight},
+    near_lake_primitives::block::Block,
+    EventFilter, LakeContextExt,
+};
+
+use crate::{AccountPattern, WatchedBlock};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+type SubHandler =
+    dyn Fn(BlockHeight, WatchedBlock) -> BoxFuture<'static, Result<(), BoxError>> + Send + Sync;
+
+/// Describes which parts of a block a sub-handler is interested in
+/// ```
+/// use near_lake_account_watcher::router::Route;
+/// use near_lake_framework::EventFilter;
+///
+/// let route = Route::new("staking")
+///     .watch_suffix(".pool.near")
+///     .events(EventFilter::default().standard("nep141").event("ft_transfer"));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Route {
+    name: String,
+    patterns: Vec<AccountPattern>,
+    event_filters: Vec<EventFilter>,
+}
+
+impl Route {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            patterns: vec![],
+            event_filters: vec![],
+        }
+    }
+
+    /// Adds an account to the route
+    pub fn watch_account(self, account_id: AccountId) -> Self {
+        self.watch(AccountPattern::Exact(account_id))
+    }
+
+    /// Adds an account suffix (e.g. `.pool.near`) to the route
+    pub fn watch_suffix(self, suffix: impl Into<String>) -> Self {
+        self.watch(AccountPattern::Suffix(suffix.into()))
+    }
+
+    /// Adds an [AccountPattern] to the route. The transactions, receipts and events related to the matching
+    /// accounts are routed to the sub-handler
+    pub fn watch(mut self, pattern: AccountPattern) -> Self {
+        self.patterns.push(pattern);
+        self
+    }
+
+    /// Adds an [EventFilter] to the route. The matching events are routed to the sub-handler
+    /// regardless of the watched accounts
+    pub fn events(mut self, filter: EventFilter) -> Self {
+        self.event_filters.push(filter);
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn is_watched(&self, account_id: &AccountId) -> bool {
+        self.patterns
+            .iter()
+            .any(|pattern| pattern.matches(account_id))
+    }
+
+    /// Picks the parts of the block matching the route
+    fn select(&self, block: &mut Block) -> WatchedBlock {
+        let mut watched_block = crate::select(block, |account_id| self.is_watched(account_id));
+        watched_block.events = block
+            .events()
+            .filter(|event| {
+                self.is_watched(event.related_receipt_receiver_id())
+                    || self
+                        .event_filters
+                        .iter()
+                        .any(|filter| filter.matches(event))
+            })
+            .cloned()
+            .collect();
+        watched_block
+    }
+}
+
+/// The error returned by [Router::handle_block] if any of the sub-handlers has failed
+#[derive(Debug)]
+pub struct RouterError {
+    /// The names of the failed routes along with the errors
+    pub failures: Vec<(String, BoxError)>,
+}
+
+impl std::fmt::Display for RouterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} sub-handler(s) failed:", self.failures.len())?;
+        for (name, error) in &self.failures {
+            write!(f, " [{}] {};", name, error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for RouterError {}
+
+/// Fans the relevant parts of every block out to the sub-handlers concurrently.
+///
+/// The sub-handlers are called only if there is something matching their [Route] in the block.
+/// Run the Lake Framework with the router as the context and [Router::handle_block] as the handler:
+/// ```no_run
+/// use near_lake_account_watcher::{router::{Route, Router}, WatchedBlock};
+/// use near_lake_framework::EventFilter;
+///
+/// # fn main() -> anyhow::Result<()> {
+/// let router = Router::default()
+///     .route(Route::new("social").watch_account("social.near".parse()?), handle_social)
+///     .route(
+///         Route::new("nft").events(EventFilter::default().standard("nep171")),
+///         handle_nft,
+///     );
+///
+/// near_lake_framework::LakeBuilder::default()
+///     .mainnet()
+///     .start_block_height(88444526)
+///     .build()?
+///     .run_with_context(Router::handle_block, &router)?;
+/// # Ok(())
+/// # }
+///
+/// async fn handle_social(block_height: u64, watched_block: WatchedBlock) -> anyhow::Result<()> {
+///     println!("#{}: {} social receipts", block_height, watched_block.receipts.len());
+///     Ok(())
+/// }
+///
+/// async fn handle_nft(block_height: u64, watched_block: WatchedBlock) -> anyhow::Result<()> {
+///     println!("#{}: {} NFT events", block_height, watched_block.events.len());
+///     Ok(())
+/// }
+/// ```
+#[derive(Default)]
+pub struct Router {
+    routes: Vec<(Route, Arc<SubHandler>)>,
+}
+
+impl Router {
+    /// Adds a sub-handler receiving the parts of the blocks matching the [Route]
+    pub fn route<Fut, E>(
+        mut self,
+        route: Route,
+        handler: impl Fn(BlockHeight, WatchedBlock) -> Fut + Send + Sync + 'static,
+    ) -> Self
+    where
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: Into<BoxError>,
+    {
+        let handler = Arc::new(handler);
+        self.routes.push((
+            route,
+            Arc::new(move |block_height, watched_block| {
+                let handler = handler.clone();
+                Box::pin(async move {
+                    handler(block_height, watched_block)
+                        .await
+                        .map_err(Into::into)
+                })
+            }),
+        ));
+        self
+    }
+
+    /// Returns the names of the routes
+    pub fn route_names(&self) -> impl Iterator<Item = &str> {
+        self.routes.iter().map(|(route, _)| route.name())
+    }
+
+    /// The handler to pass to [Lake::run_with_context](near_lake_framework::Lake::run_with_context)
+    /// along with the router as the context
+    pub async fn handle_block(mut block: Block, router: &Router) -> Result<(), RouterError> {
+        let block_height = block.block_height();
+        let dispatches = router
+            .routes
+            .iter()
+            .filter_map(|(route, handler)| {
+                let watched_block = route.select(&mut block);
+                (!watched_block.is_empty()).then(|| {
+                    let name = route.name().to_string();
+                    let dispatch = handler(block_height, watched_block);
+                    async move { dispatch.await.map_err(|error| (name, error)) }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let failures: Vec<_> = futures::future::join_all(dispatches)
+            .await
+            .into_iter()
+            .filter_map(Result::err)
+            .collect();
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(RouterError { failures })
+        }
+    }
+}
+
+impl std::fmt::Debug for Router {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Router")
+            .field("routes", &self.route_names().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl LakeContextExt for Router {
+    /// The routes are selected in [Router::handle_block], nothing to do before the run.
+    fn execute_before_run(&self, _block: &mut Block) {}
+
+    /// We don't need to do anything after the run.
+    fn execute_after_run(&self) {}
+}
@@ -0,0 +1,215 @@
+//! Declarative filter rules loaded from a JSON or YAML config.
+//!
+//! The rules are compiled at startup into [CompiledRules] that pick the matching parts of every block,
+//! so the operators can adjust what an indexer captures by editing the config instead of the code.
+//!
+//! All the specified criteria must match, the omitted ones don't restrict anything:
+//!  * `accounts` - exact account ids or suffix patterns starting with `*` (e.g. `*.pool.near`).
+//!    Matched against the signer and the receiver of the transactions, the predecessor and the receiver
+//!    of the receipts, the emitter of the events and the affected account of the state changes
+//!  * `methods` - names of the called methods. Only the transactions and receipts with a matching
+//!    `FunctionCall` action are picked
+//!  * `event_standards` - standards of the events, e.g. `nep141`
+//!  * `state_change_kinds` - kinds of the state changes, e.g. `data_update`. State changes are picked only if set
+//! ```
+//! use near_lake_account_watcher::rules::Rules;
+//!
+//! let rules = Rules::from_yaml(r#"
+//! accounts: ["social.near", "*.pool.near"]
+//! methods: ["set"]
+//! state_change_kinds: ["data_update"]
+//! "#).unwrap().compile();
+//! assert!(rules.is_watched(&"astro-stakers.pool.near".parse().unwrap()));
+//! ```
+use std::collections::HashSet;
+
+use near_lake_framework::{
+    near_indexer_primitives::near_primitives::types::AccountId,
+    near_lake_primitives::{
+        actions::{Action, ActionMetaDataExt},
+        block::Block,
+        ReceiptId, StateChange, StateChangeValue,
+    },
+};
+
+use crate::{AccountPattern, WatchedBlock};
+
+/// The kind of the [StateChange]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StateChangeKind {
+    AccountUpdate,
+    AccountDeletion,
+    AccessKeyUpdate,
+    AccessKeyDeletion,
+    DataUpdate,
+    DataDeletion,
+    ContractCodeUpdate,
+    ContractCodeDeletion,
+}
+
+impl From<&StateChangeValue> for StateChangeKind {
+    fn from(value: &StateChangeValue) -> Self {
+        match value {
+            StateChangeValue::AccountUpdate { .. } => Self::AccountUpdate,
+            StateChangeValue::AccountDeletion { .. } => Self::AccountDeletion,
+            StateChangeValue::AccessKeyUpdate { .. } => Self::AccessKeyUpdate,
+            StateChangeValue::AccessKeyDeletion { .. } => Self::AccessKeyDeletion,
+            StateChangeValue::DataUpdate { .. } => Self::DataUpdate,
+            StateChangeValue::DataDeletion { .. } => Self::DataDeletion,
+            StateChangeValue::ContractCodeUpdate { .. } => Self::ContractCodeUpdate,
+            StateChangeValue::ContractCodeDeletion { .. } => Self::ContractCodeDeletion,
+        }
+    }
+}
+
+/// The error returned if the rules can't be parsed
+#[derive(Debug, thiserror::Error)]
+pub enum RulesError {
+    #[error("Failed to parse the rules from JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Failed to parse the rules from YAML: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+    #[error("Invalid account in the rules: {0}")]
+    InvalidAccount(String),
+}
+
+/// The filter rules as they are written in the config
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Rules {
+    pub accounts: Vec<String>,
+    pub methods: Vec<String>,
+    pub event_standards: Vec<String>,
+    pub state_change_kinds: Vec<StateChangeKind>,
+}
+
+impl Rules {
+    pub fn from_json(rules: &str) -> Result<Self, RulesError> {
+        Self::validate(serde_json::from_str(rules)?)
+    }
+
+    pub fn from_yaml(rules: &str) -> Result<Self, RulesError> {
+        Self::validate(serde_yaml::from_str(rules)?)
+    }
+
+    fn validate(rules: Self) -> Result<Self, RulesError> {
+        for account in &rules.accounts {
+            if !account.starts_with('*') && account.parse::<AccountId>().is_err() {
+                return Err(RulesError::InvalidAccount(account.clone()));
+            }
+        }
+        Ok(rules)
+    }
+
+    /// Compiles the rules into the per-block filter
+    pub fn compile(self) -> CompiledRules {
+        let any_account = self.accounts.is_empty();
+        let mut exact_accounts = HashSet::new();
+        let mut patterns = vec![];
+        for account in self.accounts {
+            match account.strip_prefix('*') {
+                Some(suffix) => patterns.push(AccountPattern::Suffix(suffix.to_string())),
+                // validated on parsing, the invalid accounts of the `Rules` built by hand never match
+                None => exact_accounts.extend(account.parse::<AccountId>().ok()),
+            }
+        }
+        CompiledRules {
+            any_account,
+            exact_accounts,
+            patterns,
+            methods: self.methods.into_iter().collect(),
+            event_standards: self.event_standards.into_iter().collect(),
+            state_change_kinds: self.state_change_kinds.into_iter().collect(),
+        }
+    }
+}
+
+/// The parts of a block matching the [CompiledRules]
+#[derive(Clone, Debug, Default)]
+pub struct MatchedBlock {
+    /// The matching transactions, receipts and events
+    pub watched: WatchedBlock,
+    /// The matching state changes. Empty unless `state_change_kinds` are set
+    pub state_changes: Vec<StateChange>,
+}
+
+impl MatchedBlock {
+    /// Returns true if nothing in the block matches the rules
+    pub fn is_empty(&self) -> bool {
+        self.watched.is_empty() && self.state_changes.is_empty()
+    }
+}
+
+/// The [Rules] compiled into the per-block filter
+#[derive(Clone, Debug)]
+pub struct CompiledRules {
+    any_account: bool,
+    exact_accounts: HashSet<AccountId>,
+    patterns: Vec<AccountPattern>,
+    methods: HashSet<String>,
+    event_standards: HashSet<String>,
+    state_change_kinds: HashSet<StateChangeKind>,
+}
+
+impl CompiledRules {
+    /// Returns true if the account matches the `accounts` rule
+    pub fn is_watched(&self, account_id: &AccountId) -> bool {
+        self.any_account
+            || self.exact_accounts.contains(account_id)
+            || self
+                .patterns
+                .iter()
+                .any(|pattern| pattern.matches(account_id))
+    }
+
+    fn is_method_watched(&self, action: &Action) -> bool {
+        action.as_function_call().map_or(false, |function_call| {
+            self.methods.contains(function_call.method_name())
+        })
+    }
+
+    /// Picks the parts of the block matching the rules
+    pub fn select(&self, block: &mut Block) -> MatchedBlock {
+        let mut watched = crate::select(block, |account_id| self.is_watched(account_id));
+
+        if !self.methods.is_empty() {
+            watched.transactions.retain(|tx| {
+                tx.actions_included()
+                    .any(|action| self.is_method_watched(action))
+            });
+            let receipt_ids: HashSet<ReceiptId> = block
+                .actions()
+                .filter(|action| self.is_method_watched(action))
+                .map(|action| action.receipt_id())
+                .collect();
+            watched
+                .receipts
+                .retain(|receipt| receipt_ids.contains(&receipt.receipt_id()));
+        }
+        if !self.event_standards.is_empty() {
+            watched
+                .events
+                .retain(|event| self.event_standards.contains(event.standard()));
+        }
+
+        let state_changes = if self.state_change_kinds.is_empty() {
+            vec![]
+        } else {
+            block
+                .state_changes()
+                .filter(|state_change| {
+                    self.state_change_kinds
+                        .contains(&StateChangeKind::from(state_change.value()))
+                        && self.is_watched(state_change.affected_account_id())
+                })
+                .cloned()
+                .collect()
+        };
+
+        MatchedBlock {
+            watched,
+            state_changes,
+        }
+    }
+}
@@ -2,16 +2,18 @@
 use proc_macro::TokenStream;
 use quote::quote;
 
-#[proc_macro_derive(LakeContext)]
+#[proc_macro_derive(LakeContext, attributes(lake_context))]
 pub fn lake_context_derive(input: TokenStream) -> TokenStream {
     let input = syn::parse_macro_input!(input as syn::DeriveInput);
 
     // Used in the quasi-quotation below as `#name`.
-    let name = input.ident;
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
 
     // Build the trait impl.
     // Iterate over all fields and for each field generate a call to `execute_before_run`.
-    // if the field is a an impl of LakeContext, then call `execute_before_run` on the struct.
+    // The calls resolve to the field's `LakeContextExt` impl if there is one (also behind `Arc`/`Box`),
+    // and to the no-op otherwise, see `near_lake_framework::__derive`.
 
     let fields = if let syn::Data::Struct(syn::DataStruct {
         fields: syn::Fields::Named(syn::FieldsNamed { named, .. }),
@@ -23,58 +25,170 @@ pub fn lake_context_derive(input: TokenStream) -> TokenStream {
         unimplemented!();
     };
 
-    let calls_before_run = fields
-        .iter()
-        .filter(|f| {
-            let ty = &f.ty;
-            if let syn::Type::Path(syn::TypePath { path, .. }) = ty {
-                if let Some(ident) = path.get_ident() {
-                    ident == "LakeContext"
-                } else {
-                    false
-                }
-            } else {
-                false
-            }
-        })
-        .map(|f| {
-            let name = &f.ident;
-            quote! { self.#name.execute_before_run(block); }
-        });
+    let mut context_fields = vec![];
+    for field in fields {
+        match is_skipped(field) {
+            Ok(true) => {}
+            Ok(false) => context_fields.push(&field.ident),
+            Err(err) => return err.to_compile_error().into(),
+        }
+    }
 
-    let calls_after_run = fields
-        .iter()
-        .rev()
-        .filter(|f| {
-            let ty = &f.ty;
-            if let syn::Type::Path(syn::TypePath { path, .. }) = ty {
-                if let Some(ident) = path.get_ident() {
-                    ident == "LakeContext"
-                } else {
-                    false
-                }
-            } else {
-                false
-            }
-        })
-        .map(|f| {
-            let name = &f.ident;
-            quote! { self.#name.execute_after_run(); }
-        });
+    let calls_before_run = context_fields.iter().map(|name| {
+        quote! {
+            (&near_lake_framework::__derive::Field(&self.#name)).execute_before_run(block);
+        }
+    });
+
+    let calls_after_run = context_fields.iter().rev().map(|name| {
+        quote! {
+            (&near_lake_framework::__derive::Field(&self.#name)).execute_after_run();
+        }
+    });
+
+    let calls_after_run_with_scope = context_fields.iter().rev().map(|name| {
+        quote! {
+            (&near_lake_framework::__derive::Field(&self.#name)).execute_after_run_with_scope(scope);
+        }
+    });
+
+    // The futures of the fields are created upfront, so the returned future doesn't borrow the context itself
+    let finalizers = context_fields.iter().rev().map(|name| {
+        quote! {
+            (&near_lake_framework::__derive::Field(&self.#name)).finalize(),
+        }
+    });
 
     let expanded = quote! {
         // The generated impl.
-        impl near_lake_framework::LakeContextExt for #name {
-            fn execute_before_run(&self, block: &mut near_lake_primitives::block::Block) {
+        impl #impl_generics near_lake_framework::LakeContextExt for #name #ty_generics #where_clause {
+            #[allow(unused_variables)]
+            fn execute_before_run(&self, block: &mut near_lake_framework::near_lake_primitives::block::Block) {
+                #[allow(unused_imports)]
+                use near_lake_framework::__derive::{ContextField as _, PlainField as _};
                 #( #calls_before_run )*
             }
 
             fn execute_after_run(&self) {
+                #[allow(unused_imports)]
+                use near_lake_framework::__derive::{ContextField as _, PlainField as _};
                 #( #calls_after_run )*
             }
+
+            #[allow(unused_variables)]
+            fn execute_after_run_with_scope(&self, scope: &near_lake_framework::near_lake_primitives::block::BlockScope) {
+                #[allow(unused_imports)]
+                use near_lake_framework::__derive::{ContextField as _, PlainField as _};
+                #( #calls_after_run_with_scope )*
+            }
+
+            fn finalize(&self) -> near_lake_framework::__derive::BoxFuture<'_, ()> {
+                #[allow(unused_imports)]
+                use near_lake_framework::__derive::{ContextField as _, PlainField as _};
+                let finalizers: Vec<near_lake_framework::__derive::BoxFuture<'_, ()>> = vec![#( #finalizers )*];
+                Box::pin(async move {
+                    for finalizer in finalizers {
+                        finalizer.await;
+                    }
+                })
+            }
         }
     };
 
     // Hand the output tokens back to the compiler.
     proc_macro::TokenStream::from(expanded)
 }
+
+/// Turns the async handler function into the indexer: generates the `main` function setting up the `LakeBuilder`
+/// with `LakeBuilder::from_args` (the standard command line arguments and environment variables) and running the handler.
+/// The handler taking the context as the second argument needs the expression creating the context:
+/// `#[near_lake_framework::main(context = MyContext::new())]`
+/// ```ignore
+/// // NEAR_LAKE_CHAIN_ID=mainnet my-indexer --start-block-height 88444526
+/// #[near_lake_framework::main]
+/// async fn handle_block(mut block: near_lake_primitives::block::Block) -> anyhow::Result<()> {
+///     println!("Block #{}: {} receipts", block.block_height(), block.receipts().count());
+///     Ok(())
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn main(args: TokenStream, item: TokenStream) -> TokenStream {
+    let mut context: Option<syn::Expr> = None;
+    let args_parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("context") {
+            context = Some(meta.value()?.parse()?);
+            Ok(())
+        } else {
+            Err(meta.error("unsupported `main` attribute, expected `context = <expr>`"))
+        }
+    });
+    syn::parse_macro_input!(args with args_parser);
+    let handler = syn::parse_macro_input!(item as syn::ItemFn);
+
+    let name = &handler.sig.ident;
+    if handler.sig.asyncness.is_none() {
+        return syn::Error::new_spanned(handler.sig.fn_token, "the handler must be `async`")
+            .to_compile_error()
+            .into();
+    }
+    if name == "main" {
+        return syn::Error::new_spanned(
+            name,
+            "the handler can't be named `main`, the attribute generates it",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    let run = match (handler.sig.inputs.len(), context) {
+        (1, None) => quote! { .run(#name) },
+        (2, Some(context)) => quote! { .run_with_context(#name, &#context) },
+        (2, None) => return syn::Error::new_spanned(
+            &handler.sig.inputs,
+            "the handler taking the context needs `#[near_lake_framework::main(context = <expr>)]`",
+        )
+        .to_compile_error()
+        .into(),
+        _ => {
+            return syn::Error::new_spanned(
+                &handler.sig.inputs,
+                "the handler must take the block (and the context if `context = <expr>` is set)",
+            )
+            .to_compile_error()
+            .into()
+        }
+    };
+
+    let expanded = quote! {
+        #handler
+
+        fn main() -> Result<(), Box<dyn std::error::Error>> {
+            near_lake_framework::LakeBuilder::from_args()?
+                .build()?
+                #run?;
+            Ok(())
+        }
+    };
+
+    proc_macro::TokenStream::from(expanded)
+}
+
+/// Whether the field is marked with `#[lake_context(skip)]` and doesn't participate in the hooks
+fn is_skipped(field: &syn::Field) -> syn::Result<bool> {
+    let mut skip = false;
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("lake_context"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("skip") {
+                skip = true;
+                Ok(())
+            } else {
+                Err(meta.error("unsupported `lake_context` attribute, expected `skip`"))
+            }
+        })?;
+    }
+    Ok(skip)
+}
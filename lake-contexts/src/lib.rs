@@ -0,0 +1,8 @@
+#![doc = include_str!("../README.md")]
+pub use log_sampling::{LogSampled, LogSamplingContext};
+pub use profiling::{BlockTimings, ProfilingContext};
+pub use rate_limit::RateLimitContext;
+
+mod log_sampling;
+mod profiling;
+mod rate_limit;
@@ -0,0 +1,76 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use near_lake_framework::{near_lake_primitives::block::Block, LakeContextExt};
+
+/// The marker of the blocks sampled by the [LogSamplingContext], kept in the block's
+/// [BlockScope](near_lake_framework::near_lake_primitives::block::BlockScope)
+#[derive(Clone, Copy, Debug)]
+pub struct LogSampled;
+
+/// Logs the summary of every n-th block (the height, the number of the shards, transactions and receipts),
+/// so the long-running indexers show they are alive without flooding the logs with every block.
+///
+/// The handler can log its own details for the same blocks only, checking [LogSamplingContext::is_sampled]
+/// ```no_run
+/// use near_lake_contexts::LogSamplingContext;
+/// # use near_lake_framework::near_lake_primitives::block::Block;
+///
+/// # fn main() -> anyhow::Result<()> {
+/// near_lake_framework::LakeBuilder::default()
+///     .mainnet()
+///     .start_block_height(80504433)
+///     .build()?
+///     .run_with_context(handle_block, &LogSamplingContext::every(1000))?;
+/// # Ok(())
+/// # }
+///
+/// async fn handle_block(mut block: Block, _ctx: &LogSamplingContext) -> anyhow::Result<()> {
+///     let events = block.events().count();
+///     if LogSamplingContext::is_sampled(&block) {
+///         println!("Block #{}: {} events", block.block_height(), events);
+///     }
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug)]
+pub struct LogSamplingContext {
+    every: u64,
+    seen: AtomicU64,
+}
+
+impl LogSamplingContext {
+    /// Samples every n-th block, starting with the first one
+    pub fn every(every: u64) -> Self {
+        Self {
+            every: every.max(1),
+            seen: AtomicU64::new(0),
+        }
+    }
+
+    /// Whether the block is sampled for the logs
+    pub fn is_sampled(block: &Block) -> bool {
+        block.scope().contains::<LogSampled>()
+    }
+}
+
+impl LakeContextExt for LogSamplingContext {
+    fn execute_before_run(&self, block: &mut Block) {
+        if self.seen.fetch_add(1, Ordering::Relaxed) % self.every != 0 {
+            return;
+        }
+        block.scope().insert(LogSampled);
+        let shards = block.shards().count();
+        let transactions = block.transactions().count();
+        let receipts = block.receipts().count();
+        tracing::info!(
+            target: "near_lake_contexts",
+            block_height = block.block_height(),
+            shards,
+            transactions,
+            receipts,
+            "Sampled block",
+        );
+    }
+
+    fn execute_after_run(&self) {}
+}
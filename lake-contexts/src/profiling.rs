@@ -0,0 +1,123 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use near_lake_framework::{
+    near_indexer_primitives::near_primitives::types::BlockHeight,
+    near_lake_primitives::block::{Block, BlockScope},
+    LakeContextExt,
+};
+
+/// The start of the block processing, kept in the [BlockScope] between the hooks
+struct BlockStarted {
+    block_height: BlockHeight,
+    started_at: Instant,
+}
+
+/// The aggregated timings of the blocks measured by the [ProfilingContext]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BlockTimings {
+    /// The number of the measured blocks
+    pub blocks: u64,
+    /// The total time spent on the blocks
+    pub total: Duration,
+    /// The longest time spent on a block
+    pub max: Duration,
+    /// The height of the block that took the longest
+    pub slowest_block_height: Option<BlockHeight>,
+}
+
+impl BlockTimings {
+    /// The average time spent on a block
+    pub fn average(&self) -> Duration {
+        self.total
+            .checked_div(self.blocks.try_into().unwrap_or(u32::MAX))
+            .unwrap_or_default()
+    }
+}
+
+/// Measures the time spent on every block from the before-hooks of the contexts through the handler
+/// to the after-hooks and warns about the blocks slower than the threshold.
+///
+/// Put it first in the derived context (or the tuple of the contexts), so the timings include the hooks of the rest of them.
+/// ```no_run
+/// use near_lake_contexts::ProfilingContext;
+/// # use near_lake_framework::near_lake_primitives::block::Block;
+///
+/// # fn main() -> anyhow::Result<()> {
+/// let profiling = ProfilingContext::default().with_slow_block_threshold(std::time::Duration::from_secs(1));
+///
+/// near_lake_framework::LakeBuilder::default()
+///     .mainnet()
+///     .start_block_height(80504433)
+///     .end_block_height(80505433)
+///     .build()?
+///     .run_with_context(handle_block, &profiling)?;
+///
+/// println!("{:?} per block on average", profiling.timings().average());
+/// # Ok(())
+/// # }
+///
+/// async fn handle_block(_block: Block, _ctx: &ProfilingContext) -> anyhow::Result<()> {
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct ProfilingContext {
+    slow_block_threshold: Option<Duration>,
+    timings: Mutex<BlockTimings>,
+}
+
+impl ProfilingContext {
+    /// Warns about the blocks taking longer than the threshold
+    pub fn with_slow_block_threshold(mut self, threshold: Duration) -> Self {
+        self.slow_block_threshold = Some(threshold);
+        self
+    }
+
+    /// Returns the timings of the blocks measured so far
+    pub fn timings(&self) -> BlockTimings {
+        self.timings
+            .lock()
+            .map(|timings| timings.clone())
+            .unwrap_or_default()
+    }
+
+    fn record(&self, block_height: BlockHeight, elapsed: Duration) {
+        if let Ok(mut timings) = self.timings.lock() {
+            timings.blocks += 1;
+            timings.total += elapsed;
+            if elapsed >= timings.max {
+                timings.max = elapsed;
+                timings.slowest_block_height = Some(block_height);
+            }
+        }
+        if self
+            .slow_block_threshold
+            .map_or(false, |threshold| elapsed > threshold)
+        {
+            tracing::warn!(
+                target: "near_lake_contexts",
+                block_height,
+                ?elapsed,
+                "Slow block",
+            );
+        }
+    }
+}
+
+impl LakeContextExt for ProfilingContext {
+    fn execute_before_run(&self, block: &mut Block) {
+        block.scope().insert(BlockStarted {
+            block_height: block.block_height(),
+            started_at: Instant::now(),
+        });
+    }
+
+    fn execute_after_run(&self) {}
+
+    fn execute_after_run_with_scope(&self, scope: &BlockScope) {
+        if let Some(started) = scope.remove::<BlockStarted>() {
+            self.record(started.block_height, started.started_at.elapsed());
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use near_lake_framework::{near_lake_primitives::block::Block, LakeContextExt};
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+    waited: Duration,
+}
+
+/// The token bucket limiting the rate of the calls the handler makes to the external services (RPC, APIs),
+/// shared by all the blocks handled concurrently.
+///
+/// The bucket holds up to `burst` tokens and gets `per_second` tokens every second, every call takes a token.
+/// The total time spent waiting for the tokens is logged once the stream has terminated.
+/// ```no_run
+/// use near_lake_contexts::RateLimitContext;
+/// # use near_lake_framework::near_lake_primitives::block::Block;
+///
+/// # fn main() -> anyhow::Result<()> {
+/// let rpc_rate_limit = RateLimitContext::new(10.0, 20);
+///
+/// near_lake_framework::LakeBuilder::default()
+///     .mainnet()
+///     .start_block_height(80504433)
+///     .concurrency(8)
+///     .build()?
+///     .run_with_context(handle_block, &rpc_rate_limit)?;
+/// # Ok(())
+/// # }
+///
+/// async fn handle_block(mut block: Block, rpc_rate_limit: &RateLimitContext) -> anyhow::Result<()> {
+///     for _receipt in block.receipts() {
+///         rpc_rate_limit.acquire().await;
+///         // query the RPC
+///     }
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug)]
+pub struct RateLimitContext {
+    per_second: f64,
+    burst: f64,
+    bucket: Mutex<Bucket>,
+}
+
+impl RateLimitContext {
+    /// Creates the full bucket of `burst` tokens refilled with `per_second` tokens every second.
+    ///
+    /// Panics if `per_second` is negative, NaN or infinite
+    pub fn new(per_second: f64, burst: u32) -> Self {
+        assert!(
+            per_second.is_finite() && per_second >= 0.0,
+            "The rate limit must be a finite non-negative number of tokens per second, got {}",
+            per_second
+        );
+        let burst = f64::from(burst.max(1));
+        Self {
+            per_second,
+            burst,
+            bucket: Mutex::new(Bucket {
+                tokens: burst,
+                refilled_at: Instant::now(),
+                waited: Duration::ZERO,
+            }),
+        }
+    }
+
+    /// Takes a token if there is one available right away
+    pub fn try_acquire(&self) -> bool {
+        self.take().is_none()
+    }
+
+    /// Takes a token, waiting for the bucket to be refilled if it is empty
+    pub async fn acquire(&self) {
+        while let Some(wait) = self.take() {
+            self.bucket().waited += wait;
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// The total time the calls have spent waiting for the tokens
+    pub fn waited(&self) -> Duration {
+        self.bucket().waited
+    }
+
+    // the bucket is consistent after any statement, so the poisoned one is used as is
+    fn bucket(&self) -> MutexGuard<'_, Bucket> {
+        self.bucket
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Takes a token, returns how long to wait for the next one if the bucket is empty
+    fn take(&self) -> Option<Duration> {
+        let mut bucket = self.bucket();
+        let now = Instant::now();
+        let refill = now.duration_since(bucket.refilled_at).as_secs_f64() * self.per_second;
+        bucket.tokens = (bucket.tokens + refill).min(self.burst);
+        bucket.refilled_at = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return None;
+        }
+        if self.per_second <= 0.0 {
+            return Some(Duration::from_secs(1));
+        }
+        Some(Duration::from_secs_f64(
+            (1.0 - bucket.tokens) / self.per_second,
+        ))
+    }
+}
+
+impl LakeContextExt for RateLimitContext {
+    fn execute_before_run(&self, _block: &mut Block) {}
+
+    fn execute_after_run(&self) {}
+
+    fn finalize(&self) -> futures::future::BoxFuture<'_, ()> {
+        let waited = self.waited();
+        Box::pin(async move {
+            tracing::info!(
+                target: "near_lake_contexts",
+                ?waited,
+                "Total time spent waiting for the rate limit",
+            );
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn takes_burst_tokens_right_away() {
+        let rate_limit = RateLimitContext::new(0.0, 3);
+
+        assert!((0..3).all(|_| rate_limit.try_acquire()));
+        assert!(!rate_limit.try_acquire());
+    }
+
+    #[tokio::test]
+    async fn waits_for_refill() {
+        let rate_limit = RateLimitContext::new(100.0, 1);
+
+        rate_limit.acquire().await;
+        assert_eq!(rate_limit.waited(), Duration::ZERO);
+        let started_at = Instant::now();
+        rate_limit.acquire().await;
+        assert!(rate_limit.waited() > Duration::ZERO);
+        assert!(started_at.elapsed() >= Duration::from_millis(5));
+    }
+
+    #[test]
+    fn keeps_limiting_with_poisoned_lock() {
+        let rate_limit = RateLimitContext::new(0.0, 1);
+        let _ = std::thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    let _bucket = rate_limit.bucket.lock().unwrap();
+                    panic!("poison the lock");
+                })
+                .join()
+        });
+        assert!(rate_limit.bucket.is_poisoned());
+
+        assert!(rate_limit.try_acquire());
+        assert!(!rate_limit.try_acquire());
+    }
+
+    #[test]
+    #[should_panic(expected = "finite non-negative")]
+    fn refuses_nan_rate() {
+        RateLimitContext::new(f64::NAN, 1);
+    }
+}
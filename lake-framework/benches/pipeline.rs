@@ -0,0 +1,192 @@
+//! Benchmarks of the fetch/parse pipeline: JSON deserialization of the blocks, construction of the
+//! [Block](near_lake_framework::near_lake_primitives::block::Block) caches and the streamer loop
+//! against an in-memory [S3Client].
+//!
+//! Run with `cargo bench -p near-lake-framework`
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use aws_sdk_s3::error::SdkError;
+use aws_sdk_s3::operation::get_object::{GetObjectError, GetObjectOutput};
+use aws_sdk_s3::operation::head_object::{HeadObjectError, HeadObjectOutput};
+use aws_sdk_s3::operation::list_objects_v2::{ListObjectsV2Error, ListObjectsV2Output};
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::types::CommonPrefix;
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use near_lake_framework::near_indexer_primitives::{
+    views::BlockView, CryptoHash, IndexerShard, StreamerMessage,
+};
+use near_lake_framework::near_lake_primitives::block::Block;
+use near_lake_framework::{LakeBuilder, S3Client};
+
+const BLOCK_JSON: &str = include_str!("../blocks/000000879765/block.json");
+const SHARD_JSON: &str = include_str!("../blocks/000000879765/shard_0.json");
+
+/// How many times the receipts, transactions and state changes of the sample shard are repeated
+/// to get a large block
+const LARGE_SHARD_FACTOR: usize = 500;
+
+const STREAMED_BLOCKS: u64 = 200;
+const START_BLOCK_HEIGHT: u64 = 1_000;
+
+fn large_shard_json() -> String {
+    let mut shard: serde_json::Value = serde_json::from_str(SHARD_JSON).unwrap();
+    for path in [
+        "/receipt_execution_outcomes",
+        "/state_changes",
+        "/chunk/transactions",
+        "/chunk/receipts",
+    ] {
+        let items = shard.pointer_mut(path).unwrap().as_array_mut().unwrap();
+        *items = items
+            .iter()
+            .cycle()
+            .take(items.len() * LARGE_SHARD_FACTOR)
+            .cloned()
+            .collect();
+    }
+    shard.to_string()
+}
+
+fn streamer_message(block_json: &str, shard_json: &str) -> StreamerMessage {
+    StreamerMessage {
+        block: serde_json::from_str(block_json).unwrap(),
+        shards: vec![serde_json::from_str(shard_json).unwrap()],
+    }
+}
+
+fn deserialization(c: &mut Criterion) {
+    let large_shard_json = large_shard_json();
+    let mut group = c.benchmark_group("deserialization");
+    group.throughput(Throughput::Bytes(BLOCK_JSON.len() as u64));
+    group.bench_function("block", |b| {
+        b.iter(|| serde_json::from_str::<BlockView>(BLOCK_JSON).unwrap())
+    });
+    group.throughput(Throughput::Bytes(large_shard_json.len() as u64));
+    group.bench_function("large_shard", |b| {
+        b.iter(|| serde_json::from_str::<IndexerShard>(&large_shard_json).unwrap())
+    });
+    group.finish();
+}
+
+fn block_caches(c: &mut Criterion) {
+    let large_shard_json = large_shard_json();
+    let mut group = c.benchmark_group("block_caches");
+    group.bench_function("large_block", |b| {
+        b.iter_batched(
+            || streamer_message(BLOCK_JSON, &large_shard_json),
+            |streamer_message| {
+                let mut block: Block = streamer_message.into();
+                block.receipts().count()
+                    + block.transactions().count()
+                    + block.actions().count()
+                    + block.events().count()
+                    + block.state_changes().count()
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+/// Serves a chain of the sample blocks from memory
+#[derive(Debug)]
+struct InMemoryS3Client {
+    objects: HashMap<String, Vec<u8>>,
+}
+
+impl InMemoryS3Client {
+    fn new(start_block_height: u64, blocks: u64) -> Self {
+        let mut objects = HashMap::new();
+        let mut prev_hash = CryptoHash::default();
+        for block_height in start_block_height..start_block_height + blocks {
+            let hash = CryptoHash::hash_bytes(&block_height.to_le_bytes());
+            let mut block: serde_json::Value = serde_json::from_str(BLOCK_JSON).unwrap();
+            block["header"]["height"] = block_height.into();
+            block["header"]["hash"] = serde_json::to_value(hash).unwrap();
+            block["header"]["prev_hash"] = serde_json::to_value(prev_hash).unwrap();
+            prev_hash = hash;
+
+            objects.insert(
+                format!("{:0>12}/block.json", block_height),
+                block.to_string().into_bytes(),
+            );
+            objects.insert(
+                format!("{:0>12}/shard_0.json", block_height),
+                SHARD_JSON.as_bytes().to_vec(),
+            );
+        }
+        Self { objects }
+    }
+}
+
+#[async_trait]
+impl S3Client for InMemoryS3Client {
+    async fn get_object(
+        &self,
+        _bucket: &str,
+        prefix: &str,
+    ) -> Result<GetObjectOutput, SdkError<GetObjectError>> {
+        let object = self.objects.get(prefix).cloned().unwrap_or_default();
+        Ok(GetObjectOutput::builder()
+            .body(ByteStream::from(object))
+            .build())
+    }
+
+    async fn list_objects(
+        &self,
+        _bucket: &str,
+        start_after: &str,
+        _continuation_token: Option<String>,
+    ) -> Result<ListObjectsV2Output, SdkError<ListObjectsV2Error>> {
+        let mut common_prefixes: Vec<_> = self
+            .objects
+            .keys()
+            .filter_map(|key| key.strip_suffix("block.json"))
+            .filter(|prefix| *prefix > start_after)
+            .map(|prefix| prefix.to_string())
+            .collect();
+        common_prefixes.sort();
+        Ok(ListObjectsV2Output::builder()
+            .set_common_prefixes(Some(
+                common_prefixes
+                    .into_iter()
+                    .map(|prefix| CommonPrefix::builder().prefix(prefix).build())
+                    .collect(),
+            ))
+            .build())
+    }
+
+    async fn head_object(
+        &self,
+        _bucket: &str,
+        _prefix: &str,
+    ) -> Result<HeadObjectOutput, SdkError<HeadObjectError>> {
+        Ok(HeadObjectOutput::builder().build())
+    }
+}
+
+fn streamer(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let mut group = c.benchmark_group("streamer");
+    group.throughput(Throughput::Elements(STREAMED_BLOCKS));
+    group.sample_size(10);
+    group.bench_function("in_memory", |b| {
+        b.to_async(&runtime).iter(|| async {
+            LakeBuilder::default()
+                .mainnet()
+                .s3_client(InMemoryS3Client::new(START_BLOCK_HEIGHT, STREAMED_BLOCKS))
+                .start_block_height(START_BLOCK_HEIGHT)
+                .end_block_height(START_BLOCK_HEIGHT + STREAMED_BLOCKS - 1)
+                .build()
+                .unwrap()
+                .run_async(|_block| async { Ok::<_, std::io::Error>(()) })
+                .await
+                .unwrap()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, deserialization, block_caches, streamer);
+criterion_main!(benches);
@@ -0,0 +1,127 @@
+//! Admin interface over a Unix socket to control the running indexer with the line commands:
+//! `status`, `pause`, `resume`, `seek <block_height>` and `limit <blocks_per_second|off>`. Every command is answered with a JSON line.
+//! ```text
+//! $ echo status | nc -U /run/indexer.sock
+//! {"blocks_processed":42,"elapsed_secs":10,"handler_failures":0,"in_flight":1,"last_block_height":112205814,"max_blocks_per_second":null,"paused":false,"queued":99,"stream_failures":0}
+//! ```
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+
+use crate::control::ControlHandle;
+use crate::types::LakeError;
+
+/// Stops the listener and removes the socket file on drop
+pub(crate) struct AdminSocket {
+    path: std::path::PathBuf,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl Drop for AdminSocket {
+    fn drop(&mut self) {
+        self.task.abort();
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Binds the Unix socket to the path (replacing the stale socket file, if any) and serves
+/// the commands in the background until the returned [AdminSocket] is dropped
+pub(crate) fn serve(
+    path: &std::path::Path,
+    control: ControlHandle,
+) -> Result<AdminSocket, LakeError> {
+    let _ = std::fs::remove_file(path);
+    let listener =
+        tokio::net::UnixListener::bind(path).map_err(|err| LakeError::InternalError {
+            error_message: format!(
+                "Failed to bind the admin socket to {}: {}",
+                path.display(),
+                err
+            ),
+        })?;
+    tracing::info!(target: crate::LAKE_FRAMEWORK, path = %path.display(), "Serving the admin socket");
+
+    Ok(AdminSocket {
+        path: path.to_path_buf(),
+        task: tokio::spawn(async move {
+            loop {
+                let Ok((stream, _)) = listener.accept().await else {
+                    continue;
+                };
+                let control = control.clone();
+                tokio::spawn(async move {
+                    if let Err(err) = serve_connection(stream, &control).await {
+                        tracing::debug!(
+                            target: crate::LAKE_FRAMEWORK,
+                            error = %err,
+                            "Failed to serve the admin connection",
+                        );
+                    }
+                });
+            }
+        }),
+    })
+}
+
+async fn serve_connection(
+    stream: tokio::net::UnixStream,
+    control: &ControlHandle,
+) -> std::io::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = tokio::io::BufReader::new(reader).lines();
+    while let Some(line) = lines.next_line().await? {
+        let mut response = execute(line.trim(), control).to_string();
+        response.push('\n');
+        writer.write_all(response.as_bytes()).await?;
+    }
+    Ok(())
+}
+
+fn execute(command: &str, control: &ControlHandle) -> serde_json::Value {
+    let mut args = command.split_whitespace();
+    match (args.next(), args.next()) {
+        (Some("status"), None) => {
+            let status = control.status();
+            serde_json::json!({
+                "paused": status.paused,
+                "blocks_processed": status.blocks_processed,
+                "last_block_height": status.last_block_height,
+                "in_flight": status.in_flight,
+                "queued": status.queued,
+                "handler_failures": status.handler_failures,
+                "stream_failures": status.stream_failures,
+                "elapsed_secs": status.elapsed.as_secs(),
+                "max_blocks_per_second": control.max_blocks_per_second(),
+            })
+        }
+        (Some("pause"), None) => {
+            control.pause();
+            serde_json::json!({ "ok": true })
+        }
+        (Some("resume"), None) => {
+            control.resume();
+            serde_json::json!({ "ok": true })
+        }
+        (Some("seek"), Some(block_height)) => match block_height.parse() {
+            Ok(block_height) => {
+                control.seek(block_height);
+                serde_json::json!({ "ok": true })
+            }
+            Err(_) => {
+                serde_json::json!({ "error": format!("Invalid block height: {}", block_height) })
+            }
+        },
+        (Some("limit"), Some("off")) => {
+            control.set_max_blocks_per_second(None);
+            serde_json::json!({ "ok": true })
+        }
+        (Some("limit"), Some(max_blocks_per_second)) => match max_blocks_per_second.parse() {
+            Ok(max_blocks_per_second) if max_blocks_per_second > 0.0 => {
+                control.set_max_blocks_per_second(Some(max_blocks_per_second));
+                serde_json::json!({ "ok": true })
+            }
+            _ => serde_json::json!({
+                "error": format!("Invalid blocks per second limit: {}", max_blocks_per_second)
+            }),
+        },
+        _ => serde_json::json!({ "error": format!("Unknown command: {}", command) }),
+    }
+}
@@ -0,0 +1,184 @@
+//! Serving the blocks out of a tar archive of a block height range instead of the bucket,
+//! see [ArchiveS3Client]
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{Read, Seek};
+
+use async_trait::async_trait;
+use aws_sdk_s3::operation::get_object::{GetObjectError, GetObjectOutput};
+use aws_sdk_s3::operation::head_object::{HeadObjectError, HeadObjectOutput};
+use aws_sdk_s3::operation::list_objects_v2::{ListObjectsV2Error, ListObjectsV2Output};
+
+use crate::s3_fetchers::S3Client;
+
+/// The same page size the S3 `List` requests return
+const LIST_PAGE_SIZE: usize = 1000;
+
+/// [S3Client] reading the objects out of a single (uncompressed) tar archive of a block height range,
+/// so the reproducible datasets can be shipped for the analysis and the courses without the S3 access.
+///
+/// The archive holds the objects under the same keys as the bucket does, e.g. `000088444526/block.json`
+/// and `000088444526/shard_0.json` for the [DefaultKeyLayout](crate::DefaultKeyLayout). The bucket name is ignored.
+/// Only the index of the archive is kept in memory, the objects are read from the disk on demand.
+///
+/// *Note*: The archive ends at some height, so set the [LakeBuilder::end_block_height](crate::LakeBuilder::end_block_height)
+/// to its last block height, otherwise the stream waits for the new blocks once the archive is over
+/// ```no_run
+/// # fn main() -> anyhow::Result<()> {
+///    near_lake_framework::LakeBuilder::default()
+///        .mainnet()
+///        .s3_client(near_lake_framework::ArchiveS3Client::open("blocks-88444526-88445525.tar")?)
+///        .start_block_height(88444526)
+///        .end_block_height(88445525)
+///        .build()?
+///        .run(handle_block)?;
+///    Ok(())
+/// # }
+///
+/// # async fn handle_block(_block: near_lake_primitives::block::Block) -> anyhow::Result<()> { Ok(()) }
+/// ```
+#[derive(Debug)]
+pub struct ArchiveS3Client {
+    path: std::path::PathBuf,
+    /// The position of the object data in the archive and its size by the object key
+    objects: BTreeMap<String, (u64, u64)>,
+    /// The top-level prefixes of the keys (e.g. `000088444526/`) the `List` requests return
+    prefixes: BTreeSet<String>,
+}
+
+impl ArchiveS3Client {
+    /// Reads the index of the tar archive
+    pub fn open(path: impl Into<std::path::PathBuf>) -> std::io::Result<Self> {
+        let path = path.into();
+        let mut archive = tar::Archive::new(std::fs::File::open(&path)?);
+        let mut objects = BTreeMap::new();
+        let mut prefixes = BTreeSet::new();
+
+        for entry in archive.entries()? {
+            let entry = entry?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            let entry_path = entry.path()?;
+            let key = entry_path
+                .to_string_lossy()
+                .trim_start_matches("./")
+                .to_string();
+            if let Some((prefix, _)) = key.split_once('/') {
+                prefixes.insert(format!("{}/", prefix));
+            }
+            objects.insert(key, (entry.raw_file_position(), entry.size()));
+        }
+
+        tracing::info!(
+            target: crate::LAKE_FRAMEWORK,
+            path = %path.display(),
+            objects = objects.len(),
+            "Opened the blocks archive",
+        );
+        Ok(Self {
+            path,
+            objects,
+            prefixes,
+        })
+    }
+
+    async fn read_object(&self, position: u64, size: u64) -> std::io::Result<Vec<u8>> {
+        let path = self.path.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut file = std::fs::File::open(path)?;
+            file.seek(std::io::SeekFrom::Start(position))?;
+            let mut body = vec![0; size as usize];
+            file.read_exact(&mut body)?;
+            Ok(body)
+        })
+        .await
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?
+    }
+}
+
+/// The `404 Not Found` response for the objects missing in the archive,
+/// so they are classified as [ErrorKind::NotFoundYet](crate::ErrorKind::NotFoundYet) as the bucket ones are
+fn not_found<E>(error: E) -> aws_sdk_s3::error::SdkError<E> {
+    aws_sdk_s3::error::SdkError::service_error(
+        error,
+        aws_sdk_s3::config::http::HttpResponse::new(
+            404u16.try_into().expect("404 is a valid status code"),
+            aws_smithy_types::body::SdkBody::empty(),
+        ),
+    )
+}
+
+#[async_trait]
+impl S3Client for ArchiveS3Client {
+    async fn get_object(
+        &self,
+        _bucket: &str,
+        prefix: &str,
+    ) -> Result<GetObjectOutput, aws_sdk_s3::error::SdkError<GetObjectError>> {
+        let Some((position, size)) = self.objects.get(prefix).copied() else {
+            return Err(not_found(GetObjectError::NoSuchKey(
+                aws_sdk_s3::types::error::NoSuchKey::builder().build(),
+            )));
+        };
+        let body = self.read_object(position, size).await.map_err(|err| {
+            aws_sdk_s3::error::SdkError::dispatch_failure(aws_sdk_s3::error::ConnectorError::io(
+                err.into(),
+            ))
+        })?;
+        Ok(GetObjectOutput::builder()
+            .body(aws_sdk_s3::primitives::ByteStream::from(body))
+            .build())
+    }
+
+    async fn list_objects(
+        &self,
+        _bucket: &str,
+        start_after: &str,
+        continuation_token: Option<String>,
+    ) -> Result<ListObjectsV2Output, aws_sdk_s3::error::SdkError<ListObjectsV2Error>> {
+        // the continuation token is the last prefix of the previous page
+        let start_after = continuation_token.as_deref().unwrap_or(start_after);
+        let mut prefixes = self
+            .prefixes
+            .range::<str, _>((
+                std::ops::Bound::Excluded(start_after),
+                std::ops::Bound::Unbounded,
+            ))
+            .take(LIST_PAGE_SIZE + 1)
+            .cloned()
+            .collect::<Vec<_>>();
+        let next_continuation_token = if prefixes.len() > LIST_PAGE_SIZE {
+            prefixes.truncate(LIST_PAGE_SIZE);
+            prefixes.last().cloned()
+        } else {
+            None
+        };
+
+        Ok(ListObjectsV2Output::builder()
+            .set_common_prefixes(Some(
+                prefixes
+                    .into_iter()
+                    .map(|prefix| {
+                        aws_sdk_s3::types::CommonPrefix::builder()
+                            .prefix(prefix)
+                            .build()
+                    })
+                    .collect(),
+            ))
+            .set_next_continuation_token(next_continuation_token)
+            .build())
+    }
+
+    async fn head_object(
+        &self,
+        _bucket: &str,
+        prefix: &str,
+    ) -> Result<HeadObjectOutput, aws_sdk_s3::error::SdkError<HeadObjectError>> {
+        if !self.objects.contains_key(prefix) {
+            return Err(not_found(HeadObjectError::NotFound(
+                aws_sdk_s3::types::error::NotFound::builder().build(),
+            )));
+        }
+        Ok(HeadObjectOutput::builder().build())
+    }
+}
@@ -0,0 +1,377 @@
+//! Sharing of the fetched objects with the peer indexers over HTTP,
+//! see [LakeBuilder::block_share_addr](crate::LakeBuilder::block_share_addr)
+//!
+//! The listener speaks the subset of the S3 protocol the built-in client needs (`GetObject`, `HeadObject`
+//! and `ListObjectsV2` with the path-style addressing), so the peers point their
+//! [LakeBuilder::cache_endpoint](crate::LakeBuilder::cache_endpoint) at it.
+//!
+//! The listener is unauthenticated, so it serves the block and shard objects of the indexer's own bucket only
+//! and must be reachable from the private network of the fleet only.
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use aws_sdk_s3::error::SdkError;
+use aws_sdk_s3::operation::get_object::{GetObjectError, GetObjectOutput};
+use aws_sdk_s3::operation::head_object::{HeadObjectError, HeadObjectOutput};
+use aws_sdk_s3::operation::list_objects_v2::{ListObjectsV2Error, ListObjectsV2Output};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::s3_fetchers::{KeyLayout, S3Client};
+use crate::types::{ErrorKind, LakeError};
+
+/// How many recently fetched objects to keep in memory for the peers
+const SHARED_OBJECTS: usize = 1000;
+
+/// The shard ids above this one are not served to the peers
+const MAX_SHARD_ID: u64 = 1024;
+
+/// [S3Client] keeping the recently fetched objects in memory, so the objects fetched by the indexer itself
+/// or by any of the peers are fetched from the bucket only once
+#[derive(Debug)]
+pub(crate) struct SharingS3Client {
+    inner: Arc<dyn S3Client>,
+    objects: Mutex<SharedObjects>,
+}
+
+#[derive(Debug, Default)]
+struct SharedObjects {
+    objects: HashMap<String, Vec<u8>>,
+    order: VecDeque<String>,
+}
+
+impl SharingS3Client {
+    pub(crate) fn new(inner: Arc<dyn S3Client>) -> Self {
+        Self {
+            inner,
+            objects: Mutex::new(SharedObjects::default()),
+        }
+    }
+
+    fn shared_object(&self, key: &str) -> Option<Vec<u8>> {
+        self.objects.lock().ok()?.objects.get(key).cloned()
+    }
+
+    fn share_object(&self, key: String, body: Vec<u8>) {
+        let Ok(mut shared) = self.objects.lock() else {
+            return;
+        };
+        if shared.objects.insert(key.clone(), body).is_none() {
+            shared.order.push_back(key);
+        }
+        while shared.order.len() > SHARED_OBJECTS {
+            if let Some(evicted) = shared.order.pop_front() {
+                shared.objects.remove(&evicted);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl S3Client for SharingS3Client {
+    async fn get_object(
+        &self,
+        bucket: &str,
+        prefix: &str,
+    ) -> Result<GetObjectOutput, SdkError<GetObjectError>> {
+        let key = format!("{}/{}", bucket, prefix);
+        let body = match self.shared_object(&key) {
+            Some(body) => body,
+            None => {
+                let output = self.inner.get_object(bucket, prefix).await?;
+                let body = output
+                    .body
+                    .collect()
+                    .await
+                    .map_err(|err| {
+                        SdkError::dispatch_failure(aws_sdk_s3::error::ConnectorError::io(
+                            err.into(),
+                        ))
+                    })?
+                    .to_vec();
+                self.share_object(key, body.clone());
+                body
+            }
+        };
+        Ok(GetObjectOutput::builder()
+            .body(aws_sdk_s3::primitives::ByteStream::from(body))
+            .build())
+    }
+
+    async fn list_objects(
+        &self,
+        bucket: &str,
+        start_after: &str,
+        continuation_token: Option<String>,
+    ) -> Result<ListObjectsV2Output, SdkError<ListObjectsV2Error>> {
+        self.inner
+            .list_objects(bucket, start_after, continuation_token)
+            .await
+    }
+
+    async fn head_object(
+        &self,
+        bucket: &str,
+        prefix: &str,
+    ) -> Result<HeadObjectOutput, SdkError<HeadObjectError>> {
+        self.inner.head_object(bucket, prefix).await
+    }
+}
+
+/// Stops the listener on drop
+pub(crate) struct BlockShareServer(tokio::task::JoinHandle<()>);
+
+impl Drop for BlockShareServer {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// The bucket and the keys the peers may request, so the listener doesn't proxy arbitrary requests
+/// with the credentials of the indexer
+pub(crate) struct SharedKeys {
+    pub(crate) bucket: String,
+    pub(crate) key_layout: Arc<dyn KeyLayout>,
+}
+
+impl SharedKeys {
+    fn is_shared(&self, key: &str) -> bool {
+        let Some(block_height) = self.key_layout.parse_block_height(key) else {
+            return false;
+        };
+        key == self.key_layout.block_key(block_height)
+            || (0..=MAX_SHARD_ID)
+                .any(|shard_id| key == self.key_layout.shard_key(block_height, shard_id))
+    }
+}
+
+/// Binds the listener to the address and serves the block and shard objects of the bucket to the peers
+/// in the background until the returned [BlockShareServer] is dropped
+pub(crate) async fn serve(
+    addr: std::net::SocketAddr,
+    client: Arc<SharingS3Client>,
+    shared_keys: SharedKeys,
+) -> Result<BlockShareServer, LakeError> {
+    let listener =
+        tokio::net::TcpListener::bind(addr)
+            .await
+            .map_err(|err| LakeError::InternalError {
+                error_message: format!(
+                    "Failed to bind the block share server to {}: {}",
+                    addr, err
+                ),
+            })?;
+    tracing::info!(target: crate::LAKE_FRAMEWORK, %addr, "Sharing the fetched objects with the peers");
+
+    let shared_keys = Arc::new(shared_keys);
+    Ok(BlockShareServer(tokio::spawn(async move {
+        loop {
+            let Ok((stream, _)) = listener.accept().await else {
+                continue;
+            };
+            let client = client.clone();
+            let shared_keys = shared_keys.clone();
+            tokio::spawn(async move {
+                if let Err(err) = respond(stream, &*client, &shared_keys).await {
+                    tracing::debug!(
+                        target: crate::LAKE_FRAMEWORK,
+                        error = %err,
+                        "Failed to respond to the peer",
+                    );
+                }
+            });
+        }
+    })))
+}
+
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn ok(content_type: &'static str, body: Vec<u8>) -> Self {
+        Self {
+            status: "200 OK",
+            content_type,
+            body,
+        }
+    }
+
+    fn error(status: &'static str, code: &str) -> Self {
+        Self {
+            status,
+            content_type: "application/xml",
+            body: format!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?><Error><Code>{}</Code></Error>",
+                code
+            )
+            .into_bytes(),
+        }
+    }
+
+    /// Answers the failed request to the bucket, so the peer classifies the error the same way
+    fn from_sdk_error<E>(err: &SdkError<E>) -> Self {
+        match ErrorKind::from_sdk_error(err) {
+            ErrorKind::NotFoundYet => Self::error("404 Not Found", "NoSuchKey"),
+            ErrorKind::Unauthorized => Self::error("403 Forbidden", "AccessDenied"),
+            _ => Self::error("503 Service Unavailable", "ServiceUnavailable"),
+        }
+    }
+}
+
+async fn respond(
+    mut stream: tokio::net::TcpStream,
+    client: &dyn S3Client,
+    shared_keys: &SharedKeys,
+) -> std::io::Result<()> {
+    // the request line is all we need, the client sends the requests without a body
+    let mut request = [0u8; 4096];
+    let len = stream.read(&mut request).await?;
+    let mut request_line = std::str::from_utf8(&request[..len])
+        .ok()
+        .and_then(|request| request.lines().next())
+        .unwrap_or_default()
+        .split_whitespace();
+    let method = request_line.next().unwrap_or_default();
+    let target = request_line.next().unwrap_or_default();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let path = percent_decode(path.trim_start_matches('/'));
+    let (bucket, key) = path.split_once('/').unwrap_or((&path, ""));
+
+    let response = match (method, key) {
+        // not `403`, the peers wait for the forbidden block data to appear, see `ErrorKind::from_object_sdk_error`
+        ("GET" | "HEAD", _) if bucket != shared_keys.bucket => {
+            Response::error("400 Bad Request", "InvalidBucketName")
+        }
+        ("GET", "") => list_objects(client, bucket, query).await,
+        ("GET" | "HEAD", key) if !shared_keys.is_shared(key) => {
+            Response::error("400 Bad Request", "InvalidArgument")
+        }
+        ("GET", key) => match client.get_object(bucket, key).await {
+            Ok(output) => match output.body.collect().await {
+                Ok(body) => Response::ok("application/json", body.to_vec()),
+                Err(_) => Response::error("503 Service Unavailable", "ServiceUnavailable"),
+            },
+            Err(err) => Response::from_sdk_error(&err),
+        },
+        ("HEAD", key) => match client.head_object(bucket, key).await {
+            Ok(_) => Response::ok("application/json", vec![]),
+            Err(err) => Response::from_sdk_error(&err),
+        },
+        _ => Response::error("405 Method Not Allowed", "MethodNotAllowed"),
+    };
+
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        response.content_type,
+        response.body.len(),
+    );
+    stream.write_all(head.as_bytes()).await?;
+    if method != "HEAD" {
+        stream.write_all(&response.body).await?;
+    }
+    stream.shutdown().await
+}
+
+/// Answers the `ListObjectsV2` request with the XML the S3 does
+async fn list_objects(client: &dyn S3Client, bucket: &str, query: &str) -> Response {
+    let param = |name: &str| {
+        query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| percent_decode(value))
+    };
+    let output = match client
+        .list_objects(
+            bucket,
+            &param("start-after").unwrap_or_default(),
+            param("continuation-token"),
+        )
+        .await
+    {
+        Ok(output) => output,
+        Err(err) => return Response::from_sdk_error(&err),
+    };
+
+    let mut body = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+         <ListBucketResult xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\">\
+         <Name>{}</Name><Delimiter>/</Delimiter><KeyCount>{}</KeyCount><IsTruncated>{}</IsTruncated>",
+        xml_escape(bucket),
+        output.common_prefixes().len(),
+        output.next_continuation_token().is_some(),
+    );
+    if let Some(next_continuation_token) = output.next_continuation_token() {
+        body.push_str(&format!(
+            "<NextContinuationToken>{}</NextContinuationToken>",
+            xml_escape(next_continuation_token)
+        ));
+    }
+    for prefix in output
+        .common_prefixes()
+        .iter()
+        .filter_map(|common_prefix| common_prefix.prefix())
+    {
+        body.push_str(&format!(
+            "<CommonPrefixes><Prefix>{}</Prefix></CommonPrefixes>",
+            xml_escape(prefix)
+        ));
+    }
+    body.push_str("</ListBucketResult>");
+    Response::ok("application/xml", body.into_bytes())
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escaped = (bytes[index] == b'%')
+            .then(|| bytes.get(index + 1..index + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn shares_block_and_shard_objects_only() {
+        let shared_keys = SharedKeys {
+            bucket: "near-lake-data-mainnet".to_string(),
+            key_layout: Arc::new(crate::DefaultKeyLayout),
+        };
+
+        assert!(shared_keys.is_shared("000000879765/block.json"));
+        assert!(shared_keys.is_shared("000000879765/shard_3.json"));
+        assert!(!shared_keys.is_shared("000000879765/shard_4096.json"));
+        assert!(!shared_keys.is_shared("000000879765/../secrets.json"));
+        assert!(!shared_keys.is_shared("indexer-state/last_height"));
+        assert!(!shared_keys.is_shared(""));
+    }
+}
@@ -0,0 +1,133 @@
+//! The channel the streamer delivers the blocks to, see [ChannelStrategy]
+use std::sync::Arc;
+
+use tokio::sync::{broadcast, mpsc};
+
+use near_lake_primitives::StreamerMessage;
+
+/// The type of the channel the streamer delivers the blocks to the handlers through.
+/// Default: [ChannelStrategy::Bounded] with the capacity of [LakeBuilder::blocks_preload_pool_size](crate::LakeBuilder::blocks_preload_pool_size)
+///
+/// The handlers own the blocks they receive, so there is no broadcast strategy: to fan the blocks out
+/// to several consumers, stream them into a `tokio::sync::broadcast` channel with [Lake::stream_into](crate::Lake::stream_into)
+/// ```no_run
+/// use near_lake_framework::ChannelStrategy;
+///
+/// # fn main() -> anyhow::Result<()> {
+///    let lake = near_lake_framework::LakeBuilder::default()
+///        .testnet()
+///        .start_block_height(112205773)
+///        .channel_strategy(ChannelStrategy::Unbounded)
+///        .build()?;
+///    # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub enum ChannelStrategy {
+    /// The bounded channel with the given capacity: the streamer waits for the handlers once it is full
+    Bounded(usize),
+    /// The unbounded channel: the streamer never waits for the handlers, so the memory grows
+    /// as long as they are slower than the stream
+    Unbounded,
+}
+
+/// The sending half of the channel the streamer writes the blocks to, see [Lake::stream_into](crate::Lake::stream_into).
+/// It is created from the sender of the tokio bounded, unbounded or broadcast channel
+#[derive(Clone, Debug)]
+pub struct StreamerMessageSender {
+    sender: Sender,
+}
+
+#[derive(Clone, Debug)]
+enum Sender {
+    Bounded(mpsc::Sender<StreamerMessage>),
+    Unbounded(mpsc::UnboundedSender<StreamerMessage>),
+    Broadcast(broadcast::Sender<Arc<StreamerMessage>>),
+}
+
+impl StreamerMessageSender {
+    /// Sends the block, waiting for the capacity of the bounded channel.
+    /// Returns `false` if the channel is closed (there are no receivers left)
+    pub async fn send(&self, streamer_message: StreamerMessage) -> bool {
+        match &self.sender {
+            Sender::Bounded(sender) => sender.send(streamer_message).await.is_ok(),
+            Sender::Unbounded(sender) => sender.send(streamer_message).is_ok(),
+            Sender::Broadcast(sender) => sender.send(Arc::new(streamer_message)).is_ok(),
+        }
+    }
+
+    /// Whether the channel is closed (there are no receivers left)
+    pub fn is_closed(&self) -> bool {
+        match &self.sender {
+            Sender::Bounded(sender) => sender.is_closed(),
+            Sender::Unbounded(sender) => sender.is_closed(),
+            Sender::Broadcast(sender) => sender.receiver_count() == 0,
+        }
+    }
+
+    fn new(sender: Sender) -> Self {
+        Self { sender }
+    }
+}
+
+impl From<mpsc::Sender<StreamerMessage>> for StreamerMessageSender {
+    fn from(sender: mpsc::Sender<StreamerMessage>) -> Self {
+        Self::new(Sender::Bounded(sender))
+    }
+}
+
+impl From<mpsc::UnboundedSender<StreamerMessage>> for StreamerMessageSender {
+    fn from(sender: mpsc::UnboundedSender<StreamerMessage>) -> Self {
+        Self::new(Sender::Unbounded(sender))
+    }
+}
+
+impl From<broadcast::Sender<Arc<StreamerMessage>>> for StreamerMessageSender {
+    fn from(sender: broadcast::Sender<Arc<StreamerMessage>>) -> Self {
+        Self::new(Sender::Broadcast(sender))
+    }
+}
+
+/// The receiving half of the channel the [Lake](crate::Lake) reads the blocks from
+pub(crate) enum StreamerMessageReceiver {
+    Bounded(mpsc::Receiver<StreamerMessage>),
+    Unbounded(mpsc::UnboundedReceiver<StreamerMessage>),
+}
+
+impl StreamerMessageReceiver {
+    /// Polls to receive the next block, `None` once the channel is closed
+    pub(crate) fn poll_recv(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<StreamerMessage>> {
+        match self {
+            Self::Bounded(receiver) => receiver.poll_recv(cx),
+            Self::Unbounded(receiver) => receiver.poll_recv(cx),
+        }
+    }
+
+    /// The number of the blocks waiting in the channel
+    pub(crate) fn len(&self) -> usize {
+        match self {
+            Self::Bounded(receiver) => receiver.len(),
+            Self::Unbounded(receiver) => receiver.len(),
+        }
+    }
+}
+
+/// Creates the channel of the configured [ChannelStrategy]
+pub(crate) fn channel(config: &crate::Lake) -> (StreamerMessageSender, StreamerMessageReceiver) {
+    match config
+        .channel_strategy
+        .unwrap_or(ChannelStrategy::Bounded(config.blocks_preload_pool_size))
+    {
+        ChannelStrategy::Bounded(capacity) => {
+            let (sender, receiver) = mpsc::channel(capacity);
+            (sender.into(), StreamerMessageReceiver::Bounded(receiver))
+        }
+        ChannelStrategy::Unbounded => {
+            let (sender, receiver) = mpsc::unbounded_channel();
+            (sender.into(), StreamerMessageReceiver::Unbounded(receiver))
+        }
+    }
+}
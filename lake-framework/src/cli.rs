@@ -0,0 +1,118 @@
+//! The standard command line and environment configuration of the indexers, see [LakeBuilder::from_args]
+use crate::types::{LakeBuilder, LakeError};
+
+const USAGE: &str = "\
+Options (each one can be set with the environment variable instead):
+    --mainnet | --testnet | --betanet   the chain to index                  NEAR_LAKE_CHAIN_ID
+    --start-block-height <HEIGHT>       the block height to start from      NEAR_LAKE_START_BLOCK_HEIGHT
+    --end-block-height <HEIGHT>         the block height to stop at         NEAR_LAKE_END_BLOCK_HEIGHT
+    --concurrency <N>                   the blocks handled concurrently     NEAR_LAKE_CONCURRENCY
+    --help                              prints this message";
+
+impl LakeBuilder {
+    /// Sets up the builder from the standard command line arguments of the process, falling back to the environment variables:
+    /// - `--mainnet`, `--testnet` or `--betanet` (`NEAR_LAKE_CHAIN_ID`)
+    /// - `--start-block-height <HEIGHT>` (`NEAR_LAKE_START_BLOCK_HEIGHT`)
+    /// - `--end-block-height <HEIGHT>` (`NEAR_LAKE_END_BLOCK_HEIGHT`)
+    /// - `--concurrency <N>` (`NEAR_LAKE_CONCURRENCY`)
+    ///
+    /// The values can be passed as `--option=value` too. `--help` prints the usage and exits the process.
+    /// The rest of the builder can be set up as usual, and it is what the [main](crate::main) attribute uses
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///    // NEAR_LAKE_CHAIN_ID=testnet my-indexer --start-block-height 112205773
+    ///    near_lake_framework::LakeBuilder::from_args()?
+    ///        .build()?
+    ///        .run(handle_block)?;
+    ///    Ok(())
+    /// # }
+    ///
+    /// # async fn handle_block(_block: near_lake_primitives::block::Block) -> anyhow::Result<()> { Ok(()) }
+    /// ```
+    pub fn from_args() -> Result<Self, LakeError> {
+        if std::env::args().skip(1).any(|arg| arg == "--help") {
+            println!("{}", USAGE);
+            std::process::exit(0);
+        }
+        Self::from_args_iter(std::env::args().skip(1))
+    }
+
+    /// The version of [LakeBuilder::from_args] parsing the given arguments (without the program name),
+    /// e.g. the rest of them after the tool's own subcommand. It doesn't handle `--help`
+    pub fn from_args_iter(args: impl IntoIterator<Item = String>) -> Result<Self, LakeError> {
+        Self::default().with_args(args, |name| std::env::var(name).ok())
+    }
+
+    fn with_args(
+        mut self,
+        args: impl IntoIterator<Item = String>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, LakeError> {
+        let mut chain_id = env("NEAR_LAKE_CHAIN_ID");
+        let mut start_block_height = env("NEAR_LAKE_START_BLOCK_HEIGHT");
+        let mut end_block_height = env("NEAR_LAKE_END_BLOCK_HEIGHT");
+        let mut concurrency = env("NEAR_LAKE_CONCURRENCY");
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let (name, inline_value) = match arg.split_once('=') {
+                Some((name, value)) => (name.to_string(), Some(value.to_string())),
+                None => (arg, None),
+            };
+            let value = match name.as_str() {
+                "--mainnet" | "--testnet" | "--betanet" => {
+                    chain_id = Some(name.trim_start_matches("--").to_string());
+                    continue;
+                }
+                "--start-block-height" => &mut start_block_height,
+                "--end-block-height" => &mut end_block_height,
+                "--concurrency" => &mut concurrency,
+                _ => {
+                    return Err(config_error(format!(
+                        "Unknown argument `{}`\n\n{}",
+                        name, USAGE
+                    )))
+                }
+            };
+            *value = Some(inline_value.or_else(|| args.next()).ok_or_else(|| {
+                config_error(format!("Missing the value of `{}`\n\n{}", name, USAGE))
+            })?);
+        }
+
+        self = match chain_id.as_deref() {
+            Some("mainnet") => self.mainnet(),
+            Some("testnet") => self.testnet(),
+            Some("betanet") => self.betanet(),
+            Some(chain_id) => {
+                return Err(config_error(format!(
+                    "Unknown chain `{}`, expected `mainnet`, `testnet` or `betanet`",
+                    chain_id
+                )))
+            }
+            None => self,
+        };
+        if let Some(start_block_height) = start_block_height {
+            self = self.start_block_height(parse("start block height", &start_block_height)?);
+        }
+        if let Some(end_block_height) = end_block_height {
+            self = self.end_block_height(parse("end block height", &end_block_height)?);
+        }
+        if let Some(concurrency) = concurrency {
+            self = self.concurrency(parse("concurrency", &concurrency)?);
+        }
+        Ok(self)
+    }
+}
+
+fn parse<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, LakeError>
+where
+    T::Err: std::fmt::Display,
+{
+    value
+        .parse()
+        .map_err(|err| config_error(format!("Invalid {} `{}`: {}", name, value, err)))
+}
+
+fn config_error(error_message: String) -> LakeError {
+    LakeError::ConfigError { error_message }
+}
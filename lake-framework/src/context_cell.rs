@@ -0,0 +1,81 @@
+//! Shared mutable state of the contexts, see [ContextCell]
+use std::sync::Arc;
+
+/// The shared mutable state of a context: the value behind an [Arc] and an async-aware read-write lock,
+/// accessed with the closures, so the lock is never held across an `.await` and never poisoned.
+///
+/// The clones share the same value, so the context deriving `Clone` (and [LakeContext](crate::LakeContext))
+/// with the cells in its fields shares the state between the clones. The cells are the plain state for the
+/// [LakeContext](crate::LakeContext) derive and don't take part in the hooks.
+/// ```
+/// use near_lake_framework::{ContextCell, LakeContext};
+///
+/// #[derive(Clone, Default, LakeContext)]
+/// struct StatsContext {
+///     function_calls: ContextCell<u64>,
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() {
+///    let context = StatsContext::default();
+///    context.function_calls.write(|calls| *calls += 3).await;
+///    assert_eq!(context.function_calls.read(|calls| *calls).await, 3);
+///    assert_eq!(context.clone().function_calls.get().await, 3);
+/// # }
+/// ```
+pub struct ContextCell<T>(Arc<tokio::sync::RwLock<T>>);
+
+impl<T> ContextCell<T> {
+    /// Wraps the value into the cell
+    pub fn new(value: T) -> Self {
+        Self(Arc::new(tokio::sync::RwLock::new(value)))
+    }
+
+    /// Calls the closure with the shared reference to the value, waiting for the writers (if any) to finish
+    pub async fn read<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        f(&*self.0.read().await)
+    }
+
+    /// Calls the closure with the exclusive reference to the value, waiting for the readers and the writers (if any) to finish
+    pub async fn write<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        f(&mut *self.0.write().await)
+    }
+
+    /// Replaces the value, returning the previous one
+    pub async fn replace(&self, value: T) -> T {
+        self.write(|current| std::mem::replace(current, value))
+            .await
+    }
+
+    /// Returns the copy of the value
+    pub async fn get(&self) -> T
+    where
+        T: Clone,
+    {
+        self.read(T::clone).await
+    }
+}
+
+impl<T> Clone for ContextCell<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T: Default> Default for ContextCell<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for ContextCell<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("ContextCell").field(&self.0).finish()
+    }
+}
+
+impl<T> From<T> for ContextCell<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
@@ -0,0 +1,274 @@
+use std::sync::{Arc, Mutex};
+
+use crate::types::{BlockHeight, RunStats};
+
+/// Handle to control the running indexer: pause and resume the handling of the blocks,
+/// seek to another block height, shut down gracefully and read the status.
+///
+/// Get it with [Lake::control_handle](crate::Lake::control_handle) before running the indexer,
+/// the handle is cheap to clone and can be moved to another task
+/// ```no_run
+/// # #[tokio::main]
+/// # async fn main() -> anyhow::Result<()> {
+///    let lake = near_lake_framework::LakeBuilder::default()
+///        .testnet()
+///        .start_block_height(112205773)
+///        .build()?;
+///    let control = lake.control_handle();
+///    let indexer = lake.spawn(handle_block);
+///
+///    control.pause();
+///    println!("{:?}", control.status());
+///    control.seek(112205873);
+///    control.resume();
+///
+///    indexer.await??;
+///    Ok(())
+/// # }
+///
+/// # async fn handle_block(_block: near_lake_primitives::block::Block) -> anyhow::Result<()> { Ok(()) }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ControlHandle(Arc<ControlState>);
+
+#[derive(Debug)]
+struct ControlState {
+    paused: tokio::sync::watch::Sender<bool>,
+    shutdown: tokio::sync::watch::Sender<bool>,
+    streamed: tokio::sync::watch::Sender<Option<BlockHeight>>,
+    caught_up: Mutex<CaughtUp>,
+    seek_to: Mutex<Option<BlockHeight>>,
+    seek_requested: tokio::sync::Notify,
+    stats: Mutex<Option<Arc<RunStats>>>,
+    max_blocks_per_second: Mutex<Option<f64>>,
+    /// The time the next block is allowed to be handled at under the limit
+    next_block_at: Mutex<Option<tokio::time::Instant>>,
+}
+
+impl Default for ControlState {
+    fn default() -> Self {
+        Self {
+            paused: tokio::sync::watch::Sender::new(false),
+            shutdown: tokio::sync::watch::Sender::new(false),
+            streamed: tokio::sync::watch::Sender::new(None),
+            caught_up: Mutex::new(CaughtUp::Backfilling),
+            seek_to: Mutex::new(None),
+            seek_requested: tokio::sync::Notify::new(),
+            stats: Mutex::new(None),
+            max_blocks_per_second: Mutex::new(None),
+            next_block_at: Mutex::new(None),
+        }
+    }
+}
+
+/// Whether the indexer has caught up with the chain head, see [Lake::on_caught_up](crate::Lake::on_caught_up)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CaughtUp {
+    Backfilling,
+    /// The streamer has reached the chain head after sending the block height, the handlers haven't yet
+    Streamed(BlockHeight),
+    Handled,
+}
+
+/// The status of the running indexer returned by [ControlHandle::status]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct StreamStatus {
+    /// Whether the handling of the blocks is paused
+    pub paused: bool,
+    /// Number of blocks the handler has finished with since the start of the run
+    pub blocks_processed: u64,
+    /// The highest processed block height
+    pub last_block_height: Option<BlockHeight>,
+    /// Number of blocks being handled at the moment (including the paused ones)
+    pub in_flight: usize,
+    /// Number of fetched blocks waiting in the channel to be handled
+    pub queued: usize,
+    /// Number of times the handler has returned an error (each retry is counted)
+    pub handler_failures: u64,
+    /// Number of times the stream has failed and has been restarted or stopped
+    pub stream_failures: u64,
+    /// Time elapsed since the start of the run
+    pub elapsed: std::time::Duration,
+}
+
+impl ControlHandle {
+    /// Pauses the handling of the blocks. The blocks being handled are finished,
+    /// the prefetched ones wait in the pool until [ControlHandle::resume] is called
+    pub fn pause(&self) {
+        self.0.paused.send_replace(true);
+    }
+
+    /// Resumes the handling of the blocks paused with [ControlHandle::pause]
+    pub fn resume(&self) {
+        self.0.paused.send_replace(false);
+    }
+
+    /// Restarts the stream from the given block height.
+    ///
+    /// *Note*: The blocks being handled at the moment are dropped in the middle of the handler
+    pub fn seek(&self, block_height: BlockHeight) {
+        *self.0.seek_to.lock().unwrap() = Some(block_height);
+        self.0.seek_requested.notify_one();
+    }
+
+    /// Stops the indexer gracefully: the stream stops taking the new blocks, the blocks being handled
+    /// are finished (the paused handling is resumed to drain them), the last processed block height is stored
+    /// to the [LakeBuilder::watermark_store](crate::LakeBuilder::watermark_store) and [Lake::run](crate::Lake::run) returns
+    pub fn shutdown(&self) {
+        self.0.shutdown.send_replace(true);
+        self.resume();
+    }
+
+    /// Limits the number of blocks passed to the handler per second, see
+    /// [LakeBuilder::max_blocks_per_second](crate::LakeBuilder::max_blocks_per_second). `None` removes the limit
+    pub fn set_max_blocks_per_second(&self, max_blocks_per_second: Option<f64>) {
+        *self.0.max_blocks_per_second.lock().unwrap() =
+            max_blocks_per_second.filter(|max_blocks_per_second| {
+                max_blocks_per_second.is_finite() && *max_blocks_per_second > 0.0
+            });
+    }
+
+    /// Returns the current limit of the blocks passed to the handler per second
+    pub fn max_blocks_per_second(&self) -> Option<f64> {
+        *self.0.max_blocks_per_second.lock().unwrap()
+    }
+
+    /// Returns the status of the running indexer
+    pub fn status(&self) -> StreamStatus {
+        let paused = *self.0.paused.borrow();
+        self.0
+            .stats
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|stats| stats.status(paused))
+            .unwrap_or(StreamStatus {
+                paused,
+                ..Default::default()
+            })
+    }
+
+    /// Subscribes to the last block height the streamer has sent to the handlers (`None` until the first one),
+    /// so the other tasks of the process (the status endpoints, the coordinators) can observe the progress
+    /// without touching the handler. The streamer runs ahead of the handlers by up to
+    /// [LakeBuilder::blocks_preload_pool_size](crate::LakeBuilder::blocks_preload_pool_size) blocks
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> anyhow::Result<()> {
+    ///    let lake = near_lake_framework::LakeBuilder::default()
+    ///        .testnet()
+    ///        .start_block_height(112205773)
+    ///        .build()?;
+    ///    let mut streamed_block_height = lake.control_handle().streamed_block_height();
+    ///    tokio::spawn(async move {
+    ///        while streamed_block_height.changed().await.is_ok() {
+    ///            println!("Streamed block #{:?}", *streamed_block_height.borrow_and_update());
+    ///        }
+    ///    });
+    ///
+    ///    lake.run_async(handle_block).await?;
+    ///    Ok(())
+    /// # }
+    ///
+    /// # async fn handle_block(_block: near_lake_primitives::block::Block) -> anyhow::Result<()> { Ok(()) }
+    /// ```
+    pub fn streamed_block_height(&self) -> tokio::sync::watch::Receiver<Option<BlockHeight>> {
+        self.0.streamed.subscribe()
+    }
+
+    /// Publishes the block height the streamer has sent to the handlers
+    pub(crate) fn record_streamed(&self, block_height: BlockHeight) {
+        self.0.streamed.send_replace(Some(block_height));
+    }
+
+    /// Returns true once the indexer has caught up with the chain head (the historical blocks are handled)
+    /// and follows the tip, see [Lake::on_caught_up](crate::Lake::on_caught_up)
+    pub fn is_caught_up(&self) -> bool {
+        *self.0.caught_up.lock().unwrap() == CaughtUp::Handled
+    }
+
+    /// Records that the streamer has reached the chain head after sending the block height
+    pub(crate) fn record_caught_up(&self, block_height: BlockHeight) {
+        let mut caught_up = self.0.caught_up.lock().unwrap();
+        if *caught_up == CaughtUp::Backfilling {
+            *caught_up = CaughtUp::Streamed(block_height);
+        }
+    }
+
+    /// Returns the block height the streamer has caught up with once all the blocks up to it are handled,
+    /// only the first time. `lowest_in_flight` is the lowest block height still being handled
+    pub(crate) fn take_caught_up(
+        &self,
+        handled_block_height: BlockHeight,
+        lowest_in_flight: BlockHeight,
+    ) -> Option<BlockHeight> {
+        let mut caught_up = self.0.caught_up.lock().unwrap();
+        match *caught_up {
+            CaughtUp::Streamed(block_height)
+                if handled_block_height >= block_height && lowest_in_flight > block_height =>
+            {
+                *caught_up = CaughtUp::Handled;
+                Some(block_height)
+            }
+            _ => None,
+        }
+    }
+
+    /// Attaches the stats of the run to report them with [ControlHandle::status]
+    pub(crate) fn attach(&self, stats: Arc<RunStats>) {
+        *self.0.stats.lock().unwrap() = Some(stats);
+    }
+
+    /// Waits until the handling of the blocks is not paused
+    pub(crate) async fn wait_resumed(&self) {
+        let mut paused = self.0.paused.subscribe();
+        // the sender lives as long as `self`, so the channel is never closed here
+        let _ = paused.wait_for(|paused| !paused).await;
+    }
+
+    /// Waits for the slot of the next block under the [ControlHandle::max_blocks_per_second] limit
+    pub(crate) async fn throttle(&self) {
+        let Some(max_blocks_per_second) = self.max_blocks_per_second() else {
+            return;
+        };
+        let block_at = {
+            let mut next_block_at = self.0.next_block_at.lock().unwrap();
+            let block_at = std::cmp::max(
+                next_block_at.unwrap_or_else(tokio::time::Instant::now),
+                tokio::time::Instant::now(),
+            );
+            *next_block_at =
+                Some(block_at + std::time::Duration::from_secs_f64(1.0 / max_blocks_per_second));
+            block_at
+        };
+        tokio::time::sleep_until(block_at).await;
+    }
+
+    /// Resolves once [ControlHandle::shutdown] is called
+    pub(crate) async fn shutdown_requested(&self) {
+        let mut shutdown = self.0.shutdown.subscribe();
+        let _ = shutdown.wait_for(|shutdown| *shutdown).await;
+    }
+
+    /// Returns true if [ControlHandle::shutdown] has been called
+    pub(crate) fn is_shutting_down(&self) -> bool {
+        *self.0.shutdown.borrow()
+    }
+
+    /// Resolves once [ControlHandle::seek] is called
+    pub(crate) async fn seek_requested(&self) {
+        loop {
+            let requested = self.0.seek_to.lock().unwrap().is_some();
+            if requested {
+                return;
+            }
+            self.0.seek_requested.notified().await;
+        }
+    }
+
+    /// Returns the block height requested with [ControlHandle::seek], if any, and clears the request
+    pub(crate) fn take_seek(&self) -> Option<BlockHeight> {
+        self.0.seek_to.lock().unwrap().take()
+    }
+}
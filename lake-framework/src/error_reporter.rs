@@ -0,0 +1,98 @@
+use std::fmt::Debug;
+
+use crate::types::{BlockHeight, LakeError};
+
+/// The stage of the pipeline the reported error has happened at
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ErrorSource {
+    /// The error has stopped the streamer fetching the blocks
+    Fetch,
+    /// The handler has returned an error (or timed out)
+    Handler,
+}
+
+/// The error passed to the [ErrorReporter] along with the context it has happened in
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct ErrorReport<'a> {
+    /// The stage of the pipeline the error has happened at
+    pub source: ErrorSource,
+    /// The error itself
+    pub error: &'a LakeError,
+    /// The block height the error has happened at, if known
+    pub block_height: Option<BlockHeight>,
+    /// The shard id the error has happened at, if relevant
+    pub shard_id: Option<u64>,
+    /// The data provider the blocks are fetched from
+    pub provider: &'static str,
+    /// The number of the handler call for the block ([ErrorSource::Handler]),
+    /// or the number of the stream failures since the start of the run ([ErrorSource::Fetch])
+    pub attempt: u64,
+}
+
+impl ErrorReport<'_> {
+    /// Returns the messages of the error and its sources, from the outermost to the root cause
+    pub fn error_chain(&self) -> Vec<String> {
+        let mut error_chain = vec![];
+        let mut error: Option<&dyn std::error::Error> = Some(self.error);
+        while let Some(current) = error {
+            error_chain.push(current.to_string());
+            error = current.source();
+        }
+        error_chain
+    }
+}
+
+/// Hook to report the fetch failures and the handler errors to an external service (Sentry, PagerDuty, etc.),
+/// so the production indexers can alert on the failures without scraping the logs.
+/// Set up with [LakeBuilder::error_reporter](crate::LakeBuilder::error_reporter).
+///
+/// The reporter is called for every failure, including the ones the [Lake::on_error](crate::Lake::on_error)
+/// callback decides to skip or retry. Keep it fast, it is called on the streaming path.
+///
+/// An example of the Sentry integration (with the `sentry` crate added to the dependencies):
+/// ```ignore
+/// use near_lake_framework::{ErrorReport, ErrorReporter};
+///
+/// #[derive(Debug)]
+/// struct SentryErrorReporter;
+///
+/// impl ErrorReporter for SentryErrorReporter {
+///     fn report(&self, report: &ErrorReport<'_>) {
+///         sentry::with_scope(
+///             |scope| {
+///                 scope.set_tag("source", format!("{:?}", report.source));
+///                 scope.set_tag("provider", report.provider);
+///                 if let Some(block_height) = report.block_height {
+///                     scope.set_tag("block_height", block_height);
+///                 }
+///                 if let Some(shard_id) = report.shard_id {
+///                     scope.set_tag("shard_id", shard_id);
+///                 }
+///                 scope.set_extra("attempt", report.attempt.into());
+///                 scope.set_extra("error_chain", report.error_chain().into());
+///             },
+///             || sentry::capture_error(report.error),
+///         );
+///     }
+/// }
+///
+/// # fn main() -> anyhow::Result<()> {
+///    let _sentry = sentry::init("https://key@sentry.io/42");
+///
+///    near_lake_framework::LakeBuilder::default()
+///        .mainnet()
+///        .start_block_height(88444526)
+///        .error_reporter(SentryErrorReporter)
+///        .build()?
+///        .run(handle_block)?;
+///    Ok(())
+/// # }
+///
+/// # async fn handle_block(_block: near_lake_primitives::block::Block) -> anyhow::Result<()> { Ok(()) }
+/// ```
+pub trait ErrorReporter: Debug + Send + Sync {
+    /// Reports the error along with its context
+    fn report(&self, report: &ErrorReport<'_>);
+}
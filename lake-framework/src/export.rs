@@ -0,0 +1,391 @@
+//! Export of the block height ranges to the local files, see [Lake::export]
+use std::path::Path;
+
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+use crate::state_store::{FileStateStore, StateStore};
+use crate::types::{BlockHeight, Lake, LakeError, Progress, RunSummary};
+
+/// The file the [ExportFormat::Directory] export keeps the last exported block height in
+const DIRECTORY_STATE_FILE: &str = ".export-state.json";
+
+/// How many bytes to read at once while looking for the last line of the ndjson file
+const TAIL_CHUNK_SIZE: u64 = 64 * 1024;
+
+/// The width of the progress bar in characters
+const PROGRESS_BAR_WIDTH: usize = 40;
+
+/// The format of the files written by [Lake::export]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// A single file with a [StreamerMessage](near_lake_primitives::StreamerMessage) JSON per line
+    Ndjson,
+    /// A directory with the `block.json` and `shard_N.json` files in the bucket layout
+    /// (the [KeyLayout](crate::KeyLayout) of the [Lake]), so it can be synced to a bucket and streamed as a lake
+    Directory,
+}
+
+impl Lake {
+    /// Exports the blocks from the start to the end block height (it has to be set) to the local files,
+    /// for the users who just want the data files.
+    ///
+    /// The export is resumable: started again with the same path, it continues after the last exported block
+    /// (the ndjson file is read from its last complete line, the directory keeps its state in the `.export-state.json` file).
+    /// The blocks are exported one by one in the order, a failed write aborts the export
+    /// (unless the [Lake::on_error] callback decides otherwise). Unless the [Lake::on_progress] callback is set up,
+    /// the progress bar is printed to the stderr every second (or every [LakeBuilder::progress_interval](crate::LakeBuilder::progress_interval))
+    ///```no_run
+    /// use near_lake_framework::ExportFormat;
+    ///
+    ///# #[tokio::main]
+    ///# async fn main() -> anyhow::Result<()> {
+    ///    let summary = near_lake_framework::LakeBuilder::default()
+    ///        .mainnet()
+    ///        .start_block_height(88444526)
+    ///        .end_block_height(88445525)
+    ///        .build()?
+    ///        .export(ExportFormat::Ndjson, "blocks.ndjson")
+    ///        .await?;
+    ///    println!("Exported {} blocks", summary.blocks_processed);
+    ///    Ok(())
+    ///# }
+    ///```
+    pub async fn export(
+        mut self,
+        format: ExportFormat,
+        path: impl AsRef<Path>,
+    ) -> Result<RunSummary, LakeError> {
+        let path = path.as_ref();
+        let Some(end_block_height) = self.end_block_height else {
+            return Err(LakeError::ConfigError {
+                error_message: "The export needs the end block height".to_string(),
+            });
+        };
+
+        let state_store = FileStateStore::new(path.join(DIRECTORY_STATE_FILE));
+        let last_exported_block_height = match format {
+            ExportFormat::Ndjson => ndjson_last_block_height(path).await?,
+            ExportFormat::Directory => state_store.last_height().await?,
+        };
+        if let Some(last_exported_block_height) = last_exported_block_height {
+            if last_exported_block_height >= end_block_height {
+                tracing::info!(
+                    target: crate::LAKE_FRAMEWORK,
+                    block_height = last_exported_block_height,
+                    path = %path.display(),
+                    "The range is exported already",
+                );
+                return Ok(RunSummary::default());
+            }
+            tracing::info!(
+                target: crate::LAKE_FRAMEWORK,
+                block_height = last_exported_block_height,
+                path = %path.display(),
+                "Resuming the export",
+            );
+            self.start_block_height =
+                std::cmp::max(self.start_block_height, last_exported_block_height + 1);
+        }
+
+        self.concurrency = 1;
+        // a skipped block would leave a gap the resumed export doesn't fill
+        self.stop_on_handler_error = true;
+        let progress_bar = self.on_progress.is_none();
+        if progress_bar {
+            self.progress_interval
+                .get_or_insert(std::time::Duration::from_secs(1));
+            let start_block_height = self.start_block_height;
+            self =
+                self.on_progress(move |progress| print_progress_bar(start_block_height, progress));
+        }
+
+        let summary = match format {
+            ExportFormat::Ndjson => {
+                let file = tokio::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .await
+                    .map_err(|err| io_error(path, err))?;
+                let file = tokio::sync::Mutex::new(file);
+                let summary = self
+                    .run_async(|block| {
+                        let file = &file;
+                        async move {
+                            let mut line = serde_json::to_vec(block.streamer_message())?;
+                            line.push(b'\n');
+                            file.lock()
+                                .await
+                                .write_all(&line)
+                                .await
+                                .map_err(|err| io_error(path, err))
+                        }
+                    })
+                    .await;
+                // the tokio file completes the last write in the background, wait for it
+                file.into_inner()
+                    .flush()
+                    .await
+                    .map_err(|err| io_error(path, err))?;
+                summary
+            }
+            ExportFormat::Directory => {
+                let key_layout = self.key_layout();
+                let (key_layout, state_store) = (&key_layout, &state_store);
+                self.run_async(|block| async move {
+                    let streamer_message = block.streamer_message();
+                    let block_height = streamer_message.block.header.height;
+                    // the shards are written before the `block.json`, the same way the lake buckets are
+                    for shard in &streamer_message.shards {
+                        write_file(
+                            &path.join(key_layout.shard_key(block_height, shard.shard_id)),
+                            serde_json::to_vec(shard)?,
+                        )
+                        .await?;
+                    }
+                    write_file(
+                        &path.join(key_layout.block_key(block_height)),
+                        serde_json::to_vec(&streamer_message.block)?,
+                    )
+                    .await?;
+                    state_store.set_last_height(block_height).await
+                })
+                .await
+            }
+        };
+        if progress_bar {
+            eprintln!();
+        }
+        summary
+    }
+}
+
+/// Renders the progress of the export from the start block height to the target one as a single terminal line
+fn print_progress_bar(start_block_height: BlockHeight, progress: &Progress) {
+    let total = (progress.target_block_height + 1).saturating_sub(start_block_height);
+    let done = progress.last_block_height.map_or(0, |block_height| {
+        (block_height + 1).saturating_sub(start_block_height)
+    });
+    let ratio = if total == 0 {
+        1.0
+    } else {
+        (done as f64 / total as f64).min(1.0)
+    };
+    let filled = (ratio * PROGRESS_BAR_WIDTH as f64) as usize;
+    eprint!(
+        "\r[{}{}] {:>5.1}% #{} {:.1} blocks/s, ETA {}",
+        "#".repeat(filled),
+        "-".repeat(PROGRESS_BAR_WIDTH - filled),
+        ratio * 100.0,
+        progress.last_block_height.unwrap_or(start_block_height),
+        progress.blocks_per_second,
+        progress
+            .eta
+            .map(|eta| format!("{}s", eta.as_secs()))
+            .unwrap_or_else(|| "-".to_string()),
+    );
+}
+
+/// Returns the block height of the last complete line of the ndjson file, if any.
+/// The incomplete last line (the export has been interrupted while writing it) is truncated
+async fn ndjson_last_block_height(path: &Path) -> Result<Option<BlockHeight>, LakeError> {
+    let mut file = match tokio::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .await
+    {
+        Ok(file) => file,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(io_error(path, err)),
+    };
+    let len = file
+        .metadata()
+        .await
+        .map_err(|err| io_error(path, err))?
+        .len();
+
+    let Some(line_end) = rfind_newline(&mut file, len)
+        .await
+        .map_err(|err| io_error(path, err))?
+    else {
+        file.set_len(0).await.map_err(|err| io_error(path, err))?;
+        return Ok(None);
+    };
+    if line_end + 1 < len {
+        tracing::warn!(
+            target: crate::LAKE_FRAMEWORK,
+            path = %path.display(),
+            "Truncating the incomplete last line of the export",
+        );
+        file.set_len(line_end + 1)
+            .await
+            .map_err(|err| io_error(path, err))?;
+    }
+    let line_start = rfind_newline(&mut file, line_end)
+        .await
+        .map_err(|err| io_error(path, err))?
+        .map_or(0, |newline| newline + 1);
+
+    let mut line = vec![0; (line_end - line_start) as usize];
+    file.seek(std::io::SeekFrom::Start(line_start))
+        .await
+        .map_err(|err| io_error(path, err))?;
+    file.read_exact(&mut line)
+        .await
+        .map_err(|err| io_error(path, err))?;
+    let streamer_message: serde_json::Value = serde_json::from_slice(&line)?;
+    Ok(streamer_message["block"]["header"]["height"].as_u64())
+}
+
+/// Returns the position of the last `\n` before the given position
+async fn rfind_newline(file: &mut tokio::fs::File, before: u64) -> std::io::Result<Option<u64>> {
+    let mut end = before;
+    while end > 0 {
+        let start = end.saturating_sub(TAIL_CHUNK_SIZE);
+        let mut chunk = vec![0; (end - start) as usize];
+        file.seek(std::io::SeekFrom::Start(start)).await?;
+        file.read_exact(&mut chunk).await?;
+        if let Some(index) = chunk.iter().rposition(|byte| *byte == b'\n') {
+            return Ok(Some(start + index as u64));
+        }
+        end = start;
+    }
+    Ok(None)
+}
+
+async fn write_file(path: &Path, content: Vec<u8>) -> Result<(), LakeError> {
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir)
+            .await
+            .map_err(|err| io_error(dir, err))?;
+    }
+    tokio::fs::write(path, content)
+        .await
+        .map_err(|err| io_error(path, err))
+}
+
+fn io_error(path: &Path, err: std::io::Error) -> LakeError {
+    LakeError::InternalError {
+        error_message: format!("Failed to access {}: {}", path.display(), err),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!(
+            "near-lake-framework-export-{}-{}",
+            name,
+            std::process::id()
+        ))
+    }
+
+    fn line(block_height: BlockHeight, padding: usize) -> String {
+        format!(
+            "{}\n",
+            serde_json::json!({
+                "block": {"header": {"height": block_height}},
+                "padding": "x".repeat(padding),
+            })
+        )
+    }
+
+    /// Writes the content to the file and returns the last block height found in it along with the content left
+    async fn last_block_height(name: &str, content: &str) -> (Option<BlockHeight>, String) {
+        let path = temp_path(name);
+        std::fs::write(&path, content).unwrap();
+        let last_block_height = ndjson_last_block_height(&path).await.unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        (last_block_height, content)
+    }
+
+    #[tokio::test]
+    async fn finds_last_block_height_of_ndjson() {
+        assert_eq!(
+            ndjson_last_block_height(&temp_path("missing"))
+                .await
+                .unwrap(),
+            None
+        );
+        assert_eq!(last_block_height("empty", "").await, (None, String::new()));
+
+        let content = format!("{}{}", line(1, 0), line(2, 0));
+        assert_eq!(
+            last_block_height("complete", &content).await,
+            (Some(2), content.clone())
+        );
+
+        // the last line has not been written completely
+        let torn_line = line(3, 0);
+        let torn_content = format!("{}{}", content, &torn_line[..torn_line.len() / 2]);
+        assert_eq!(
+            last_block_height("torn", &torn_content).await,
+            (Some(2), content)
+        );
+        let no_newline = line(1, 0).trim_end().to_string();
+        assert_eq!(
+            last_block_height("no-newline", &no_newline).await,
+            (None, String::new())
+        );
+
+        let content = format!("{}{}", line(1, 0), line(2, TAIL_CHUNK_SIZE as usize * 2));
+        assert_eq!(
+            last_block_height("long-line", &content).await,
+            (Some(2), content)
+        );
+    }
+
+    #[tokio::test]
+    async fn finds_newlines_across_chunks() {
+        let path = temp_path("newlines");
+        let mut content = vec![b'x'; TAIL_CHUNK_SIZE as usize * 3];
+        content[10] = b'\n';
+        content[TAIL_CHUNK_SIZE as usize + 10] = b'\n';
+        std::fs::write(&path, &content).unwrap();
+        let mut file = tokio::fs::File::open(&path).await.unwrap();
+
+        let len = content.len() as u64;
+        assert_eq!(
+            rfind_newline(&mut file, len).await.unwrap(),
+            Some(TAIL_CHUNK_SIZE + 10)
+        );
+        assert_eq!(
+            rfind_newline(&mut file, TAIL_CHUNK_SIZE + 10)
+                .await
+                .unwrap(),
+            Some(10)
+        );
+        assert_eq!(rfind_newline(&mut file, 10).await.unwrap(), None);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn exports_up_to_tip() {
+        let path = temp_path("tip.ndjson");
+        let _ = std::fs::remove_file(&path);
+        let export = || {
+            crate::test::fixture_lake()
+                .end_block_height(879765)
+                .build()
+                .unwrap()
+                .on_progress(|_progress| {})
+                .export(ExportFormat::Ndjson, &path)
+        };
+
+        let summary = tokio::time::timeout(std::time::Duration::from_secs(10), export())
+            .await
+            .expect("The export has waited for the blocks above the end block height")
+            .unwrap();
+        assert_eq!(summary.blocks_processed, 1);
+        assert_eq!(ndjson_last_block_height(&path).await.unwrap(), Some(879765));
+
+        // resumed, the export finds the range exported already
+        let summary = export().await.unwrap();
+        assert_eq!(summary.blocks_processed, 0);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,63 @@
+//! The handlers implemented on the structs, see [BlockHandler]
+use async_trait::async_trait;
+
+/// The alternative to the handler closures for the larger applications: implement the trait on a struct holding
+/// the dependencies of the handler and run it with [Lake::run_handler](crate::Lake::run_handler).
+/// The struct can be constructed and called directly in the tests, without starting the stream.
+/// ```no_run
+/// use async_trait::async_trait;
+/// use near_lake_framework::BlockHandler;
+///
+/// struct ReceiptsCounter {
+///     accounts: Vec<String>,
+/// }
+///
+/// #[async_trait]
+/// impl BlockHandler for ReceiptsCounter {
+///     type Error = anyhow::Error;
+///
+///     async fn handle(&self, mut block: near_lake_primitives::block::Block) -> anyhow::Result<()> {
+///         let receipts = block
+///             .receipts()
+///             .filter(|receipt| self.accounts.iter().any(|account| receipt.receiver_id().as_str() == account))
+///             .count();
+///         println!("Block #{}: {} receipts", block.block_height(), receipts);
+///         Ok(())
+///     }
+/// }
+///
+/// # fn main() -> anyhow::Result<()> {
+///    near_lake_framework::LakeBuilder::default()
+///        .testnet()
+///        .start_block_height(112205773)
+///        .build()?
+///        .run_handler(ReceiptsCounter { accounts: vec!["wrap.testnet".to_string()] })?;
+///    Ok(())
+/// # }
+/// ```
+#[async_trait]
+pub trait BlockHandler: Send + Sync {
+    /// The error of the handler, reported as the [LakeError::HandlerError](crate::LakeError::HandlerError)
+    type Error: Into<Box<dyn std::error::Error>>;
+
+    /// Handles the block, the same way the handler closure passed to [Lake::run](crate::Lake::run) does
+    async fn handle(&self, block: near_lake_primitives::block::Block) -> Result<(), Self::Error>;
+}
+
+#[async_trait]
+impl<H: BlockHandler + ?Sized> BlockHandler for std::sync::Arc<H> {
+    type Error = H::Error;
+
+    async fn handle(&self, block: near_lake_primitives::block::Block) -> Result<(), Self::Error> {
+        (**self).handle(block).await
+    }
+}
+
+#[async_trait]
+impl<H: BlockHandler + ?Sized> BlockHandler for Box<H> {
+    type Error = H::Error;
+
+    async fn handle(&self, block: near_lake_primitives::block::Block) -> Result<(), Self::Error> {
+        (**self).handle(block).await
+    }
+}
@@ -0,0 +1,25 @@
+//! Periodic reporting of the [StreamStatus](crate::StreamStatus), see [Lake::on_heartbeat](crate::Lake::on_heartbeat)
+use crate::types::Lake;
+
+/// Stops the heartbeat on drop
+pub(crate) struct HeartbeatTask(tokio::task::JoinHandle<()>);
+
+impl Drop for HeartbeatTask {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Spawns the heartbeat if [Lake::on_heartbeat](crate::Lake::on_heartbeat) is set up
+pub(crate) fn spawn(config: &Lake) -> Option<HeartbeatTask> {
+    let heartbeat = config.on_heartbeat.clone()?;
+    let control = config.control.clone();
+
+    Some(HeartbeatTask(tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(heartbeat.0);
+        loop {
+            ticks.tick().await;
+            (heartbeat.1)(control.status());
+        }
+    })))
+}
@@ -0,0 +1,144 @@
+//! Tiny HTTP listener serving the Prometheus metrics (`/metrics`) and the health status (`/healthz`)
+//! of the running indexer, so the containerized indexers get the standard probes without a web framework.
+use std::sync::Arc;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::types::{LakeError, RunStats, S3RequestsCounter};
+
+/// Stops the server on drop
+pub(crate) struct HttpServer(tokio::task::JoinHandle<()>);
+
+impl Drop for HttpServer {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Binds the listener to the address and serves the requests in the background until the returned
+/// [HttpServer] is dropped
+pub(crate) async fn serve(
+    addr: std::net::SocketAddr,
+    stats: Arc<RunStats>,
+    s3_requests_counter: S3RequestsCounter,
+) -> Result<HttpServer, LakeError> {
+    let listener =
+        tokio::net::TcpListener::bind(addr)
+            .await
+            .map_err(|err| LakeError::InternalError {
+                error_message: format!("Failed to bind the HTTP server to {}: {}", addr, err),
+            })?;
+    tracing::info!(target: crate::LAKE_FRAMEWORK, %addr, "Serving /metrics and /healthz");
+
+    Ok(HttpServer(tokio::spawn(async move {
+        loop {
+            let Ok((stream, _)) = listener.accept().await else {
+                continue;
+            };
+            let stats = stats.clone();
+            let s3_requests_counter = s3_requests_counter.clone();
+            tokio::spawn(async move {
+                if let Err(err) = respond(stream, &stats, &s3_requests_counter).await {
+                    tracing::debug!(
+                        target: crate::LAKE_FRAMEWORK,
+                        error = %err,
+                        "Failed to respond to the HTTP request",
+                    );
+                }
+            });
+        }
+    })))
+}
+
+async fn respond(
+    mut stream: tokio::net::TcpStream,
+    stats: &RunStats,
+    s3_requests_counter: &S3RequestsCounter,
+) -> std::io::Result<()> {
+    // the request line is all we need, the probes send tiny requests
+    let mut request = [0u8; 1024];
+    let len = stream.read(&mut request).await?;
+    let path = std::str::from_utf8(&request[..len])
+        .ok()
+        .and_then(|request| request.split_whitespace().nth(1))
+        .unwrap_or_default();
+
+    let (status, content_type, body) = match path {
+        "/metrics" => (
+            "200 OK",
+            "text/plain; version=0.0.4",
+            metrics(stats, s3_requests_counter),
+        ),
+        "/healthz" => ("200 OK", "application/json", health(stats)),
+        _ => ("404 Not Found", "text/plain", "Not Found".to_string()),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+fn metrics(stats: &RunStats, s3_requests_counter: &S3RequestsCounter) -> String {
+    let summary = stats.summary();
+    let mut metrics = format!(
+        "# HELP near_lake_blocks_processed_total Blocks passed to the handler\n\
+         # TYPE near_lake_blocks_processed_total counter\n\
+         near_lake_blocks_processed_total {}\n\
+         # HELP near_lake_last_block_height The highest processed block height\n\
+         # TYPE near_lake_last_block_height gauge\n\
+         near_lake_last_block_height {}\n\
+         # HELP near_lake_handler_failures_total Failed handler calls\n\
+         # TYPE near_lake_handler_failures_total counter\n\
+         near_lake_handler_failures_total {}\n\
+         # HELP near_lake_s3_requests_total S3 requests sent by the built-in client\n\
+         # TYPE near_lake_s3_requests_total counter\n\
+         near_lake_s3_requests_total{{type=\"get\"}} {}\n\
+         near_lake_s3_requests_total{{type=\"head\"}} {}\n\
+         near_lake_s3_requests_total{{type=\"list\"}} {}\n\
+         # HELP near_lake_uptime_seconds Time since the indexer has started\n\
+         # TYPE near_lake_uptime_seconds gauge\n\
+         near_lake_uptime_seconds {}\n",
+        summary.blocks_processed,
+        summary.last_block_height.unwrap_or_default(),
+        summary.handler_failures,
+        s3_requests_counter.get_requests(),
+        s3_requests_counter.head_requests(),
+        s3_requests_counter.list_requests(),
+        summary.elapsed.as_secs_f64(),
+    );
+    // the lag is known only when the chain head is probed (`LakeBuilder::lag_probe_interval`)
+    if let Some(lag) = stats.lag() {
+        metrics.push_str(&format!(
+            "# HELP near_lake_chain_head_height The latest block height in the bucket\n\
+             # TYPE near_lake_chain_head_height gauge\n\
+             near_lake_chain_head_height {}\n\
+             # HELP near_lake_lag_blocks Block heights between the last processed one and the chain head\n\
+             # TYPE near_lake_lag_blocks gauge\n\
+             near_lake_lag_blocks {}\n\
+             # HELP near_lake_lag_seconds Time since the timestamp of the last processed block\n\
+             # TYPE near_lake_lag_seconds gauge\n\
+             near_lake_lag_seconds {}\n",
+            lag.chain_head_height,
+            lag.blocks.unwrap_or(lag.chain_head_height),
+            lag.seconds.unwrap_or_default().as_secs_f64(),
+        ));
+    }
+    metrics
+}
+
+fn health(stats: &RunStats) -> String {
+    let summary = stats.summary();
+    serde_json::json!({
+        "status": "ok",
+        "blocks_processed": summary.blocks_processed,
+        "last_block_height": summary.last_block_height,
+        "handler_failures": summary.handler_failures,
+        "uptime_secs": summary.elapsed.as_secs(),
+    })
+    .to_string()
+}
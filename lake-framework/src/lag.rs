@@ -0,0 +1,84 @@
+//! Periodic probing of the chain head to measure the lag of the indexer behind it,
+//! see [LakeBuilder::lag_probe_interval](crate::LakeBuilder::lag_probe_interval)
+use std::sync::Arc;
+
+use crate::types::{Lake, RunStats};
+
+/// Stops the monitor on drop
+pub(crate) struct LagMonitor(tokio::task::JoinHandle<()>);
+
+impl Drop for LagMonitor {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Spawns the monitor if [LakeBuilder::lag_probe_interval](crate::LakeBuilder::lag_probe_interval) is set
+pub(crate) fn spawn(config: &Lake, stats: Arc<RunStats>) -> Option<LagMonitor> {
+    let interval = config.lag_probe_interval?;
+    let config = config.clone();
+
+    Some(LagMonitor(tokio::spawn(async move {
+        let block_provider = crate::provider::block_provider(&config).await;
+        let mut ticks = tokio::time::interval(interval);
+
+        loop {
+            ticks.tick().await;
+            // the chain head can't be lower than the previously probed one or the last processed block
+            let start_from_block_height = std::cmp::max(
+                stats
+                    .lag()
+                    .map(|lag| lag.chain_head_height)
+                    .unwrap_or_default(),
+                stats
+                    .summary()
+                    .last_block_height
+                    .unwrap_or(config.start_block_height),
+            );
+            match block_provider
+                .latest_block_height(start_from_block_height)
+                .await
+            {
+                Ok(chain_head_height) => {
+                    stats.record_chain_head(chain_head_height.unwrap_or(start_from_block_height))
+                }
+                Err(err) => {
+                    tracing::warn!(
+                        target: crate::LAKE_FRAMEWORK,
+                        provider = block_provider.name(),
+                        block_height = start_from_block_height,
+                        error = %err,
+                        "Failed to probe the chain head",
+                    );
+                    continue;
+                }
+            }
+
+            let Some(lag) = stats.lag() else {
+                continue;
+            };
+            tracing::debug!(
+                target: crate::LAKE_FRAMEWORK,
+                block_height = lag.last_block_height,
+                chain_head_height = lag.chain_head_height,
+                lag_blocks = lag.blocks,
+                lag_secs = lag.seconds.map(|seconds| seconds.as_secs()),
+                "Probed the chain head",
+            );
+            let Some(on_lag_alert) = &config.on_lag_alert else {
+                continue;
+            };
+            if lag.blocks.unwrap_or_default() > on_lag_alert.0 {
+                tracing::warn!(
+                    target: crate::LAKE_FRAMEWORK,
+                    block_height = lag.last_block_height,
+                    chain_head_height = lag.chain_head_height,
+                    lag_blocks = lag.blocks,
+                    threshold = on_lag_alert.0,
+                    "The indexer lags behind the chain head",
+                );
+                (on_lag_alert.1)(&lag);
+            }
+        }
+    })))
+}
@@ -3,22 +3,111 @@
 extern crate derive_builder;
 
 use futures::{Future, StreamExt};
+use tracing::Instrument;
 
-pub use near_lake_context_derive::LakeContext;
+pub use near_lake_context_derive::{main, LakeContext};
 pub use near_lake_primitives::{
     self,
     near_indexer_primitives::{self, near_primitives},
 };
 
+#[cfg(feature = "archive")]
+pub use archive::ArchiveS3Client;
 pub use aws_credential_types::Credentials;
-pub use types::{Lake, LakeBuilder, LakeContextExt, LakeError};
+pub use channel::{ChannelStrategy, StreamerMessageSender};
+pub use context_cell::ContextCell;
+pub use control::{ControlHandle, StreamStatus};
+pub use error_reporter::{ErrorReport, ErrorReporter, ErrorSource};
+pub use export::ExportFormat;
+pub use handler::BlockHandler;
+#[cfg(feature = "mqtt-sink")]
+pub use mqtt_sink::MqttEventSink;
+#[cfg(feature = "postgres-sink")]
+pub use postgres_sink::{PostgresCopySink, PostgresRow};
+pub use provider::BlockProvider;
+pub use s3_fetchers::{DefaultKeyLayout, KeyLayout, S3Client};
+pub use s3_sink::S3RepublishSink;
+#[cfg(feature = "postgres-sink")]
+pub use state_store::PostgresStateStore;
+pub use state_store::{FileStateStore, S3StateStore, StateStore, LAST_HEIGHT_KEY, WATERMARK_KEY};
+#[doc(hidden)]
+pub use types::__derive;
+pub use types::{
+    ErrorDecision, ErrorKind, EventFilter, Lag, Lake, LakeBuilder, LakeContextExt, LakeError,
+    Progress, RunSummary, S3Pricing, S3RequestsCounter,
+};
 
+#[cfg(all(unix, feature = "admin-socket"))]
+mod admin_socket;
+#[cfg(feature = "archive")]
+mod archive;
+#[cfg(feature = "block-share")]
+mod block_share;
+mod channel;
+mod cli;
+mod context_cell;
+mod control;
+mod error_reporter;
+mod export;
+mod handler;
+mod heartbeat;
+#[cfg(feature = "http-server")]
+mod http_server;
+mod lag;
+#[cfg(feature = "mqtt-sink")]
+mod mqtt_sink;
+mod parser;
+#[cfg(feature = "postgres-sink")]
+mod postgres_sink;
+mod progress;
+mod provider;
 mod s3_fetchers;
+mod s3_sink;
+#[cfg(feature = "signals")]
+mod signals;
+mod state_store;
 mod streamer;
 pub(crate) mod types;
+mod watchdog;
 
 pub(crate) const LAKE_FRAMEWORK: &str = "near_lake_framework";
 
+/// The low-level API of the 0.7.x versions: spawns the streamer and returns its handle and the `receiver`
+/// of the raw [StreamerMessage](near_indexer_primitives::StreamerMessage), so the existing pipelines can adopt
+/// this crate incrementally before moving to [Lake::run](types::Lake::run) and the [Block](near_lake_primitives::block::Block).
+/// The channel is bounded by [LakeBuilder::blocks_preload_pool_size](types::LakeBuilder::blocks_preload_pool_size),
+/// the handlers, the hooks and the restarts of the [Lake::run](types::Lake::run) are not involved, see [Lake::stream_into](types::Lake::stream_into).
+/// It has to be called within the tokio runtime
+///```no_run
+///# #[tokio::main]
+///# async fn main() -> anyhow::Result<()> {
+///    let config = near_lake_framework::LakeBuilder::default()
+///        .testnet()
+///        .start_block_height(112205773)
+///        .build()?;
+///    let (sender, mut stream) = near_lake_framework::streamer(config);
+///
+///    while let Some(streamer_message) = stream.recv().await {
+///        eprintln!("{}", streamer_message.block.header.height);
+///    }
+///
+///    match sender.await {
+///        Ok(Ok(())) => Ok(()),
+///        Ok(Err(err)) => Err(err.into()),
+///        Err(err) => Err(err.into()),
+///    }
+///# }
+///```
+pub fn streamer(
+    config: types::Lake,
+) -> (
+    tokio::task::JoinHandle<Result<(), types::LakeError>>,
+    tokio::sync::mpsc::Receiver<near_indexer_primitives::StreamerMessage>,
+) {
+    let (sender, receiver) = tokio::sync::mpsc::channel(config.blocks_preload_pool_size);
+    (config.stream_into(sender), receiver)
+}
+
 impl types::Lake {
     /// Creates `mpsc::channel` and returns the `receiver` to read the stream of `StreamerMessage`
     ///```no_run
@@ -49,7 +138,7 @@ impl types::Lake {
         self,
         f: impl Fn(near_lake_primitives::block::Block, &'context C) -> Fut,
         context: &'context C,
-    ) -> Result<(), LakeError>
+    ) -> Result<RunSummary, LakeError>
     where
         Fut: Future<Output = Result<(), E>>,
         E: Into<Box<dyn std::error::Error>>,
@@ -57,39 +146,805 @@ impl types::Lake {
         let runtime = tokio::runtime::Runtime::new()
             .map_err(|err| LakeError::RuntimeStartError { error: err })?;
 
-        runtime.block_on(async move {
-            // capture the concurrency value before it moves into the streamer
-            let concurrency = self.concurrency;
+        runtime.block_on(self.run_with_context_async(f, context))
+    }
+
+    /// The version of [Lake::run_with_context] taking the ownership of the context (or sharing the [Arc] with it),
+    /// so the context can be constructed inline and doesn't have to outlive the call.
+    /// The handler gets the [Arc] of the context, which it can move into the spawned tasks
+    ///```no_run
+    ///  # use near_lake_framework::{LakeContext};
+    /// use std::sync::Arc;
+    ///
+    /// #[derive(LakeContext)]
+    ///  struct MyContext {
+    ///      my_field: String,
+    ///  }
+    ///
+    ///# fn main() -> anyhow::Result<()> {
+    ///    near_lake_framework::LakeBuilder::default()
+    ///        .testnet()
+    ///        .start_block_height(112205773)
+    ///        .build()?
+    ///        .run_with_shared_context(
+    ///            handle_block,
+    ///            MyContext {
+    ///                my_field: "my_value".to_string(),
+    ///            },
+    ///        )?;
+    ///    Ok(())
+    ///# }
+    ///
+    /// # async fn handle_block(_block: near_lake_primitives::block::Block, context: Arc<MyContext>) -> anyhow::Result<()> { Ok(()) }
+    ///```
+    pub fn run_with_shared_context<C: LakeContextExt, E, Fut>(
+        self,
+        f: impl Fn(near_lake_primitives::block::Block, std::sync::Arc<C>) -> Fut,
+        context: impl Into<std::sync::Arc<C>>,
+    ) -> Result<RunSummary, LakeError>
+    where
+        Fut: Future<Output = Result<(), E>>,
+        E: Into<Box<dyn std::error::Error>>,
+    {
+        let runtime = tokio::runtime::Runtime::new()
+            .map_err(|err| LakeError::RuntimeStartError { error: err })?;
+
+        runtime.block_on(self.run_with_shared_context_async(f, context))
+    }
+
+    /// The async version of [Lake::run_with_shared_context] that runs inside the caller's runtime
+    /// instead of creating its own one and blocking the thread
+    pub async fn run_with_shared_context_async<C: LakeContextExt, E, Fut>(
+        self,
+        f: impl Fn(near_lake_primitives::block::Block, std::sync::Arc<C>) -> Fut,
+        context: impl Into<std::sync::Arc<C>>,
+    ) -> Result<RunSummary, LakeError>
+    where
+        Fut: Future<Output = Result<(), E>>,
+        E: Into<Box<dyn std::error::Error>>,
+    {
+        let context = context.into();
+
+        self.run_with_context_async(|block, context| f(block, context.clone()), &context)
+            .await
+    }
+
+    /// The async version of [Lake::run_with_context] that runs inside the caller's runtime
+    /// instead of creating its own one and blocking the thread.
+    ///
+    /// Once the stream has terminated (whatever the result), it awaits [LakeContextExt::finalize] of the context
+    ///```no_run
+    ///  # use near_lake_framework::{LakeContext};
+    ///
+    /// #[derive(LakeContext)]
+    ///  struct MyContext {
+    ///      my_field: String,
+    ///  }
+    ///
+    ///# #[tokio::main]
+    ///# async fn main() -> anyhow::Result<()> {
+    ///    let context = MyContext {
+    ///       my_field: "my_value".to_string(),
+    ///    };
+    ///
+    ///    near_lake_framework::LakeBuilder::default()
+    ///        .testnet()
+    ///        .start_block_height(112205773)
+    ///        .build()?
+    ///        .run_with_context_async(handle_block, &context)
+    ///        .await?;
+    ///    Ok(())
+    ///# }
+    ///
+    /// # async fn handle_block(_block: near_lake_primitives::block::Block, context: &MyContext) -> anyhow::Result<()> { Ok(()) }
+    ///```
+    pub async fn run_with_context_async<'context, C: LakeContextExt, E, Fut>(
+        self,
+        f: impl Fn(near_lake_primitives::block::Block, &'context C) -> Fut,
+        context: &'context C,
+    ) -> Result<RunSummary, LakeError>
+    where
+        Fut: Future<Output = Result<(), E>>,
+        E: Into<Box<dyn std::error::Error>>,
+    {
+        let result = self.run_with_context_until_stopped(f, context).await;
+        context.finalize().await;
+        result
+    }
+
+    async fn run_with_context_until_stopped<'context, C: LakeContextExt, E, Fut>(
+        self,
+        f: impl Fn(near_lake_primitives::block::Block, &'context C) -> Fut,
+        context: &'context C,
+    ) -> Result<RunSummary, LakeError>
+    where
+        Fut: Future<Output = Result<(), E>>,
+        E: Into<Box<dyn std::error::Error>>,
+    {
+        let f = &f;
+        let mut config = self;
+        if let Some(resume_store) = config.resume_store.clone() {
+            if let Some(block_height) = state_store::resume_block_height(&*resume_store).await? {
+                if block_height > config.start_block_height {
+                    tracing::info!(
+                        target: LAKE_FRAMEWORK,
+                        block_height,
+                        "Resuming from the stored block height",
+                    );
+                    config.start_block_height = block_height;
+                }
+            }
+            config.watermark_store.get_or_insert(resume_store);
+        }
+        let stats_handle = std::sync::Arc::new(types::RunStats::new());
+        let stats = &*stats_handle;
+        #[cfg(feature = "http-server")]
+        let _http_server = match config.http_server_addr {
+            Some(addr) => Some(
+                http_server::serve(
+                    addr,
+                    stats_handle.clone(),
+                    config.s3_requests_counter.clone(),
+                )
+                .await?,
+            ),
+            None => None,
+        };
+        #[cfg(feature = "block-share")]
+        let _block_share_server = match config.block_share_addr {
+            Some(addr) => {
+                let client = std::sync::Arc::new(block_share::SharingS3Client::new(
+                    streamer::lake_s3_client(&config).await,
+                ));
+                // the streamer fetches through the shared objects too, the disk cache (if any) is wrapped already
+                config.s3_client = Some(client.clone());
+                config.cache_dir = None;
+                let shared_keys = block_share::SharedKeys {
+                    bucket: config.s3_bucket_name.clone(),
+                    key_layout: config.key_layout(),
+                };
+                Some(block_share::serve(addr, client, shared_keys).await?)
+            }
+            None => None,
+        };
+        let _progress_reporter = progress::spawn(&config, stats_handle.clone());
+        let _lag_monitor = lag::spawn(&config, stats_handle.clone());
+        config.control.attach(stats_handle.clone());
+        if config.max_blocks_per_second.is_some() {
+            config
+                .control
+                .set_max_blocks_per_second(config.max_blocks_per_second);
+        }
+        let _heartbeat = heartbeat::spawn(&config);
+        #[cfg(all(unix, feature = "admin-socket"))]
+        let _admin_socket = match &config.admin_socket_path {
+            Some(path) => Some(admin_socket::serve(path, config.control.clone())?),
+            None => None,
+        };
+        #[cfg(feature = "signals")]
+        let _signal_handler = config
+            .graceful_shutdown
+            .then(|| signals::spawn(config.control.clone()));
+        // the block height the stream has been retried at last (if known) and the number of the retries in a row
+        let mut stream_retries: Option<(Option<types::BlockHeight>, u64)> = None;
+        loop {
+            // capture the values before the config moves into the streamer
+            let concurrency = config.concurrency;
+            let on_error = &config.on_error.clone();
+            let max_retries = config.max_retries;
+            // refetches the blocks the handler is retried with, the handlers own the ones they have received
+            let retry_provider = &match on_error {
+                Some(_) => Some(provider::block_provider(&config).await),
+                None => None,
+            };
+            let error_reporter = &config.error_reporter.clone();
+            let provider = config.provider_name();
+            let control = &config.control.clone();
+            let handler_timeout = config.handler_timeout;
+            let watermark_store = config.watermark_store.clone();
+            let on_caught_up = &config.on_caught_up.clone();
+            let enrichers = &config.enrichers.clone();
+            let default_handler_error_decision = if config.stop_on_handler_error {
+                types::ErrorDecision::Abort
+            } else {
+                types::ErrorDecision::Skip
+            };
+            // the block height following the last received one, 0 until the first block is received
+            let next_block_height = std::sync::atomic::AtomicU64::new(0);
+            // the heights of the blocks passed to the handler and not processed yet
+            let in_flight = &std::sync::Mutex::new(std::collections::BTreeSet::new());
+            // whether the watchdog has stopped the stream (`TakeUntil::is_stopped` is true once the stream has ended too)
+            let stalled = &std::sync::atomic::AtomicBool::new(false);
+            // the time a block has been received or handled last
+            let last_activity = &std::sync::Mutex::new(std::time::Instant::now());
 
             // instantiate the NEAR Lake Framework Stream
-            let (sender, stream) = streamer::streamer(self);
+            let (sender, mut receiver) = streamer::streamer(config.clone());
+            // report the number of the blocks waiting in the channel on every receive
+            let stream = futures::stream::poll_fn(move |cx| {
+                let streamer_message = receiver.poll_recv(cx);
+                stats.record_queued(receiver.len());
+                streamer_message
+            });
 
             // read the stream events and pass them to a handler function with
-            // concurrency 1
-            let mut handlers = tokio_stream::wrappers::ReceiverStream::new(stream)
-                .map(|streamer_message| async {
-                    let mut block: near_lake_primitives::block::Block = streamer_message.into();
+            // concurrency 1, stop taking the new blocks on shutdown (the ones being handled are drained)
+            let handlers = stream
+                .take_until(control.shutdown_requested())
+                .map(|streamer_message| {
+                    let block_height = streamer_message.block.header.height;
+                    let block_timestamp_nanosec = streamer_message.block.header.timestamp_nanosec;
+                    next_block_height.store(block_height + 1, std::sync::atomic::Ordering::Relaxed);
+                    {
+                        let mut in_flight = in_flight.lock().unwrap();
+                        in_flight.insert(block_height);
+                        stats.record_in_flight(in_flight.len());
+                    }
+                    *last_activity.lock().unwrap() = std::time::Instant::now();
+                    // the span shows which blocks are expensive to handle and why in the trace views
+                    let shards = &streamer_message.shards;
+                    let block_span = tracing::info_span!(
+                        target: LAKE_FRAMEWORK,
+                        "block",
+                        block_height,
+                        shards = shards.len(),
+                        transactions = shards
+                            .iter()
+                            .filter_map(|shard| shard.chunk.as_ref())
+                            .map(|chunk| chunk.transactions.len())
+                            .sum::<usize>(),
+                        receipts = shards
+                            .iter()
+                            .map(|shard| shard.receipt_execution_outcomes.len())
+                            .sum::<usize>(),
+                        logs = shards
+                            .iter()
+                            .flat_map(|shard| shard.receipt_execution_outcomes.iter())
+                            .map(|outcome| outcome.execution_outcome.outcome.logs.len())
+                            .sum::<usize>(),
+                        state_changes = shards
+                            .iter()
+                            .map(|shard| shard.state_changes.len())
+                            .sum::<usize>(),
+                        gas_burnt = shards
+                            .iter()
+                            .flat_map(|shard| shard.receipt_execution_outcomes.iter())
+                            .map(|outcome| outcome.execution_outcome.outcome.gas_burnt)
+                            .sum::<u64>(),
+                    );
+                    async move {
+                        let mut streamer_message = Some(streamer_message);
+                        control.wait_resumed().await;
+                        control.throttle().await;
+                        let mut attempt: u64 = 0;
+                        loop {
+                            attempt += 1;
+                            let user_indexer_function_execution_result = async {
+                                let streamer_message = match streamer_message.take() {
+                                    Some(streamer_message) => streamer_message,
+                                    None => {
+                                        retry_provider
+                                            .as_ref()
+                                            .expect("retried with on_error only")
+                                            .fetch_streamer_message(block_height)
+                                            .await?
+                                    }
+                                };
+                                let mut block: near_lake_primitives::block::Block =
+                                    streamer_message.into();
 
-                    context.execute_before_run(&mut block);
+                                let enrichments = futures::future::join_all(
+                                    enrichers.iter().map(|enricher| (enricher.0)(&block)),
+                                )
+                                .await;
+                                for attach in enrichments {
+                                    attach(block.extensions_mut());
+                                }
 
-                    let user_indexer_function_execution_result = f(block, context).await;
+                                context.execute_before_run(&mut block);
+                                let scope = block.scope().clone();
 
-                    context.execute_after_run();
+                                let result = match handler_timeout {
+                                    Some(timeout) => {
+                                        match tokio::time::timeout(timeout, f(block, context)).await
+                                        {
+                                            Ok(result) => {
+                                                result.map_err(|err| LakeError::HandlerError {
+                                                    block_height,
+                                                    error_message: err.into().to_string(),
+                                                })
+                                            }
+                                            Err(_) => Err(LakeError::HandlerTimeout {
+                                                block_height,
+                                                timeout,
+                                            }),
+                                        }
+                                    }
+                                    None => f(block, context).await.map_err(|err| {
+                                        LakeError::HandlerError {
+                                            block_height,
+                                            error_message: err.into().to_string(),
+                                        }
+                                    }),
+                                };
 
-                    user_indexer_function_execution_result
+                                context.execute_after_run_with_scope(&scope);
+                                result
+                            }
+                            .await;
+
+                            let Err(err) = user_indexer_function_execution_result else {
+                                stats.record_block(block_height, block_timestamp_nanosec);
+                                return Ok(block_height);
+                            };
+                            stats.record_handler_failure();
+                            if let Some(error_reporter) = error_reporter {
+                                error_reporter.report(&ErrorReport {
+                                    source: ErrorSource::Handler,
+                                    error: &err,
+                                    block_height: Some(block_height),
+                                    shard_id: None,
+                                    provider,
+                                    attempt,
+                                });
+                            }
+                            let decision = match on_error
+                                .as_ref()
+                                .map(|on_error| (on_error.0)(&err, Some(block_height)))
+                                .unwrap_or(default_handler_error_decision)
+                            {
+                                types::ErrorDecision::Retry if attempt > max_retries => {
+                                    tracing::warn!(
+                                        target: LAKE_FRAMEWORK,
+                                        block_height,
+                                        retries = max_retries,
+                                        "The retries of the block are exhausted",
+                                    );
+                                    default_handler_error_decision
+                                }
+                                decision => decision,
+                            };
+                            match decision {
+                                types::ErrorDecision::Retry => {
+                                    tracing::warn!(
+                                        target: LAKE_FRAMEWORK,
+                                        block_height,
+                                        attempt,
+                                        error = %err,
+                                        "Handler failed to process the block. Retrying",
+                                    );
+                                }
+                                types::ErrorDecision::Abort => return Err(err),
+                                types::ErrorDecision::Skip => {
+                                    tracing::warn!(
+                                        target: LAKE_FRAMEWORK,
+                                        block_height,
+                                        error = %err,
+                                        "Handler failed to process the block. Skipping",
+                                    );
+                                    stats.record_block(block_height, block_timestamp_nanosec);
+                                    return Ok(block_height);
+                                }
+                            }
+                        }
+                    }
+                    .instrument(block_span)
                 })
-                .buffer_unordered(concurrency);
+                .buffer_unordered(concurrency)
+                .take_until(control.seek_requested())
+                .take_until(futures::FutureExt::map(
+                    watchdog::stalled(&config, last_activity, in_flight, &next_block_height),
+                    |()| stalled.store(true, std::sync::atomic::Ordering::Relaxed),
+                ));
+            // the stop futures are not `Unpin`
+            let mut handlers = Box::pin(handlers);
 
-            while let Some(_handle_message) = handlers.next().await {}
+            let mut handler_error = None;
+            // the processed blocks above the lowest in-flight one
+            let mut processed = std::collections::BTreeSet::new();
+            while let Some(handler_result) = handlers.next().await {
+                let block_height = match handler_result {
+                    Ok(block_height) => block_height,
+                    Err(err) => {
+                        handler_error = Some(err);
+                        break;
+                    }
+                };
+                *last_activity.lock().unwrap() = std::time::Instant::now();
+                let lowest_in_flight = {
+                    let mut in_flight = in_flight.lock().unwrap();
+                    in_flight.remove(&block_height);
+                    stats.record_in_flight(in_flight.len());
+                    in_flight.first().copied().unwrap_or(u64::MAX)
+                };
+                if let Some(caught_up_block_height) =
+                    control.take_caught_up(block_height, lowest_in_flight)
+                {
+                    tracing::info!(
+                        target: LAKE_FRAMEWORK,
+                        block_height = caught_up_block_height,
+                        "Caught up with the chain head, following the tip",
+                    );
+                    if let Some(on_caught_up) = on_caught_up {
+                        (on_caught_up.0)(caught_up_block_height);
+                    }
+                }
+                let Some(watermark_store) = &watermark_store else {
+                    continue;
+                };
+                processed.insert(block_height);
+                let Some(watermark) = processed.range(..lowest_in_flight).next_back().copied()
+                else {
+                    continue;
+                };
+                processed = processed.split_off(&(watermark + 1));
+                if let Err(err) = watermark_store
+                    .set(state_store::WATERMARK_KEY, &watermark.to_string())
+                    .await
+                {
+                    tracing::warn!(
+                        target: LAKE_FRAMEWORK,
+                        block_height = watermark,
+                        error = %err,
+                        "Failed to publish the watermark",
+                    );
+                }
+            }
+            let stalled = stalled.load(std::sync::atomic::Ordering::Relaxed);
             drop(handlers); // close the channel so the sender will stop
 
+            if let Some(err) = handler_error {
+                sender.abort();
+                return Err(err);
+            }
+
+            if let Some(block_height) = control.take_seek() {
+                sender.abort();
+                tracing::info!(
+                    target: LAKE_FRAMEWORK,
+                    block_height,
+                    "Restarting the stream from the requested block height",
+                );
+                config.start_block_height = block_height;
+                continue;
+            }
+
+            if control.is_shutting_down() {
+                sender.abort();
+                let summary = stats.summary();
+                if let (Some(watermark_store), Some(last_block_height)) =
+                    (&watermark_store, summary.last_block_height)
+                {
+                    watermark_store.set_last_height(last_block_height).await?;
+                }
+                tracing::info!(
+                    target: LAKE_FRAMEWORK,
+                    block_height = summary.last_block_height,
+                    "The indexer has been shut down gracefully",
+                );
+                return Ok(summary);
+            }
+
+            if stalled {
+                sender.abort();
+                // the blocks received after the watchdog has resolved are dropped unfinished, restart from the lowest of them
+                let lowest_in_flight = in_flight.lock().unwrap().first().copied();
+                config.start_block_height = match (
+                    lowest_in_flight,
+                    next_block_height.load(std::sync::atomic::Ordering::Relaxed),
+                ) {
+                    (Some(block_height), _) => block_height,
+                    (None, 0) => config.start_block_height,
+                    (None, next_block_height) => next_block_height,
+                };
+                tracing::warn!(
+                    target: LAKE_FRAMEWORK,
+                    block_height = config.start_block_height,
+                    "The stream has stalled. Restarting it",
+                );
+                continue;
+            }
+
             // propagate errors from the sender
-            match sender.await {
-                Ok(Ok(())) => Ok(()),
-                Ok(Err(err)) => Err(err),
-                Err(err) => Err(err.into()), // JoinError
+            let err = match sender.await {
+                Ok(Ok(())) => return Ok(stats.summary()),
+                Ok(Err(err)) => err,
+                Err(err) => return Err(err.into()), // JoinError
+            };
+            let block_height = err.block_height();
+            let stream_failures = stats.record_stream_failure();
+            if let Some(error_reporter) = error_reporter {
+                error_reporter.report(&ErrorReport {
+                    source: ErrorSource::Fetch,
+                    error: &err,
+                    block_height,
+                    shard_id: err.shard_id(),
+                    provider,
+                    attempt: stream_failures,
+                });
             }
-        })
+            let decision = match on_error
+                .as_ref()
+                .map(|on_error| (on_error.0)(&err, block_height))
+                .unwrap_or(types::ErrorDecision::Abort)
+            {
+                types::ErrorDecision::Retry => {
+                    let retries = match stream_retries {
+                        Some((retried_block_height, retries))
+                            if retried_block_height == block_height =>
+                        {
+                            retries + 1
+                        }
+                        _ => 1,
+                    };
+                    stream_retries = Some((block_height, retries));
+                    if retries > config.max_retries {
+                        tracing::warn!(
+                            target: LAKE_FRAMEWORK,
+                            block_height,
+                            retries = config.max_retries,
+                            "The retries of the block are exhausted",
+                        );
+                        types::ErrorDecision::Abort
+                    } else {
+                        types::ErrorDecision::Retry
+                    }
+                }
+                decision => decision,
+            };
+            let next_block_height =
+                match next_block_height.load(std::sync::atomic::Ordering::Relaxed) {
+                    0 => None,
+                    next_block_height => Some(next_block_height),
+                };
+            config.start_block_height = match (decision, block_height) {
+                (types::ErrorDecision::Abort, _) => return Err(err),
+                (types::ErrorDecision::Retry, Some(block_height)) => block_height,
+                (types::ErrorDecision::Skip, Some(block_height)) => block_height + 1,
+                (_, None) => next_block_height.unwrap_or(config.start_block_height),
+            };
+            tracing::warn!(
+                target: LAKE_FRAMEWORK,
+                block_height = config.start_block_height,
+                error = %err,
+                "The stream has failed. Restarting it",
+            );
+        }
+    }
+
+    /// Returns the [ControlHandle](crate::ControlHandle) to pause, resume, seek and check the status
+    /// of the indexer once it is running
+    pub fn control_handle(&self) -> ControlHandle {
+        self.control.clone()
+    }
+
+    /// Sets up the callback to decide what to do on errors: skip the block, retry it, or abort.
+    ///
+    /// The callback is called with the error and the block height it has happened at (if known) both for
+    /// the errors returned by the handler and the errors that have stopped the streamer.
+    /// Without the callback the handler errors are skipped (unless [LakeBuilder::stop_on_handler_error](crate::LakeBuilder::stop_on_handler_error) is set)
+    /// and the streamer errors abort [Lake::run](crate::Lake::run)
+    ///```no_run
+    /// use near_lake_framework::{ErrorDecision, ErrorKind};
+    ///
+    ///# fn main() -> anyhow::Result<()> {
+    ///    near_lake_framework::LakeBuilder::default()
+    ///        .testnet()
+    ///        .start_block_height(112205773)
+    ///        .build()?
+    ///        .on_error(|err, block_height| match err.kind() {
+    ///            ErrorKind::Transient => ErrorDecision::Retry,
+    ///            _ => {
+    ///                eprintln!("Failed to process block {:?}: {}", block_height, err);
+    ///                ErrorDecision::Abort
+    ///            }
+    ///        })
+    ///        .run(handle_block)?;
+    ///    Ok(())
+    ///# }
+    ///
+    /// # async fn handle_block(_block: near_lake_primitives::block::Block) -> anyhow::Result<()> { Ok(()) }
+    ///```
+    pub fn on_error(
+        mut self,
+        f: impl Fn(&LakeError, Option<types::BlockHeight>) -> types::ErrorDecision
+            + Send
+            + Sync
+            + 'static,
+    ) -> Self {
+        self.on_error = Some(types::ErrorCallback(std::sync::Arc::new(f)));
+        self
+    }
+
+    /// Sets up the callback to receive the [Progress](crate::Progress) reports every [LakeBuilder::progress_interval](crate::LakeBuilder::progress_interval),
+    /// e.g. to export the ETA of the backfill to the monitoring
+    ///```no_run
+    ///# fn main() -> anyhow::Result<()> {
+    ///    near_lake_framework::LakeBuilder::default()
+    ///        .mainnet()
+    ///        .start_block_height(9820210)
+    ///        .progress_interval(std::time::Duration::from_secs(60))
+    ///        .build()?
+    ///        .on_progress(|progress| {
+    ///            println!(
+    ///                "{} blocks behind, {:.1} blocks/s, ETA {:?}",
+    ///                progress.blocks_remaining, progress.blocks_per_second, progress.eta,
+    ///            );
+    ///        })
+    ///        .run(handle_block)?;
+    ///    Ok(())
+    ///# }
+    ///
+    /// # async fn handle_block(_block: near_lake_primitives::block::Block) -> anyhow::Result<()> { Ok(()) }
+    ///```
+    pub fn on_progress(mut self, f: impl Fn(&types::Progress) + Send + Sync + 'static) -> Self {
+        self.on_progress = Some(types::ProgressCallback(std::sync::Arc::new(f)));
+        self
+    }
+
+    /// Sets up the callback called once the indexer has caught up with the chain head: the streamer has reached
+    /// the latest block in the bucket and the handler has finished with all the blocks up to it (its height is passed),
+    /// e.g. to enable the user-facing notifications only after the historical blocks are processed.
+    /// It is called once per run, see [ControlHandle::is_caught_up](crate::ControlHandle::is_caught_up) as well
+    ///```no_run
+    ///# fn main() -> anyhow::Result<()> {
+    ///    near_lake_framework::LakeBuilder::default()
+    ///        .mainnet()
+    ///        .start_block_height(9820210)
+    ///        .build()?
+    ///        .on_caught_up(|block_height| {
+    ///            println!("Switched to the tip-following at #{}", block_height);
+    ///        })
+    ///        .run(handle_block)?;
+    ///    Ok(())
+    ///# }
+    ///
+    /// # async fn handle_block(_block: near_lake_primitives::block::Block) -> anyhow::Result<()> { Ok(()) }
+    ///```
+    pub fn on_caught_up(mut self, f: impl Fn(types::BlockHeight) + Send + Sync + 'static) -> Self {
+        self.on_caught_up = Some(types::CaughtUpCallback(std::sync::Arc::new(f)));
+        self
+    }
+
+    /// Sets up the callback to alert on the [Lag](crate::Lag) behind the chain head exceeding the threshold (in blocks).
+    /// The callback is called on every probe of the chain head ([LakeBuilder::lag_probe_interval](crate::LakeBuilder::lag_probe_interval))
+    /// while the lag exceeds the threshold
+    ///```no_run
+    ///# fn main() -> anyhow::Result<()> {
+    ///    near_lake_framework::LakeBuilder::default()
+    ///        .mainnet()
+    ///        .start_block_height(9820210)
+    ///        .lag_probe_interval(std::time::Duration::from_secs(30))
+    ///        .build()?
+    ///        .on_lag_alert(100, |lag| {
+    ///            eprintln!("{:?} blocks ({:?}) behind the chain head", lag.blocks, lag.seconds);
+    ///        })
+    ///        .run(handle_block)?;
+    ///    Ok(())
+    ///# }
+    ///
+    /// # async fn handle_block(_block: near_lake_primitives::block::Block) -> anyhow::Result<()> { Ok(()) }
+    ///```
+    pub fn on_lag_alert(
+        mut self,
+        threshold: u64,
+        f: impl Fn(&types::Lag) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_lag_alert = Some(types::LagAlert(threshold, std::sync::Arc::new(f)));
+        self
+    }
+
+    /// Sets up the callback to be notified when the stream stalls (see [LakeBuilder::stall_timeout](crate::LakeBuilder::stall_timeout))
+    /// and to decide whether to restart it. The callback is called with the block height the stream is waiting for
+    /// and the time no blocks have been received for, and returns `true` to restart the stream or `false` to keep waiting
+    ///```no_run
+    ///# fn main() -> anyhow::Result<()> {
+    ///    near_lake_framework::LakeBuilder::default()
+    ///        .testnet()
+    ///        .start_block_height(112205773)
+    ///        .stall_timeout(std::time::Duration::from_secs(60))
+    ///        .build()?
+    ///        .on_stall(|block_height, stalled_for| {
+    ///            eprintln!("Waiting for block #{} for {:?}, restarting the stream", block_height, stalled_for);
+    ///            true
+    ///        })
+    ///        .run(handle_block)?;
+    ///    Ok(())
+    ///# }
+    ///
+    /// # async fn handle_block(_block: near_lake_primitives::block::Block) -> anyhow::Result<()> { Ok(()) }
+    ///```
+    pub fn on_stall(
+        mut self,
+        f: impl Fn(types::BlockHeight, std::time::Duration) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.on_stall = Some(types::StallCallback(std::sync::Arc::new(f)));
+        self
+    }
+
+    /// Sets up the callback to be called every `interval` with the [StreamStatus](crate::StreamStatus) of the running indexer
+    /// (the last processed block height, the numbers of the blocks being handled and waiting in the channel, the error counters),
+    /// so the liveness data can be pushed into the monitoring system of your choice
+    ///```no_run
+    ///# fn main() -> anyhow::Result<()> {
+    ///    near_lake_framework::LakeBuilder::default()
+    ///        .testnet()
+    ///        .start_block_height(112205773)
+    ///        .build()?
+    ///        .on_heartbeat(std::time::Duration::from_secs(10), |status| {
+    ///            println!(
+    ///                "#{:?}: {} in flight, {} queued",
+    ///                status.last_block_height, status.in_flight, status.queued,
+    ///            );
+    ///        })
+    ///        .run(handle_block)?;
+    ///    Ok(())
+    ///# }
+    ///
+    /// # async fn handle_block(_block: near_lake_primitives::block::Block) -> anyhow::Result<()> { Ok(()) }
+    ///```
+    pub fn on_heartbeat(
+        mut self,
+        interval: std::time::Duration,
+        f: impl Fn(StreamStatus) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_heartbeat = Some(types::Heartbeat(interval, std::sync::Arc::new(f)));
+        self
+    }
+
+    /// Registers an async function to enrich every block after it is fetched but before it is passed to the handler.
+    ///
+    /// The enrichment functions run concurrently, and the value each of them returns is attached to the
+    /// [Block::extensions](near_lake_primitives::block::Block::extensions) map, so the enrichment logic
+    /// (e.g. fetching token metadata from RPC) can be reused across handlers. The returned future can't borrow
+    /// the block, so clone the data it needs.
+    ///```no_run
+    /// struct ReceiptsCount(usize);
+    ///
+    ///# fn main() -> anyhow::Result<()> {
+    ///    near_lake_framework::LakeBuilder::default()
+    ///        .testnet()
+    ///        .start_block_height(112205773)
+    ///        .build()?
+    ///        .enrich(|block| {
+    ///            let receipts_count = block.streamer_message().shards.iter()
+    ///                .map(|shard| shard.receipt_execution_outcomes.len())
+    ///                .sum();
+    ///            async move { ReceiptsCount(receipts_count) }
+    ///        })
+    ///        .run(handle_block)?;
+    ///    Ok(())
+    ///# }
+    ///
+    /// async fn handle_block(block: near_lake_primitives::block::Block) -> anyhow::Result<()> {
+    ///     if let Some(ReceiptsCount(receipts_count)) = block.extensions().get::<ReceiptsCount>() {
+    ///         println!("Block #{} has {} receipts", block.block_height(), receipts_count);
+    ///     }
+    ///     Ok(())
+    /// }
+    ///```
+    pub fn enrich<T, Fut>(
+        mut self,
+        f: impl Fn(&near_lake_primitives::block::Block) -> Fut + Send + Sync + 'static,
+    ) -> Self
+    where
+        T: Send + Sync + 'static,
+        Fut: Future<Output = T> + Send + 'static,
+    {
+        self.enrichers
+            .push(types::Enricher(std::sync::Arc::new(move |block| {
+                let enrichment = f(block);
+                Box::pin(async move {
+                    let value = enrichment.await;
+                    Box::new(
+                        move |extensions: &mut near_lake_primitives::block::Extensions| {
+                            extensions.insert(value);
+                        },
+                    )
+                        as Box<dyn FnOnce(&mut near_lake_primitives::block::Extensions) + Send>
+                })
+            })));
+        self
     }
 
     /// Creates `mpsc::channel` and returns the `receiver` to read the stream of `StreamerMessage`
@@ -108,21 +963,625 @@ impl types::Lake {
     pub fn run<Fut, E>(
         self,
         f: impl Fn(near_lake_primitives::block::Block) -> Fut,
-    ) -> Result<(), LakeError>
+    ) -> Result<RunSummary, LakeError>
     where
         Fut: Future<Output = Result<(), E>>,
         E: Into<Box<dyn std::error::Error>>,
     {
-        struct EmptyContext {}
+        let context = EmptyContext {};
+
+        self.run_with_context(|block, _context| f(block), &context)
+    }
 
-        impl LakeContextExt for EmptyContext {
-            fn execute_before_run(&self, _block: &mut near_lake_primitives::block::Block) {}
+    /// The async version of [Lake::run] that runs inside the caller's runtime
+    /// instead of creating its own one and blocking the thread
+    ///```no_run
+    ///# #[tokio::main]
+    ///# async fn main() -> anyhow::Result<()> {
+    ///    near_lake_framework::LakeBuilder::default()
+    ///        .testnet()
+    ///        .start_block_height(112205773)
+    ///        .build()?
+    ///        .run_async(handle_block)
+    ///        .await?;
+    ///    Ok(())
+    ///# }
+    ///
+    /// # async fn handle_block(_block: near_lake_primitives::block::Block) -> anyhow::Result<()> { Ok(()) }
+    ///```
+    pub async fn run_async<Fut, E>(
+        self,
+        f: impl Fn(near_lake_primitives::block::Block) -> Fut,
+    ) -> Result<RunSummary, LakeError>
+    where
+        Fut: Future<Output = Result<(), E>>,
+        E: Into<Box<dyn std::error::Error>>,
+    {
+        let context = EmptyContext {};
+
+        self.run_with_context_async(|block, _context| f(block), &context)
+            .await
+    }
+
+    /// The version of [Lake::run] accepting the `FnMut` handler, so it can keep the simple local mutable state
+    /// (counters, batching buffers) without the interior mutability.
+    ///
+    /// The blocks are handled one by one (the [LakeBuilder::concurrency](crate::LakeBuilder::concurrency) is ignored):
+    /// the handler is called for the next block once the future of the previous one has completed.
+    /// The future can't borrow the state, so do the stateful work in the handler before returning the future
+    ///```no_run
+    ///# fn main() -> anyhow::Result<()> {
+    ///    let mut receipts_count = 0;
+    ///    let mut batch = vec![];
+    ///
+    ///    near_lake_framework::LakeBuilder::default()
+    ///        .testnet()
+    ///        .start_block_height(112205773)
+    ///        .build()?
+    ///        .run_mut(|mut block| {
+    ///            receipts_count += block.receipts().count();
+    ///            batch.push(block.block_height());
+    ///            let flushed = (batch.len() == 100).then(|| std::mem::take(&mut batch));
+    ///            println!("{} receipts so far", receipts_count);
+    ///            async move {
+    ///                if let Some(block_heights) = flushed {
+    ///                    println!("Flushing {} blocks", block_heights.len());
+    ///                }
+    ///                Ok::<_, anyhow::Error>(())
+    ///            }
+    ///        })?;
+    ///    Ok(())
+    ///# }
+    ///```
+    pub fn run_mut<Fut, E>(
+        self,
+        f: impl FnMut(near_lake_primitives::block::Block) -> Fut,
+    ) -> Result<RunSummary, LakeError>
+    where
+        Fut: Future<Output = Result<(), E>>,
+        E: Into<Box<dyn std::error::Error>>,
+    {
+        let runtime = tokio::runtime::Runtime::new()
+            .map_err(|err| LakeError::RuntimeStartError { error: err })?;
+
+        runtime.block_on(self.run_mut_async(f))
+    }
+
+    /// The async version of [Lake::run_mut] that runs inside the caller's runtime
+    /// instead of creating its own one and blocking the thread
+    pub async fn run_mut_async<Fut, E>(
+        mut self,
+        f: impl FnMut(near_lake_primitives::block::Block) -> Fut,
+    ) -> Result<RunSummary, LakeError>
+    where
+        Fut: Future<Output = Result<(), E>>,
+        E: Into<Box<dyn std::error::Error>>,
+    {
+        self.concurrency = 1;
+        let f = std::sync::Mutex::new(f);
 
-            fn execute_after_run(&self) {}
+        self.run_async(|block| {
+            let mut f = f.lock().unwrap();
+            (*f)(block)
+        })
+        .await
+    }
+
+    /// Runs the indexer with the [BlockHandler] implemented on a struct instead of the closure,
+    /// see [BlockHandler] for the example
+    pub fn run_handler(self, handler: impl BlockHandler) -> Result<RunSummary, LakeError> {
+        let runtime = tokio::runtime::Runtime::new()
+            .map_err(|err| LakeError::RuntimeStartError { error: err })?;
+
+        runtime.block_on(self.run_handler_async(handler))
+    }
+
+    /// The async version of [Lake::run_handler] that runs inside the caller's runtime
+    /// instead of creating its own one and blocking the thread
+    pub async fn run_handler_async(
+        self,
+        handler: impl BlockHandler,
+    ) -> Result<RunSummary, LakeError> {
+        let handler = &handler;
+
+        self.run_async(|block| handler.handle(block)).await
+    }
+
+    /// Runs the indexer calling the handler for every event matching the [EventFilter](crate::EventFilter)
+    /// instead of every block. The events of a block are handled one by one in the order they were emitted
+    ///```no_run
+    /// use near_lake_framework::EventFilter;
+    ///
+    ///# fn main() -> anyhow::Result<()> {
+    ///    near_lake_framework::LakeBuilder::default()
+    ///        .mainnet()
+    ///        .start_block_height(88444526)
+    ///        .build()?
+    ///        .subscribe_events(
+    ///            EventFilter::default().standard("nep171").event("nft_mint"),
+    ///            handle_event,
+    ///        )?;
+    ///    Ok(())
+    ///# }
+    ///
+    /// # async fn handle_event(event: near_lake_primitives::events::Event) -> anyhow::Result<()> {
+    /// #     println!("{:?}", event.data());
+    /// #     Ok(())
+    /// # }
+    ///```
+    pub fn subscribe_events<Fut, E>(
+        self,
+        filter: types::EventFilter,
+        f: impl Fn(near_lake_primitives::events::Event) -> Fut,
+    ) -> Result<RunSummary, LakeError>
+    where
+        Fut: Future<Output = Result<(), E>>,
+        E: Into<Box<dyn std::error::Error>>,
+    {
+        let f = &f;
+        self.run(|mut block| {
+            let events: Vec<near_lake_primitives::events::Event> = block
+                .events()
+                .filter(|event| filter.matches(event))
+                .cloned()
+                .collect();
+            async move {
+                for event in events {
+                    f(event).await?;
+                }
+                Ok::<(), E>(())
+            }
+        })
+    }
+
+    /// Spawns the indexer on the caller's runtime and returns the `JoinHandle`,
+    /// so it can be supervised or aborted
+    ///```no_run
+    ///# #[tokio::main]
+    ///# async fn main() -> anyhow::Result<()> {
+    ///    let handle = near_lake_framework::LakeBuilder::default()
+    ///        .testnet()
+    ///        .start_block_height(112205773)
+    ///        .build()?
+    ///        .spawn(handle_block);
+    ///
+    ///    // do other work, abort the indexer with `handle.abort()` if needed
+    ///    handle.await??;
+    ///    Ok(())
+    ///# }
+    ///
+    /// # async fn handle_block(_block: near_lake_primitives::block::Block) -> anyhow::Result<()> { Ok(()) }
+    ///```
+    pub fn spawn<Fut, E>(
+        self,
+        f: impl Fn(near_lake_primitives::block::Block) -> Fut + Send + Sync + 'static,
+    ) -> tokio::task::JoinHandle<Result<RunSummary, LakeError>>
+    where
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: Into<Box<dyn std::error::Error>> + 'static,
+    {
+        tokio::spawn(self.run_async(f))
+    }
+
+    /// The version of [Lake::spawn] with the context, see [Lake::run_with_shared_context]
+    ///```no_run
+    /// use std::sync::Arc;
+    ///
+    /// #[derive(near_lake_framework::LakeContext)]
+    ///  struct MyContext {
+    ///      my_field: String,
+    ///  }
+    ///
+    ///# #[tokio::main]
+    ///# async fn main() -> anyhow::Result<()> {
+    ///    let context = Arc::new(MyContext {
+    ///        my_field: "my_value".to_string(),
+    ///    });
+    ///    let handle = near_lake_framework::LakeBuilder::default()
+    ///        .testnet()
+    ///        .start_block_height(112205773)
+    ///        .build()?
+    ///        .spawn_with_context(handle_block, context.clone());
+    ///
+    ///    handle.await??;
+    ///    Ok(())
+    ///# }
+    ///
+    /// # async fn handle_block(_block: near_lake_primitives::block::Block, context: Arc<MyContext>) -> anyhow::Result<()> { Ok(()) }
+    ///```
+    pub fn spawn_with_context<C, E, Fut>(
+        self,
+        f: impl Fn(near_lake_primitives::block::Block, std::sync::Arc<C>) -> Fut + Send + Sync + 'static,
+        context: impl Into<std::sync::Arc<C>>,
+    ) -> tokio::task::JoinHandle<Result<RunSummary, LakeError>>
+    where
+        C: LakeContextExt + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: Into<Box<dyn std::error::Error>> + 'static,
+    {
+        tokio::spawn(self.run_with_shared_context_async(f, context.into()))
+    }
+
+    /// Spawns only the streamer writing the blocks to the given channel, for embedding the stream
+    /// into the applications with their own delivery (the [ChannelStrategy] of the [Lake] is ignored then).
+    /// The handlers, the hooks and the restarts of the [Lake::run] are not involved: the streamer stops
+    /// once the end block height is reached, the receivers are dropped or the stream fails
+    ///```no_run
+    ///# #[tokio::main]
+    ///# async fn main() -> anyhow::Result<()> {
+    ///    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+    ///    let handle = near_lake_framework::LakeBuilder::default()
+    ///        .testnet()
+    ///        .start_block_height(112205773)
+    ///        .build()?
+    ///        .stream_into(sender);
+    ///
+    ///    while let Some(streamer_message) = receiver.recv().await {
+    ///        println!("Block #{}", streamer_message.block.header.height);
+    ///    }
+    ///    handle.await??;
+    ///    Ok(())
+    ///# }
+    ///```
+    pub fn stream_into(
+        self,
+        sender: impl Into<StreamerMessageSender>,
+    ) -> tokio::task::JoinHandle<Result<(), LakeError>> {
+        tokio::spawn(streamer::start(sender.into(), self))
+    }
+
+    /// Enumerates the available block heights from `from_block_height` to `to_block_height` (both inclusive)
+    /// in the ascending order with the configured [BlockProvider] (listing the bucket by default) without fetching the blocks,
+    /// so the tools can audit the data availability and plan the backfills without starting the stream.
+    /// The heights are listed page by page as the stream is polled
+    ///```no_run
+    /// use futures::TryStreamExt;
+    ///
+    ///# #[tokio::main]
+    ///# async fn main() -> anyhow::Result<()> {
+    ///    let lake = near_lake_framework::LakeBuilder::default()
+    ///        .mainnet()
+    ///        .start_block_height(88444526)
+    ///        .build()?;
+    ///
+    ///    let block_heights: Vec<u64> = lake.block_heights(88444526, 88445525).try_collect().await?;
+    ///    println!("{} of 1000 heights have the blocks (the rest are skipped)", block_heights.len());
+    ///    Ok(())
+    ///# }
+    ///```
+    pub fn block_heights(
+        &self,
+        from_block_height: types::BlockHeight,
+        to_block_height: types::BlockHeight,
+    ) -> impl futures::Stream<Item = Result<types::BlockHeight, LakeError>> + '_ {
+        use futures::TryStreamExt;
+
+        futures::stream::once(provider::block_provider(self)).flat_map(move |block_provider| {
+            futures::stream::try_unfold(Some(from_block_height), move |start_from_block_height| {
+                let block_provider = block_provider.clone();
+                async move {
+                    let Some(start_from_block_height) =
+                        start_from_block_height.filter(|height| *height <= to_block_height)
+                    else {
+                        return Ok::<_, LakeError>(None);
+                    };
+                    let block_heights = block_provider
+                        .list_block_heights(start_from_block_height)
+                        .await?;
+                    let Some(last_block_height) = block_heights.last().copied() else {
+                        return Ok(None);
+                    };
+                    let block_heights: Vec<_> = block_heights
+                        .into_iter()
+                        .take_while(|height| *height <= to_block_height)
+                        .collect();
+                    Ok(Some((block_heights, last_block_height.checked_add(1))))
+                }
+            })
+            .map_ok(|block_heights| futures::stream::iter(block_heights.into_iter().map(Ok)))
+            .try_flatten()
+        })
+    }
+
+    /// Fetches the block with the given hash (e.g. taken from the RPC receipts) with the configured [BlockProvider].
+    /// Returns `None` if the provider doesn't know the hash: the NEAR Lake buckets don't index the hashes,
+    /// so it needs either the custom provider implementing [BlockProvider::block_height_by_hash]
+    /// or the lake written by the [S3RepublishSink] with the [block hash index](S3RepublishSink::with_block_hash_index)
+    ///```no_run
+    ///# #[tokio::main]
+    ///# async fn main() -> anyhow::Result<()> {
+    ///    let lake = near_lake_framework::LakeBuilder::default()
+    ///        .s3_bucket_name("my-private-lake")
+    ///        .s3_region_name("eu-central-1")
+    ///        .start_block_height(88444526)
+    ///        .build()?;
+    ///
+    ///    let block_hash: near_lake_framework::near_lake_primitives::CryptoHash =
+    ///        "9vBUZX1dxE7pF2RQCaaYqEEpGq5DqNZvzzzDm7yy4GZW".parse().map_err(|err| anyhow::anyhow!("{:?}", err))?;
+    ///    if let Some(block) = lake.fetch_block_by_hash(block_hash).await? {
+    ///        println!("Block #{}", block.block_height());
+    ///    }
+    ///    Ok(())
+    ///# }
+    ///```
+    pub async fn fetch_block_by_hash(
+        &self,
+        block_hash: near_lake_primitives::CryptoHash,
+    ) -> Result<Option<near_lake_primitives::block::Block>, LakeError> {
+        let block_provider = provider::block_provider(self).await;
+        let Some(block_height) = block_provider.block_height_by_hash(block_hash).await? else {
+            return Ok(None);
+        };
+        let streamer_message = block_provider
+            .fetch_streamer_message(block_height)
+            .await
+            .map_err(|err| err.at_block(block_height, None))?;
+        if streamer_message.block.header.hash != block_hash {
+            return Err(LakeError::InternalError {
+                error_message: format!(
+                    "The block hash {} is indexed as the block #{} that has the hash {}",
+                    block_hash, block_height, streamer_message.block.header.hash
+                ),
+            });
         }
+        Ok(Some(streamer_message.into()))
+    }
 
-        let context = EmptyContext {};
+    /// Fetches the block of the given height with the configured [BlockProvider], with the same retries and parsing
+    /// (the [LakeBuilder::track_shards](crate::LakeBuilder::track_shards) included) the stream has,
+    /// e.g. to backfill the specific heights found missing during the reconciliation.
+    ///
+    /// Returns [LakeError::BlockNotFound] if there is no block at the height
+    ///```no_run
+    ///# #[tokio::main]
+    ///# async fn main() -> anyhow::Result<()> {
+    ///    let lake = near_lake_framework::LakeBuilder::default()
+    ///        .mainnet()
+    ///        .start_block_height(88444526)
+    ///        .build()?;
+    ///
+    ///    for block_height in [88444526, 88444530] {
+    ///        let mut block = lake.get_block(block_height).await?;
+    ///        println!("Block #{}: {} receipts", block.block_height(), block.receipts().count());
+    ///    }
+    ///    Ok(())
+    ///# }
+    ///```
+    pub async fn get_block(
+        &self,
+        block_height: types::BlockHeight,
+    ) -> Result<near_lake_primitives::block::Block, LakeError> {
+        self.get_streamer_message(block_height)
+            .await
+            .map(Into::into)
+    }
 
-        self.run_with_context(|block, _context| f(block), &context)
+    /// The version of [Lake::get_block] returning the low-level [StreamerMessage](near_indexer_primitives::StreamerMessage)
+    pub async fn get_streamer_message(
+        &self,
+        block_height: types::BlockHeight,
+    ) -> Result<near_indexer_primitives::StreamerMessage, LakeError> {
+        let block_provider = provider::block_provider(self).await;
+        // the fetchers wait for the blocks that aren't written yet, so make sure the height isn't skipped first
+        let block_heights = block_provider.list_block_heights(block_height).await?;
+        if block_heights.first() != Some(&block_height) {
+            return Err(LakeError::BlockNotFound { block_height });
+        }
+        block_provider
+            .fetch_streamer_message(block_height)
+            .await
+            .map_err(|err| err.at_block(block_height, None))
+    }
+}
+
+struct EmptyContext {}
+
+impl LakeContextExt for EmptyContext {
+    fn execute_before_run(&self, _block: &mut near_lake_primitives::block::Block) {}
+
+    fn execute_after_run(&self) {}
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use async_trait::async_trait;
+    use aws_sdk_s3::error::SdkError;
+    use aws_sdk_s3::operation::get_object::{GetObjectError, GetObjectOutput};
+    use aws_sdk_s3::operation::list_objects_v2::{ListObjectsV2Error, ListObjectsV2Output};
+    use futures::TryStreamExt;
+
+    /// Lists the even block heights up to #3000, up to 1000 of them per page
+    #[derive(Debug)]
+    struct SparseS3Client {}
+
+    #[async_trait]
+    impl S3Client for SparseS3Client {
+        async fn get_object(
+            &self,
+            _bucket: &str,
+            _prefix: &str,
+        ) -> Result<GetObjectOutput, SdkError<GetObjectError>> {
+            unimplemented!()
+        }
+
+        async fn list_objects(
+            &self,
+            _bucket: &str,
+            start_after: &str,
+            _continuation_token: Option<String>,
+        ) -> Result<ListObjectsV2Output, SdkError<ListObjectsV2Error>> {
+            let start: u64 = start_after.parse().unwrap();
+            let common_prefixes = (start..=3000)
+                .filter(|block_height| block_height % 2 == 0)
+                .take(1000)
+                .map(|block_height| {
+                    aws_sdk_s3::types::CommonPrefix::builder()
+                        .prefix(format!("{:0>12}/", block_height))
+                        .build()
+                })
+                .collect();
+            Ok(ListObjectsV2Output::builder()
+                .set_common_prefixes(Some(common_prefixes))
+                .build())
+        }
+    }
+
+    /// Serves the fixture block #879765, the last block of the bucket
+    #[derive(Debug)]
+    pub(crate) struct FixtureS3Client {}
+
+    #[async_trait]
+    impl S3Client for FixtureS3Client {
+        async fn get_object(
+            &self,
+            _bucket: &str,
+            prefix: &str,
+        ) -> Result<GetObjectOutput, SdkError<GetObjectError>> {
+            let path = format!("{}/blocks/{}", env!("CARGO_MANIFEST_DIR"), prefix);
+            let body = tokio::fs::read(path).await.unwrap();
+            Ok(GetObjectOutput::builder()
+                .body(aws_sdk_s3::primitives::ByteStream::from(body))
+                .build())
+        }
+
+        async fn list_objects(
+            &self,
+            _bucket: &str,
+            start_after: &str,
+            _continuation_token: Option<String>,
+        ) -> Result<ListObjectsV2Output, SdkError<ListObjectsV2Error>> {
+            let common_prefixes = ["000000879765/"]
+                .into_iter()
+                .filter(|prefix| *prefix > start_after)
+                .map(|prefix| {
+                    aws_sdk_s3::types::CommonPrefix::builder()
+                        .prefix(prefix)
+                        .build()
+                })
+                .collect();
+            Ok(ListObjectsV2Output::builder()
+                .set_common_prefixes(Some(common_prefixes))
+                .build())
+        }
+    }
+
+    pub(crate) fn fixture_lake() -> LakeBuilder {
+        LakeBuilder::default()
+            .s3_bucket_name("near-lake-data-mainnet")
+            .s3_region_name("eu-central-1")
+            .s3_client(FixtureS3Client {})
+            .poll_interval(std::time::Duration::from_millis(10))
+            .start_block_height(879765)
+    }
+
+    #[tokio::test]
+    async fn stops_at_last_available_block() {
+        let lake = fixture_lake().end_block_height(879765).build().unwrap();
+
+        let summary = tokio::time::timeout(
+            std::time::Duration::from_secs(10),
+            lake.run_async(|_block| async { Ok::<_, std::io::Error>(()) }),
+        )
+        .await
+        .expect("The stream has waited for the blocks above the end block height")
+        .unwrap();
+        assert_eq!(summary.blocks_processed, 1);
+        assert_eq!(summary.last_block_height, Some(879765));
+    }
+
+    /// Answers `403 AccessDenied` to every request, as S3 does when the credentials don't grant the access to the bucket
+    #[derive(Debug)]
+    pub(crate) struct ForbiddenS3Client {}
+
+    fn access_denied<E>(error: E) -> SdkError<E> {
+        SdkError::service_error(
+            error,
+            aws_sdk_s3::config::http::HttpResponse::new(
+                403u16.try_into().unwrap(),
+                aws_smithy_types::body::SdkBody::empty(),
+            ),
+        )
+    }
+
+    #[async_trait]
+    impl S3Client for ForbiddenS3Client {
+        async fn get_object(
+            &self,
+            _bucket: &str,
+            _prefix: &str,
+        ) -> Result<GetObjectOutput, SdkError<GetObjectError>> {
+            Err(access_denied(GetObjectError::generic(
+                aws_sdk_s3::error::ErrorMetadata::builder()
+                    .code("AccessDenied")
+                    .build(),
+            )))
+        }
+
+        async fn list_objects(
+            &self,
+            _bucket: &str,
+            _start_after: &str,
+            _continuation_token: Option<String>,
+        ) -> Result<ListObjectsV2Output, SdkError<ListObjectsV2Error>> {
+            Err(access_denied(ListObjectsV2Error::generic(
+                aws_sdk_s3::error::ErrorMetadata::builder()
+                    .code("AccessDenied")
+                    .build(),
+            )))
+        }
+    }
+
+    #[tokio::test]
+    async fn returns_access_denied_list_errors() {
+        let lake = fixture_lake()
+            .s3_client(ForbiddenS3Client {})
+            .build()
+            .unwrap();
+
+        let err = tokio::time::timeout(
+            std::time::Duration::from_secs(10),
+            lake.run_async(|_block| async { Ok::<_, std::io::Error>(()) }),
+        )
+        .await
+        .expect("The stream has retried the access denied errors")
+        .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Unauthorized);
+    }
+
+    #[tokio::test]
+    async fn returns_access_denied_get_errors_without_list() {
+        let lake = fixture_lake()
+            .s3_client(ForbiddenS3Client {})
+            .no_list(true)
+            .build()
+            .unwrap();
+
+        let err = tokio::time::timeout(
+            std::time::Duration::from_secs(10),
+            lake.run_async(|_block| async { Ok::<_, std::io::Error>(()) }),
+        )
+        .await
+        .expect("The stream has taken the access denied errors for the missing blocks forever")
+        .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Unauthorized);
+    }
+
+    #[tokio::test]
+    async fn enumerates_block_heights_of_range() {
+        let lake = LakeBuilder::default()
+            .s3_bucket_name("near-lake-data-mainnet")
+            .s3_region_name("eu-central-1")
+            .s3_client(SparseS3Client {})
+            .start_block_height(0)
+            .build()
+            .unwrap();
+
+        // the range spans the pages and ends between the listed heights
+        let block_heights: Vec<u64> = lake.block_heights(101, 2501).try_collect().await.unwrap();
+        assert_eq!(block_heights, (102..=2500).step_by(2).collect::<Vec<u64>>());
+
+        let block_heights: Vec<u64> = lake.block_heights(3001, 4000).try_collect().await.unwrap();
+        assert!(block_heights.is_empty());
     }
 }
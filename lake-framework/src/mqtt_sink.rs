@@ -0,0 +1,174 @@
+//! Publishing of the events to an MQTT broker, see [MqttEventSink]
+use near_lake_primitives::events::Event;
+
+/// How long to wait before polling the MQTT event loop again after a connection error
+const RECONNECT_DELAY: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Lightweight sink publishing the events to an MQTT broker, a topic per contract and event:
+/// `{topic_prefix}/{contract_id}/{standard}/{event}`, so the consumers can subscribe with the MQTT wildcards
+/// (e.g. `near/+/nep171/nft_mint`) to get the on-chain triggers without running any heavy infrastructure.
+///
+/// The payload is the JSON with the event and its receipt:
+/// `{"standard", "version", "event", "data", "receipt_id", "receiver_id", "predecessor_id"}`.
+///
+/// The sink drives the connection in a background task (reconnecting on failures) until it is dropped,
+/// so it has to be created inside the Tokio runtime
+/// ```no_run
+/// use near_lake_framework::{EventFilter, MqttEventSink};
+///
+/// # #[tokio::main]
+/// # async fn main() -> anyhow::Result<()> {
+///    let sink = MqttEventSink::new(rumqttc::MqttOptions::new("nft-indexer", "localhost", 1883))
+///        .with_topic_prefix("near");
+///
+///    near_lake_framework::LakeBuilder::default()
+///        .mainnet()
+///        .start_block_height(88444526)
+///        .build()?
+///        .run_async(|mut block| {
+///            let filter = EventFilter::default().standard("nep171");
+///            let events: Vec<_> = block.events().filter(|event| filter.matches(event)).cloned().collect();
+///            let sink = &sink;
+///            async move {
+///                for event in &events {
+///                    sink.publish(event).await?;
+///                }
+///                Ok::<_, rumqttc::ClientError>(())
+///            }
+///        })
+///        .await?;
+///    Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct MqttEventSink {
+    client: rumqttc::AsyncClient,
+    topic_prefix: String,
+    qos: rumqttc::QoS,
+    event_loop: tokio::task::JoinHandle<()>,
+}
+
+impl Drop for MqttEventSink {
+    fn drop(&mut self) {
+        self.event_loop.abort();
+    }
+}
+
+impl MqttEventSink {
+    /// Connects to the broker with the given options, the events are published with the `AtLeastOnce` QoS
+    pub fn new(options: rumqttc::MqttOptions) -> Self {
+        let (client, mut event_loop) = rumqttc::AsyncClient::new(options, 100);
+        Self {
+            client,
+            topic_prefix: "near".to_string(),
+            qos: rumqttc::QoS::AtLeastOnce,
+            event_loop: tokio::spawn(async move {
+                loop {
+                    if let Err(err) = event_loop.poll().await {
+                        tracing::warn!(
+                            target: crate::LAKE_FRAMEWORK,
+                            error = %err,
+                            "MQTT connection error, reconnecting in {:?}",
+                            RECONNECT_DELAY,
+                        );
+                        tokio::time::sleep(RECONNECT_DELAY).await;
+                    }
+                }
+            }),
+        }
+    }
+
+    /// Sets the prefix of the topics, `near` by default
+    pub fn with_topic_prefix(mut self, topic_prefix: impl Into<String>) -> Self {
+        self.topic_prefix = topic_prefix.into();
+        self
+    }
+
+    /// Sets the QoS the events are published with
+    pub fn with_qos(mut self, qos: rumqttc::QoS) -> Self {
+        self.qos = qos;
+        self
+    }
+
+    /// Returns the topic the event is published to.
+    ///
+    /// The standard and the event names are chosen by the contract, so the MQTT wildcards (`+`, `#`),
+    /// the level separators (`/`) and NUL in them are replaced with `_` to keep the event in its own topic level
+    pub fn topic(&self, event: &Event) -> String {
+        format!(
+            "{}/{}/{}/{}",
+            self.topic_prefix,
+            topic_level(event.related_receipt_receiver_id().as_str()),
+            topic_level(event.standard()),
+            topic_level(event.event())
+        )
+    }
+
+    /// Publishes the event. Waits only while the outgoing queue of the connection is full
+    pub async fn publish(&self, event: &Event) -> Result<(), rumqttc::ClientError> {
+        let payload = serde_json::json!({
+            "standard": event.standard(),
+            "version": event.version(),
+            "event": event.event(),
+            "data": event.data(),
+            "receipt_id": event.related_receipt_id().to_string(),
+            "receiver_id": event.related_receipt_receiver_id(),
+            "predecessor_id": event.related_receipt_predecessor_id(),
+        });
+        self.client
+            .publish(self.topic(event), self.qos, false, payload.to_string())
+            .await
+    }
+}
+
+/// Replaces the characters that are not allowed in (or change the meaning of) a single MQTT topic level
+fn topic_level(value: &str) -> String {
+    value.replace(['+', '#', '/', '\0'], "_")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn block_with_log(log: &str) -> near_lake_primitives::block::Block {
+        let mut shard: serde_json::Value =
+            serde_json::from_str(include_str!("../blocks/000000879765/shard_0.json")).unwrap();
+        shard["receipt_execution_outcomes"][0]["execution_outcome"]["outcome"]["logs"] =
+            serde_json::json!([log]);
+        near_lake_primitives::StreamerMessage {
+            block: serde_json::from_str(include_str!("../blocks/000000879765/block.json")).unwrap(),
+            shards: vec![serde_json::from_value(shard).unwrap()],
+        }
+        .into()
+    }
+
+    #[tokio::test]
+    async fn escapes_topic_levels() {
+        let sink = MqttEventSink::new(rumqttc::MqttOptions::new("test", "localhost", 1883))
+            .with_topic_prefix("indexer/near");
+
+        let mut block = block_with_log(
+            r#"EVENT_JSON:{"standard":"nep171","version":"1.0.0","event":"nft_mint"}"#,
+        );
+        let event = block.events().next().unwrap();
+        assert_eq!(
+            sink.topic(event),
+            format!(
+                "indexer/near/{}/nep171/nft_mint",
+                event.related_receipt_receiver_id()
+            )
+        );
+
+        let mut block = block_with_log(
+            r##"EVENT_JSON:{"standard":"nep171/+","version":"1.0.0","event":"#\u0000"}"##,
+        );
+        let event = block.events().next().unwrap();
+        assert_eq!(
+            sink.topic(event),
+            format!(
+                "indexer/near/{}/nep171__/__",
+                event.related_receipt_receiver_id()
+            )
+        );
+    }
+}
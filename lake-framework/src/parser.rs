@@ -0,0 +1,148 @@
+use std::sync::{Arc, Mutex};
+
+use crate::types::LakeError;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// How many idle buffers to keep for reuse
+const MAX_POOLED_BUFFERS: usize = 64;
+
+/// The buffers grown larger than this (e.g. by an exceptionally large block) are not kept for reuse
+const MAX_POOLED_BUFFER_CAPACITY: usize = 16 * 1024 * 1024;
+
+/// Pool of the buffers the object bodies are read into, so streaming millions of blocks
+/// doesn't allocate (and grow) a fresh buffer for every object
+#[derive(Debug, Default)]
+struct BufferPool {
+    buffers: Mutex<Vec<Vec<u8>>>,
+}
+
+impl BufferPool {
+    fn take(self: &Arc<Self>, capacity_hint: usize) -> PooledBuffer {
+        let mut buffer = self
+            .buffers
+            .lock()
+            .ok()
+            .and_then(|mut buffers| buffers.pop())
+            .unwrap_or_default();
+        buffer.reserve(capacity_hint);
+        PooledBuffer {
+            buffer,
+            pool: self.clone(),
+        }
+    }
+
+    fn put_back(&self, mut buffer: Vec<u8>) {
+        if buffer.capacity() > MAX_POOLED_BUFFER_CAPACITY {
+            return;
+        }
+        buffer.clear();
+        if let Ok(mut buffers) = self.buffers.lock() {
+            if buffers.len() < MAX_POOLED_BUFFERS {
+                buffers.push(buffer);
+            }
+        }
+    }
+}
+
+/// The buffer returned to the pool on drop
+#[derive(Debug)]
+pub(crate) struct PooledBuffer {
+    buffer: Vec<u8>,
+    pool: Arc<BufferPool>,
+}
+
+impl AsRef<[u8]> for PooledBuffer {
+    fn as_ref(&self) -> &[u8] {
+        &self.buffer
+    }
+}
+
+impl Drop for PooledBuffer {
+    fn drop(&mut self) {
+        self.pool.put_back(std::mem::take(&mut self.buffer));
+    }
+}
+
+/// Parses the fetched JSON objects either in place (on the async runtime) or on the dedicated threads,
+/// so the parsing throughput scales with the CPU cores during backfills instead of being serialized
+/// on the streamer task.
+///
+/// The object bodies are read into the pooled buffers, which are reused once the objects are parsed.
+/// The threads stop once all the clones of the pool are dropped.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ParserPool {
+    jobs: Option<tokio::sync::mpsc::UnboundedSender<Job>>,
+    buffers: Arc<BufferPool>,
+}
+
+impl ParserPool {
+    /// Spawns `threads` parser threads. `0` parses the objects in place
+    pub(crate) fn new(threads: usize) -> Self {
+        if threads == 0 {
+            return Self::default();
+        }
+        let (jobs, receiver) = tokio::sync::mpsc::unbounded_channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        for thread_id in 0..threads {
+            let receiver = receiver.clone();
+            std::thread::Builder::new()
+                .name(format!("lake-parser-{}", thread_id))
+                .spawn(move || loop {
+                    // the lock is released right after the job is received
+                    let job = match receiver.lock() {
+                        Ok(mut receiver) => receiver.blocking_recv(),
+                        Err(_) => None,
+                    };
+                    match job {
+                        Some(job) => job(),
+                        None => break,
+                    }
+                })
+                .expect("Failed to spawn the parser thread");
+        }
+        Self {
+            jobs: Some(jobs),
+            ..Default::default()
+        }
+    }
+
+    /// Reads the object body into a pooled buffer
+    pub(crate) async fn read_body(
+        &self,
+        mut body: aws_sdk_s3::primitives::ByteStream,
+        content_length: Option<i64>,
+    ) -> Result<PooledBuffer, aws_sdk_s3::primitives::ByteStreamError> {
+        let capacity_hint = content_length
+            .and_then(|content_length| usize::try_from(content_length).ok())
+            .unwrap_or_default();
+        let mut buffer = self.buffers.take(capacity_hint);
+        while let Some(chunk) = body.try_next().await? {
+            buffer.buffer.extend_from_slice(&chunk);
+        }
+        Ok(buffer)
+    }
+
+    /// Parses the JSON object as `T`
+    pub(crate) async fn parse<T>(
+        &self,
+        bytes: impl AsRef<[u8]> + Send + 'static,
+    ) -> Result<T, LakeError>
+    where
+        T: serde::de::DeserializeOwned + Send + 'static,
+    {
+        let Some(jobs) = &self.jobs else {
+            return Ok(serde_json::from_slice(bytes.as_ref())?);
+        };
+        let (sender, receiver) = tokio::sync::oneshot::channel();
+        jobs.send(Box::new(move || {
+            let _ = sender.send(serde_json::from_slice::<T>(bytes.as_ref()));
+        }))
+        .map_err(|_| LakeError::InternalError {
+            error_message: "The parser threads have stopped".to_string(),
+        })?;
+        Ok(receiver.await.map_err(|_| LakeError::InternalError {
+            error_message: "The parser thread has panicked".to_string(),
+        })??)
+    }
+}
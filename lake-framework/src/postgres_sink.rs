@@ -0,0 +1,202 @@
+//! Bulk loading of the rows projected from the blocks into Postgres with the binary `COPY`,
+//! see [PostgresCopySink]
+use tokio_postgres::binary_copy::BinaryCopyInWriter;
+use tokio_postgres::types::{ToSql, Type};
+
+use crate::types::BlockHeight;
+
+/// The row of the values in the order of the columns of the [PostgresCopySink]
+pub type PostgresRow = Vec<Box<dyn ToSql + Send + Sync>>;
+
+/// Sink loading the rows into a Postgres table with the binary `COPY` in batches of blocks,
+/// which is an order of magnitude faster than inserting the rows one by one during the backfills.
+///
+/// Every batch is written in a single transaction that first deletes the rows of the batch block heights
+/// (by the `block_height_column`) and then copies the new ones in, so the batch is either loaded completely or not at all
+/// and re-processing the same blocks after a restart doesn't duplicate the rows.
+///
+/// *Note*: The table and the column names are put into the statements as is, quote them if needed.
+/// The rows buffered since the last batch are lost unless [PostgresCopySink::flush] is called before exiting.
+/// ```no_run
+/// use near_lake_framework::{PostgresCopySink, PostgresRow};
+/// use tokio_postgres::types::Type;
+///
+/// # #[tokio::main]
+/// # async fn main() -> anyhow::Result<()> {
+///    let (client, connection) =
+///        tokio_postgres::connect("host=localhost user=postgres", tokio_postgres::NoTls).await?;
+///    tokio::spawn(connection);
+///
+///    let sink = PostgresCopySink::new(
+///        client,
+///        "receipts",
+///        [
+///            ("block_height", Type::INT8),
+///            ("receipt_id", Type::TEXT),
+///            ("receiver_id", Type::TEXT),
+///        ],
+///        "block_height",
+///    )
+///    .with_batch_size(1000);
+///
+///    near_lake_framework::LakeBuilder::default()
+///        .mainnet()
+///        .start_block_height(88444526)
+///        .end_block_height(88454526)
+///        .build()?
+///        .run_async(|mut block| {
+///            let sink = &sink;
+///            async move {
+///                let block_height = block.block_height();
+///                let rows = block
+///                    .receipts()
+///                    .map(|receipt| -> PostgresRow {
+///                        vec![
+///                            Box::new(block_height as i64),
+///                            Box::new(receipt.receipt_id().to_string()),
+///                            Box::new(receipt.receiver_id().to_string()),
+///                        ]
+///                    })
+///                    .collect();
+///                sink.write(block_height, rows).await
+///            }
+///        })
+///        .await?;
+///    sink.flush().await?;
+///    Ok(())
+/// # }
+/// ```
+pub struct PostgresCopySink {
+    table: String,
+    columns: Vec<String>,
+    column_types: Vec<Type>,
+    block_height_column: String,
+    batch_size: usize,
+    batch: tokio::sync::Mutex<Batch>,
+}
+
+struct Batch {
+    client: tokio_postgres::Client,
+    // the rows by the block height, re-processing the block replaces its rows
+    blocks: std::collections::BTreeMap<i64, Vec<PostgresRow>>,
+}
+
+impl std::fmt::Debug for PostgresCopySink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PostgresCopySink")
+            .field("table", &self.table)
+            .field("columns", &self.columns)
+            .field("block_height_column", &self.block_height_column)
+            .field("batch_size", &self.batch_size)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PostgresCopySink {
+    /// Creates the sink copying the rows into the `columns` (names and types) of the `table`,
+    /// the `block_height_column` (of the `INT8` type) keeps the height of the block the row is projected from
+    pub fn new(
+        client: tokio_postgres::Client,
+        table: impl Into<String>,
+        columns: impl IntoIterator<Item = (impl Into<String>, Type)>,
+        block_height_column: impl Into<String>,
+    ) -> Self {
+        let (columns, column_types) = columns
+            .into_iter()
+            .map(|(column, column_type)| (column.into(), column_type))
+            .unzip();
+        Self {
+            table: table.into(),
+            columns,
+            column_types,
+            block_height_column: block_height_column.into(),
+            batch_size: 100,
+            batch: tokio::sync::Mutex::new(Batch {
+                client,
+                blocks: std::collections::BTreeMap::new(),
+            }),
+        }
+    }
+
+    /// Sets the number of blocks loaded in a single transaction, 100 by default
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = std::cmp::max(batch_size, 1);
+        self
+    }
+
+    /// Buffers the rows of the block (possibly none) and loads the batch once it has [PostgresCopySink::with_batch_size] blocks.
+    /// Writing the same block again (e.g. on retry) replaces its buffered rows.
+    ///
+    /// If loading fails, the batch is kept and retried on the next call.
+    ///
+    /// **Heads up!** `Ok` means the rows are buffered, not committed: the block is in Postgres only once its batch is loaded.
+    /// The watermark of [LakeBuilder::watermark_store](crate::LakeBuilder::watermark_store) advances over the blocks
+    /// the handler has returned `Ok` for, so it may run ahead of the committed rows by up to a batch, and the buffered rows
+    /// are lost if the indexer crashes. Resume from the height the committed rows end at, or [PostgresCopySink::flush] the sink before storing a checkpoint
+    pub async fn write(
+        &self,
+        block_height: BlockHeight,
+        rows: Vec<PostgresRow>,
+    ) -> Result<(), tokio_postgres::Error> {
+        let mut batch = self.batch.lock().await;
+        batch.blocks.insert(block_height as i64, rows);
+        if batch.blocks.len() >= self.batch_size {
+            self.load(&mut batch).await?;
+        }
+        Ok(())
+    }
+
+    /// Loads the buffered rows regardless of the batch size
+    pub async fn flush(&self) -> Result<(), tokio_postgres::Error> {
+        let mut batch = self.batch.lock().await;
+        self.load(&mut batch).await
+    }
+
+    async fn load(&self, batch: &mut Batch) -> Result<(), tokio_postgres::Error> {
+        if batch.blocks.is_empty() {
+            return Ok(());
+        }
+        let Batch { client, blocks } = batch;
+        let block_heights: Vec<i64> = blocks.keys().copied().collect();
+
+        let transaction = client.transaction().await?;
+        transaction
+            .execute(
+                &format!(
+                    "DELETE FROM {} WHERE {} = ANY($1)",
+                    self.table, self.block_height_column
+                ),
+                &[&block_heights],
+            )
+            .await?;
+        let copy_in = transaction
+            .copy_in(&format!(
+                "COPY {} ({}) FROM STDIN BINARY",
+                self.table,
+                self.columns.join(", ")
+            ))
+            .await?;
+        let writer = BinaryCopyInWriter::new(copy_in, &self.column_types);
+        futures::pin_mut!(writer);
+        for row in blocks.values().flatten() {
+            let values: Vec<&(dyn ToSql + Sync)> = row
+                .iter()
+                .map(|value| &**value as &(dyn ToSql + Sync))
+                .collect();
+            writer.as_mut().write(&values).await?;
+        }
+        let rows_count = writer.finish().await?;
+        transaction.commit().await?;
+
+        tracing::debug!(
+            target: crate::LAKE_FRAMEWORK,
+            table = %self.table,
+            first_block_height = block_heights.first(),
+            last_block_height = block_heights.last(),
+            rows = rows_count,
+            "Loaded the batch into Postgres",
+        );
+        blocks.clear();
+        Ok(())
+    }
+}
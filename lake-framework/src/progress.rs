@@ -0,0 +1,99 @@
+//! Periodic reporting of the progress towards the tip of the network (or the end block height),
+//! see [LakeBuilder::progress_interval](crate::LakeBuilder::progress_interval)
+use std::sync::Arc;
+
+use crate::types::{BlockHeight, Lake, Progress, RunStats};
+
+/// Stops the reporter on drop
+pub(crate) struct ProgressReporter(tokio::task::JoinHandle<()>);
+
+impl Drop for ProgressReporter {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Spawns the reporter if [LakeBuilder::progress_interval](crate::LakeBuilder::progress_interval) is set
+pub(crate) fn spawn(config: &Lake, stats: Arc<RunStats>) -> Option<ProgressReporter> {
+    let interval = config.progress_interval?;
+    let config = config.clone();
+
+    Some(ProgressReporter(tokio::spawn(async move {
+        let block_provider = crate::provider::block_provider(&config).await;
+        let mut ticks = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+        let mut previous: Option<(tokio::time::Instant, BlockHeight)> = None;
+
+        loop {
+            let now = ticks.tick().await;
+            let summary = stats.summary();
+            let current_block_height = summary
+                .last_block_height
+                .unwrap_or(config.start_block_height.saturating_sub(1));
+
+            let target_block_height = match config.end_block_height {
+                Some(end_block_height) => end_block_height,
+                None => match block_provider
+                    .latest_block_height(current_block_height + 1)
+                    .await
+                {
+                    Ok(latest_block_height) => latest_block_height.unwrap_or(current_block_height),
+                    Err(err) => {
+                        tracing::warn!(
+                            target: crate::LAKE_FRAMEWORK,
+                            provider = block_provider.name(),
+                            block_height = current_block_height,
+                            error = %err,
+                            "Failed to find the latest block height in the bucket",
+                        );
+                        continue;
+                    }
+                },
+            };
+
+            let blocks_remaining = target_block_height.saturating_sub(current_block_height);
+            let blocks_per_second = previous
+                .map(|(previous_at, previous_block_height)| {
+                    current_block_height.saturating_sub(previous_block_height) as f64
+                        / now.duration_since(previous_at).as_secs_f64()
+                })
+                .unwrap_or_else(|| summary.blocks_processed as f64 / summary.elapsed.as_secs_f64());
+            previous = Some((now, current_block_height));
+
+            let progress = Progress {
+                blocks_processed: summary.blocks_processed,
+                last_block_height: summary.last_block_height,
+                target_block_height,
+                blocks_remaining,
+                blocks_per_second,
+                // the division by zero rate is infinite (or NaN) and doesn't convert
+                eta: std::time::Duration::try_from_secs_f64(
+                    blocks_remaining as f64 / blocks_per_second,
+                )
+                .ok(),
+            };
+
+            if blocks_remaining > 0 {
+                tracing::info!(
+                    target: crate::LAKE_FRAMEWORK,
+                    blocks_processed = progress.blocks_processed,
+                    block_height = progress.last_block_height,
+                    target_block_height,
+                    blocks_remaining,
+                    blocks_per_second,
+                    eta_secs = progress.eta.map(|eta| eta.as_secs()),
+                    "Catching up with the target block height",
+                );
+            } else {
+                tracing::debug!(
+                    target: crate::LAKE_FRAMEWORK,
+                    block_height = progress.last_block_height,
+                    "The stream has caught up with the target block height",
+                );
+            }
+
+            if let Some(on_progress) = &config.on_progress {
+                (on_progress.0)(&progress);
+            }
+        }
+    })))
+}
@@ -0,0 +1,348 @@
+//! The sources of the blocks the streamer reads from, see [BlockProvider]
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use near_lake_primitives::{CryptoHash, StreamerMessage};
+
+use crate::parser::ParserPool;
+use crate::s3_fetchers::{self, KeyLayout, S3Client};
+use crate::types::{BlockHeight, Lake, LakeError};
+
+/// The source of the blocks the streamer reads from, the NEAR Lake bucket by default.
+///
+/// Implement the trait to stream the blocks from your own source (an internal archive, a database, a proxy)
+/// and set it up with [LakeBuilder::block_provider](crate::LakeBuilder::block_provider).
+/// The streamer takes care of the prefetching, the ordering, the retries and the `prev_hash` continuity checks,
+/// so the provider only has to tell which blocks exist and to fetch them.
+/// ```no_run
+/// use async_trait::async_trait;
+/// use near_lake_framework::near_indexer_primitives::StreamerMessage;
+/// use near_lake_framework::{BlockProvider, LakeError};
+///
+/// /// Reads the blocks exported as `{block_height}.json` files into a directory
+/// #[derive(Debug)]
+/// struct DirectoryProvider {
+///     dir: std::path::PathBuf,
+/// }
+///
+/// fn internal_error(err: std::io::Error) -> LakeError {
+///     LakeError::InternalError { error_message: err.to_string() }
+/// }
+///
+/// #[async_trait]
+/// impl BlockProvider for DirectoryProvider {
+///     async fn list_block_heights(&self, start_from_block_height: u64) -> Result<Vec<u64>, LakeError> {
+///         let mut block_heights = vec![];
+///         let mut entries = tokio::fs::read_dir(&self.dir).await.map_err(internal_error)?;
+///         while let Some(entry) = entries.next_entry().await.map_err(internal_error)? {
+///             let block_height = entry.path().file_stem().and_then(|stem| stem.to_str()?.parse().ok());
+///             if let Some(block_height) = block_height.filter(|height| *height >= start_from_block_height) {
+///                 block_heights.push(block_height);
+///             }
+///         }
+///         block_heights.sort_unstable();
+///         Ok(block_heights)
+///     }
+///
+///     async fn fetch_streamer_message(&self, block_height: u64) -> Result<StreamerMessage, LakeError> {
+///         let path = self.dir.join(format!("{}.json", block_height));
+///         let bytes = tokio::fs::read(path).await.map_err(internal_error)?;
+///         Ok(serde_json::from_slice(&bytes)?)
+///     }
+///
+///     fn name(&self) -> &'static str {
+///         "directory"
+///     }
+/// }
+///
+/// # fn main() -> anyhow::Result<()> {
+///    near_lake_framework::LakeBuilder::default()
+///        .mainnet()
+///        .block_provider(DirectoryProvider { dir: "/var/lib/blocks".into() })
+///        .start_block_height(88444526)
+///        .end_block_height(88445525)
+///        .build()?
+///        .run(handle_block)?;
+///    Ok(())
+/// # }
+///
+/// # async fn handle_block(_block: near_lake_primitives::block::Block) -> anyhow::Result<()> { Ok(()) }
+/// ```
+#[async_trait]
+pub trait BlockProvider: std::fmt::Debug + Send + Sync {
+    /// Returns the heights of the available blocks at or above `start_from_block_height` in the ascending order.
+    /// It doesn't have to return all of them at once, the streamer asks for the next ones after the last returned height.
+    /// The empty list means there are no newer blocks yet, the streamer asks again in the
+    /// [LakeBuilder::poll_interval](crate::LakeBuilder::poll_interval)
+    async fn list_block_heights(
+        &self,
+        start_from_block_height: BlockHeight,
+    ) -> Result<Vec<BlockHeight>, LakeError>;
+
+    /// Fetches the block of the given (listed) height
+    async fn fetch_streamer_message(
+        &self,
+        block_height: BlockHeight,
+    ) -> Result<StreamerMessage, LakeError>;
+
+    /// Returns the latest available block height at or above `start_from_block_height`, `None` if there are no such blocks.
+    /// It is used to measure the progress and the lag and to detect the stalled stream.
+    ///
+    /// The default implementation follows [BlockProvider::list_block_heights] to the end, override it if there is a cheaper way
+    async fn latest_block_height(
+        &self,
+        mut start_from_block_height: BlockHeight,
+    ) -> Result<Option<BlockHeight>, LakeError> {
+        let mut latest_block_height = None;
+        loop {
+            let block_heights = self.list_block_heights(start_from_block_height).await?;
+            let Some(last_block_height) = block_heights.last() else {
+                return Ok(latest_block_height);
+            };
+            latest_block_height = Some(*last_block_height);
+            start_from_block_height = last_block_height + 1;
+        }
+    }
+
+    /// Returns the height of the block with the given hash, `None` if the provider doesn't know the hash.
+    /// It is used by [Lake::fetch_block_by_hash](crate::Lake::fetch_block_by_hash).
+    ///
+    /// The default implementation doesn't index the hashes and always returns `None`
+    async fn block_height_by_hash(
+        &self,
+        _block_hash: CryptoHash,
+    ) -> Result<Option<BlockHeight>, LakeError> {
+        Ok(None)
+    }
+
+    /// The name of the provider in the logs and the [ErrorReport](crate::ErrorReport)s
+    fn name(&self) -> &'static str {
+        "custom"
+    }
+}
+
+/// The [BlockProvider] reading the blocks from the NEAR Lake bucket
+#[derive(Debug)]
+pub(crate) struct S3BlockProvider {
+    lake_s3_client: Arc<dyn S3Client>,
+    s3_bucket_name: String,
+    key_layout: Arc<dyn KeyLayout>,
+    track_shards: Option<Vec<u64>>,
+    list_max_pages: usize,
+    list_concurrency: usize,
+    parser: ParserPool,
+}
+
+impl S3BlockProvider {
+    pub(crate) fn new(
+        lake_s3_client: Arc<dyn S3Client>,
+        config: &Lake,
+        parser: ParserPool,
+    ) -> Self {
+        Self {
+            lake_s3_client,
+            s3_bucket_name: config.s3_bucket_name.clone(),
+            key_layout: config.key_layout(),
+            track_shards: config.track_shards.clone(),
+            list_max_pages: config.list_max_pages,
+            list_concurrency: config.list_concurrency,
+            parser,
+        }
+    }
+}
+
+#[async_trait]
+impl BlockProvider for S3BlockProvider {
+    async fn list_block_heights(
+        &self,
+        start_from_block_height: BlockHeight,
+    ) -> Result<Vec<BlockHeight>, LakeError> {
+        s3_fetchers::list_block_heights_concurrently(
+            &*self.lake_s3_client,
+            &self.s3_bucket_name,
+            &*self.key_layout,
+            start_from_block_height,
+            self.list_max_pages,
+            self.list_concurrency,
+        )
+        .await
+    }
+
+    async fn fetch_streamer_message(
+        &self,
+        block_height: BlockHeight,
+    ) -> Result<StreamerMessage, LakeError> {
+        s3_fetchers::fetch_streamer_message(
+            &*self.lake_s3_client,
+            &self.s3_bucket_name,
+            &*self.key_layout,
+            block_height,
+            self.track_shards.as_deref(),
+            &self.parser,
+        )
+        .await
+    }
+
+    async fn latest_block_height(
+        &self,
+        start_from_block_height: BlockHeight,
+    ) -> Result<Option<BlockHeight>, LakeError> {
+        s3_fetchers::find_latest_block_height(
+            &*self.lake_s3_client,
+            &self.s3_bucket_name,
+            &*self.key_layout,
+            start_from_block_height,
+        )
+        .await
+    }
+
+    async fn block_height_by_hash(
+        &self,
+        block_hash: CryptoHash,
+    ) -> Result<Option<BlockHeight>, LakeError> {
+        s3_fetchers::fetch_block_height_by_hash(
+            &*self.lake_s3_client,
+            &self.s3_bucket_name,
+            &*self.key_layout,
+            &block_hash,
+        )
+        .await
+    }
+
+    fn name(&self) -> &'static str {
+        s3_fetchers::PROVIDER
+    }
+}
+
+/// The name of the [SwitchingBlockProvider] in the logs and the [ErrorReport](crate::ErrorReport)s
+pub(crate) const SWITCHING_PROVIDER: &str = "switching";
+
+/// The [BlockProvider] serving the block height ranges from the different providers,
+/// see [LakeBuilder::switch_provider_at](crate::LakeBuilder::switch_provider_at)
+#[derive(Debug)]
+pub(crate) struct SwitchingBlockProvider {
+    /// The providers by the block height they serve the blocks from, in the ascending order
+    providers: Vec<(BlockHeight, Arc<dyn BlockProvider>)>,
+}
+
+impl SwitchingBlockProvider {
+    pub(crate) fn new(
+        first_provider: Arc<dyn BlockProvider>,
+        switches: &[(BlockHeight, Arc<dyn BlockProvider>)],
+    ) -> Self {
+        let mut providers = vec![(0, first_provider)];
+        providers.extend(switches.iter().cloned());
+        // the sort is stable, so the switch at 0 takes over the first provider
+        providers.sort_by_key(|(from_block_height, _)| *from_block_height);
+        Self { providers }
+    }
+
+    /// Returns the index of the provider serving the block height
+    fn provider_index(&self, block_height: BlockHeight) -> usize {
+        self.providers
+            .partition_point(|(from_block_height, _)| *from_block_height <= block_height)
+            - 1
+    }
+}
+
+#[async_trait]
+impl BlockProvider for SwitchingBlockProvider {
+    async fn list_block_heights(
+        &self,
+        mut start_from_block_height: BlockHeight,
+    ) -> Result<Vec<BlockHeight>, LakeError> {
+        let mut index = self.provider_index(start_from_block_height);
+        loop {
+            let (_, provider) = &self.providers[index];
+            let mut block_heights = provider.list_block_heights(start_from_block_height).await?;
+            let Some((next_from_block_height, next_provider)) = self.providers.get(index + 1)
+            else {
+                return Ok(block_heights);
+            };
+            block_heights.retain(|block_height| block_height < next_from_block_height);
+            if !block_heights.is_empty() {
+                return Ok(block_heights);
+            }
+
+            tracing::debug!(
+                target: crate::LAKE_FRAMEWORK,
+                provider = next_provider.name(),
+                block_height = *next_from_block_height,
+                previous_provider = provider.name(),
+                "There are no more blocks before the switch height, switching the provider",
+            );
+            start_from_block_height = *next_from_block_height;
+            index += 1;
+        }
+    }
+
+    async fn fetch_streamer_message(
+        &self,
+        block_height: BlockHeight,
+    ) -> Result<StreamerMessage, LakeError> {
+        let (_, provider) = &self.providers[self.provider_index(block_height)];
+        provider.fetch_streamer_message(block_height).await
+    }
+
+    async fn latest_block_height(
+        &self,
+        start_from_block_height: BlockHeight,
+    ) -> Result<Option<BlockHeight>, LakeError> {
+        let first_index = self.provider_index(start_from_block_height);
+        for (from_block_height, provider) in self.providers[first_index..].iter().rev() {
+            let latest_block_height = provider
+                .latest_block_height(std::cmp::max(start_from_block_height, *from_block_height))
+                .await?;
+            if latest_block_height.is_some() {
+                return Ok(latest_block_height);
+            }
+        }
+        Ok(None)
+    }
+
+    async fn block_height_by_hash(
+        &self,
+        block_hash: CryptoHash,
+    ) -> Result<Option<BlockHeight>, LakeError> {
+        for (from_block_height, provider) in self.providers.iter().rev() {
+            let block_height = provider.block_height_by_hash(block_hash).await?;
+            // the block has to be served by the provider that knows it
+            if let Some(block_height) = block_height.filter(|height| height >= from_block_height) {
+                return Ok(Some(block_height));
+            }
+        }
+        Ok(None)
+    }
+
+    fn name(&self) -> &'static str {
+        SWITCHING_PROVIDER
+    }
+}
+
+/// Wraps the provider into the [SwitchingBlockProvider] if the [Lake] has the provider switches set up
+pub(crate) fn with_switches(
+    block_provider: Arc<dyn BlockProvider>,
+    config: &Lake,
+) -> Arc<dyn BlockProvider> {
+    if config.provider_switches.is_empty() {
+        return block_provider;
+    }
+    Arc::new(SwitchingBlockProvider::new(
+        block_provider,
+        &config.provider_switches,
+    ))
+}
+
+/// Returns the custom [BlockProvider] or the bucket one configured by the [Lake] (with the switches, if any).
+/// The bucket one parses the blocks in place, so it suits the probes of the latest block height
+pub(crate) async fn block_provider(config: &Lake) -> Arc<dyn BlockProvider> {
+    let block_provider: Arc<dyn BlockProvider> = match &config.block_provider {
+        Some(block_provider) => block_provider.clone(),
+        None => Arc::new(S3BlockProvider::new(
+            crate::streamer::lake_s3_client(config).await,
+            config,
+            ParserPool::default(),
+        )),
+    };
+    with_switches(block_provider, config)
+}
@@ -2,10 +2,15 @@ use async_trait::async_trait;
 use std::str::FromStr;
 
 use aws_sdk_s3::operation::get_object::GetObjectOutput;
+use aws_sdk_s3::operation::head_object::HeadObjectOutput;
 use aws_sdk_s3::operation::list_objects_v2::ListObjectsV2Output;
 
+/// The client Lake Framework fetches the objects from the bucket with.
+///
+/// By default Lake Framework uses its own AWS S3 client, but you can provide a custom one
+/// with [LakeBuilder::s3_client](crate::LakeBuilder::s3_client), e.g. a caching client or a mock for tests.
 #[async_trait]
-pub trait S3Client {
+pub trait S3Client: std::fmt::Debug + Send + Sync {
     async fn get_object(
         &self,
         bucket: &str,
@@ -19,20 +24,207 @@ pub trait S3Client {
         &self,
         bucket: &str,
         start_after: &str,
+        continuation_token: Option<String>,
     ) -> Result<
         ListObjectsV2Output,
         aws_sdk_s3::error::SdkError<aws_sdk_s3::operation::list_objects_v2::ListObjectsV2Error>,
     >;
+
+    /// Checks whether the object exists without reading it, used to probe the objects that are not written yet.
+    ///
+    /// The default implementation falls back to [S3Client::get_object] and drops the body,
+    /// override it to send the cheaper `HeadObject` requests
+    async fn head_object(
+        &self,
+        bucket: &str,
+        prefix: &str,
+    ) -> Result<
+        HeadObjectOutput,
+        aws_sdk_s3::error::SdkError<aws_sdk_s3::operation::head_object::HeadObjectError>,
+    > {
+        self.get_object(bucket, prefix)
+            .await
+            .map(|_| HeadObjectOutput::builder().build())
+            .map_err(|err| {
+                err.map_service_error(|err| {
+                    aws_sdk_s3::operation::head_object::HeadObjectError::generic(err.meta().clone())
+                })
+            })
+    }
+}
+
+/// Defines the scheme of the object keys in the bucket.
+///
+/// The default one is [DefaultKeyLayout] used by the official NEAR Lake buckets:
+/// `{block_height:0>12}/block.json` and `{block_height:0>12}/shard_{shard_id}.json`.
+/// Implement this trait if you stream from a custom near-lake fork that pads the block heights differently
+/// or shards the objects by date.
+///
+/// *Note*: The block heights are listed with the S3 `List` requests grouped by the "/" delimiter
+/// at the root of the bucket. For the nested layouts (e.g. sharded by date) consider
+/// streaming without `List` requests ([LakeBuilder::no_list](crate::LakeBuilder::no_list))
+/// ```
+/// use near_lake_framework::{KeyLayout, LakeBuilder};
+///
+/// #[derive(Debug)]
+/// struct UnpaddedKeyLayout;
+///
+/// impl KeyLayout for UnpaddedKeyLayout {
+///     fn block_key(&self, block_height: u64) -> String {
+///         format!("{}/block.json", block_height)
+///     }
+///
+///     fn shard_key(&self, block_height: u64, shard_id: u64) -> String {
+///         format!("{}/shard_{}.json", block_height, shard_id)
+///     }
+///
+///     fn list_start_after(&self, block_height: u64) -> String {
+///         block_height.to_string()
+///     }
+///
+///     fn parse_block_height(&self, prefix: &str) -> Option<u64> {
+///         prefix.split('/').next()?.parse().ok()
+///     }
+/// }
+///
+/// # fn main() {
+///    let lake = LakeBuilder::default()
+///        .s3_bucket_name("near-lake-data-custom")
+///        .s3_region_name("eu-central-1")
+///        .start_block_height(1)
+///        .key_layout(UnpaddedKeyLayout)
+///        .no_list(true)
+///        .build()
+///        .expect("Failed to build Lake");
+/// # }
+/// ```
+pub trait KeyLayout: std::fmt::Debug + Send + Sync {
+    /// Returns the key of the `block.json` object of the given block height
+    fn block_key(&self, block_height: crate::types::BlockHeight) -> String;
+
+    /// Returns the key of the `shard_N.json` object of the given block height and shard id
+    fn shard_key(&self, block_height: crate::types::BlockHeight, shard_id: u64) -> String;
+
+    /// Returns the key to start the S3 `List` request after, so the given block height is the first one listed
+    fn list_start_after(&self, block_height: crate::types::BlockHeight) -> String;
+
+    /// Parses the block height from the common prefix returned by the S3 `List` request
+    fn parse_block_height(&self, prefix: &str) -> Option<crate::types::BlockHeight>;
+
+    /// Returns the key of the object holding the height of the block with the given hash,
+    /// written by [S3RepublishSink::with_block_hash_index](crate::S3RepublishSink::with_block_hash_index).
+    /// The default one is `block_hash/{block_hash}`, it doesn't parse as a block height, so it isn't listed
+    fn block_hash_key(&self, block_hash: &near_lake_primitives::CryptoHash) -> String {
+        format!("block_hash/{}", block_hash)
+    }
 }
 
+/// The key layout of the official NEAR Lake buckets: `{block_height:0>12}/block.json`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultKeyLayout;
+
+impl KeyLayout for DefaultKeyLayout {
+    fn block_key(&self, block_height: crate::types::BlockHeight) -> String {
+        format!("{:0>12}/block.json", block_height)
+    }
+
+    fn shard_key(&self, block_height: crate::types::BlockHeight, shard_id: u64) -> String {
+        format!("{:0>12}/shard_{}.json", block_height, shard_id)
+    }
+
+    fn list_start_after(&self, block_height: crate::types::BlockHeight) -> String {
+        format!("{:0>12}", block_height)
+    }
+
+    fn parse_block_height(&self, prefix: &str) -> Option<crate::types::BlockHeight> {
+        prefix
+            .split('/')
+            .next()
+            .map(u64::from_str)
+            .and_then(|num| num.ok())
+    }
+}
+
+/// The value of the `provider` field of the structured logs
+pub(crate) const PROVIDER: &str = "s3";
+
+/// How long to wait between the `HeadObject` probes of the object that is not written to the bucket yet
+const OBJECT_PROBE_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
 #[derive(Clone, Debug)]
 pub struct LakeS3Client {
     s3: aws_sdk_s3::Client,
+    requests_counter: crate::types::S3RequestsCounter,
+    requester_pays: bool,
+    etag_cache: Option<std::sync::Arc<std::sync::Mutex<EtagCache>>>,
+}
+
+/// Bounded cache of the fetched objects along with their ETags.
+/// Used to send conditional (`If-None-Match`) `GetObject` requests and serve the body
+/// from memory when the caching proxy (or S3) responds with `304 Not Modified`.
+#[derive(Debug, Default)]
+struct EtagCache {
+    capacity: usize,
+    objects: std::collections::HashMap<String, (String, Vec<u8>)>,
+    order: std::collections::VecDeque<String>,
+}
+
+impl EtagCache {
+    fn with_capacity(capacity: usize) -> Self {
+        Self {
+            capacity,
+            ..Default::default()
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<&(String, Vec<u8>)> {
+        self.objects.get(key)
+    }
+
+    fn insert(&mut self, key: String, etag: String, body: Vec<u8>) {
+        if self.objects.insert(key.clone(), (etag, body)).is_none() {
+            self.order.push_back(key);
+        }
+        while self.order.len() > self.capacity {
+            if let Some(evicted) = self.order.pop_front() {
+                self.objects.remove(&evicted);
+            }
+        }
+    }
 }
 
 impl LakeS3Client {
-    pub fn new(s3: aws_sdk_s3::Client) -> Self {
-        Self { s3 }
+    pub fn new(
+        s3: aws_sdk_s3::Client,
+        requests_counter: crate::types::S3RequestsCounter,
+        requester_pays: bool,
+    ) -> Self {
+        Self {
+            s3,
+            requests_counter,
+            requester_pays,
+            etag_cache: None,
+        }
+    }
+
+    /// Enables the conditional `GetObject` requests, keeping up to `capacity` recently fetched objects
+    /// along with their ETags. `0` disables the cache
+    pub fn with_etag_cache(mut self, capacity: usize) -> Self {
+        self.etag_cache = (capacity > 0).then(|| {
+            std::sync::Arc::new(std::sync::Mutex::new(EtagCache::with_capacity(capacity)))
+        });
+        self
+    }
+
+    fn cached_object(&self, key: &str) -> Option<(String, Vec<u8>)> {
+        self.etag_cache
+            .as_ref()
+            .and_then(|cache| cache.lock().ok()?.get(key).cloned())
+    }
+
+    fn request_payer(&self) -> Option<aws_sdk_s3::types::RequestPayer> {
+        self.requester_pays
+            .then_some(aws_sdk_s3::types::RequestPayer::Requester)
     }
 }
 
@@ -46,121 +238,807 @@ impl S3Client for LakeS3Client {
         GetObjectOutput,
         aws_sdk_s3::error::SdkError<aws_sdk_s3::operation::get_object::GetObjectError>,
     > {
-        Ok(self
+        self.requests_counter.inc_get_requests();
+        let Some(etag_cache) = &self.etag_cache else {
+            return self
+                .s3
+                .get_object()
+                .bucket(bucket)
+                .key(prefix)
+                .set_request_payer(self.request_payer())
+                .send()
+                .await;
+        };
+
+        let cached = self.cached_object(prefix);
+        let response = self
             .s3
             .get_object()
             .bucket(bucket)
             .key(prefix)
-            .request_payer(aws_sdk_s3::types::RequestPayer::Requester)
+            .set_if_none_match(cached.as_ref().map(|(etag, _)| etag.clone()))
+            .set_request_payer(self.request_payer())
             .send()
-            .await?)
+            .await;
+
+        match response {
+            Ok(output) => {
+                let Some(etag) = output.e_tag().map(str::to_string) else {
+                    return Ok(output);
+                };
+                let body = output
+                    .body
+                    .collect()
+                    .await
+                    .map_err(|err| {
+                        aws_sdk_s3::error::SdkError::dispatch_failure(
+                            aws_sdk_s3::error::ConnectorError::io(err.into()),
+                        )
+                    })?
+                    .to_vec();
+                if let Ok(mut cache) = etag_cache.lock() {
+                    cache.insert(prefix.to_string(), etag.clone(), body.clone());
+                }
+                Ok(GetObjectOutput::builder()
+                    .e_tag(etag)
+                    .body(aws_sdk_s3::primitives::ByteStream::from(body))
+                    .build())
+            }
+            Err(err) if is_not_modified(&err) => match cached {
+                Some((etag, body)) => {
+                    tracing::debug!(
+                        target: crate::LAKE_FRAMEWORK,
+                        provider = PROVIDER,
+                        key = prefix,
+                        "Object is not modified, serving it from the ETag cache",
+                    );
+                    Ok(GetObjectOutput::builder()
+                        .e_tag(etag)
+                        .body(aws_sdk_s3::primitives::ByteStream::from(body))
+                        .build())
+                }
+                None => Err(err),
+            },
+            Err(err) => Err(err),
+        }
     }
 
     async fn list_objects(
         &self,
         bucket: &str,
         start_after: &str,
+        continuation_token: Option<String>,
     ) -> Result<
         ListObjectsV2Output,
         aws_sdk_s3::error::SdkError<aws_sdk_s3::operation::list_objects_v2::ListObjectsV2Error>,
     > {
+        self.requests_counter.inc_list_requests();
         Ok(self
             .s3
             .list_objects_v2()
             .max_keys(1000) // 1000 is the default and max value for this parameter
             .delimiter("/".to_string())
             .start_after(start_after)
-            .request_payer(aws_sdk_s3::types::RequestPayer::Requester)
+            .set_continuation_token(continuation_token)
+            .set_request_payer(self.request_payer())
+            .bucket(bucket)
+            .send()
+            .await?)
+    }
+
+    async fn head_object(
+        &self,
+        bucket: &str,
+        prefix: &str,
+    ) -> Result<
+        HeadObjectOutput,
+        aws_sdk_s3::error::SdkError<aws_sdk_s3::operation::head_object::HeadObjectError>,
+    > {
+        self.requests_counter.inc_head_requests();
+        Ok(self
+            .s3
+            .head_object()
             .bucket(bucket)
+            .key(prefix)
+            .set_request_payer(self.request_payer())
             .send()
             .await?)
     }
 }
 
+/// Read-through cache of the fetched objects in a local directory.
+/// Serves the objects from the disk if they have been fetched before, so re-running the indexer over
+/// the same range of blocks doesn't hit the bucket, and falls back to the wrapped client for the missing ones
+/// writing them back to the disk.
+///
+/// The full pages of the `List` responses (followed by a continuation token) describe the historical
+/// ranges that never change, so they are cached as well. The last page (the tip of the network) is always
+/// requested from the wrapped client.
+///
+/// The cached entries that fail to deserialize (e.g. damaged on the disk) are evicted and fetched again.
+#[derive(Debug)]
+pub(crate) struct DiskCacheS3Client {
+    inner: std::sync::Arc<dyn S3Client>,
+    cache_dir: std::path::PathBuf,
+}
+
+impl DiskCacheS3Client {
+    pub(crate) fn new(
+        inner: std::sync::Arc<dyn S3Client>,
+        cache_dir: impl Into<std::path::PathBuf>,
+    ) -> Self {
+        Self {
+            inner,
+            cache_dir: cache_dir.into(),
+        }
+    }
+
+    fn object_path(&self, bucket: &str, key: &str) -> std::path::PathBuf {
+        self.cache_dir.join(bucket).join(key)
+    }
+
+    fn list_page_path(
+        &self,
+        bucket: &str,
+        start_after: &str,
+        continuation_token: Option<&str>,
+    ) -> std::path::PathBuf {
+        // the continuation tokens are opaque and may contain any characters
+        let hex = |value: &str| {
+            value
+                .bytes()
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<String>()
+        };
+        self.cache_dir.join(bucket).join(".list").join(format!(
+            "{}-{}.json",
+            hex(start_after),
+            hex(continuation_token.unwrap_or_default())
+        ))
+    }
+
+    /// Writes the object to a temporary file first and renames it, so the interrupted writes
+    /// never leave truncated objects in the cache. The temporary file is unique to the process and the write,
+    /// so the concurrent writes of the same object (by the workers or the indexers sharing the cache) don't mix up
+    async fn store(&self, path: &std::path::Path, body: &[u8]) -> std::io::Result<()> {
+        static TMP_FILES_COUNTER: std::sync::atomic::AtomicU64 =
+            std::sync::atomic::AtomicU64::new(0);
+
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let tmp_path = path.with_extension(format!(
+            "{}.{}.tmp",
+            std::process::id(),
+            TMP_FILES_COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+        ));
+        if let Err(err) = tokio::fs::write(&tmp_path, body).await {
+            let _ = tokio::fs::remove_file(&tmp_path).await;
+            return Err(err);
+        }
+        tokio::fs::rename(&tmp_path, path).await
+    }
+
+    /// Removes the cached entry that has failed to deserialize, so it is fetched again
+    async fn evict(&self, path: &std::path::Path, error: &serde_json::Error) {
+        tracing::warn!(
+            target: crate::LAKE_FRAMEWORK,
+            provider = PROVIDER,
+            path = %path.display(),
+            %error,
+            "Evicting the damaged entry from the disk cache",
+        );
+        if let Err(err) = tokio::fs::remove_file(path).await {
+            tracing::warn!(
+                target: crate::LAKE_FRAMEWORK,
+                provider = PROVIDER,
+                path = %path.display(),
+                error = %err,
+                "Failed to evict the entry from the disk cache",
+            );
+        }
+    }
+}
+
+/// The full page of the `List` response stored in the [DiskCacheS3Client]
+#[derive(serde::Deserialize, serde::Serialize)]
+struct ListPage {
+    prefixes: Vec<String>,
+    next_continuation_token: String,
+}
+
+#[async_trait]
+impl S3Client for DiskCacheS3Client {
+    async fn get_object(
+        &self,
+        bucket: &str,
+        prefix: &str,
+    ) -> Result<
+        GetObjectOutput,
+        aws_sdk_s3::error::SdkError<aws_sdk_s3::operation::get_object::GetObjectError>,
+    > {
+        let path = self.object_path(bucket, prefix);
+        if let Ok(body) = tokio::fs::read(&path).await {
+            // the objects of the bucket are JSON
+            match serde_json::from_slice::<serde::de::IgnoredAny>(&body) {
+                Ok(_) => {
+                    tracing::trace!(
+                        target: crate::LAKE_FRAMEWORK,
+                        provider = PROVIDER,
+                        key = prefix,
+                        "Serving the object from the disk cache",
+                    );
+                    return Ok(GetObjectOutput::builder()
+                        .body(aws_sdk_s3::primitives::ByteStream::from(body))
+                        .build());
+                }
+                Err(err) => self.evict(&path, &err).await,
+            }
+        }
+
+        let output = self.inner.get_object(bucket, prefix).await?;
+        let body = output
+            .body
+            .collect()
+            .await
+            .map_err(|err| {
+                aws_sdk_s3::error::SdkError::dispatch_failure(
+                    aws_sdk_s3::error::ConnectorError::io(err.into()),
+                )
+            })?
+            .to_vec();
+        if let Err(err) = self.store(&path, &body).await {
+            tracing::warn!(
+                target: crate::LAKE_FRAMEWORK,
+                provider = PROVIDER,
+                key = prefix,
+                error = %err,
+                "Failed to write the object to the disk cache",
+            );
+        }
+        Ok(GetObjectOutput::builder()
+            .set_e_tag(output.e_tag)
+            .body(aws_sdk_s3::primitives::ByteStream::from(body))
+            .build())
+    }
+
+    async fn list_objects(
+        &self,
+        bucket: &str,
+        start_after: &str,
+        continuation_token: Option<String>,
+    ) -> Result<
+        ListObjectsV2Output,
+        aws_sdk_s3::error::SdkError<aws_sdk_s3::operation::list_objects_v2::ListObjectsV2Error>,
+    > {
+        let path = self.list_page_path(bucket, start_after, continuation_token.as_deref());
+        let page = match tokio::fs::read(&path).await {
+            Ok(page) => match serde_json::from_slice::<ListPage>(&page) {
+                Ok(page) => Some(page),
+                Err(err) => {
+                    self.evict(&path, &err).await;
+                    None
+                }
+            },
+            Err(_) => None,
+        };
+        if let Some(page) = page {
+            return Ok(ListObjectsV2Output::builder()
+                .set_common_prefixes(Some(
+                    page.prefixes
+                        .into_iter()
+                        .map(|prefix| {
+                            aws_sdk_s3::types::CommonPrefix::builder()
+                                .prefix(prefix)
+                                .build()
+                        })
+                        .collect(),
+                ))
+                .next_continuation_token(page.next_continuation_token)
+                .build());
+        }
+
+        let output = self
+            .inner
+            .list_objects(bucket, start_after, continuation_token)
+            .await?;
+        if let Some(next_continuation_token) = output.next_continuation_token.clone() {
+            let page = ListPage {
+                prefixes: output
+                    .common_prefixes()
+                    .iter()
+                    .filter_map(|common_prefix| common_prefix.prefix.clone())
+                    .collect(),
+                next_continuation_token,
+            };
+            let store = match serde_json::to_vec(&page) {
+                Ok(page) => self.store(&path, &page).await,
+                Err(err) => Err(err.into()),
+            };
+            if let Err(err) = store {
+                tracing::warn!(
+                    target: crate::LAKE_FRAMEWORK,
+                    provider = PROVIDER,
+                    start_after,
+                    error = %err,
+                    "Failed to write the list page to the disk cache",
+                );
+            }
+        }
+        Ok(output)
+    }
+
+    async fn head_object(
+        &self,
+        bucket: &str,
+        prefix: &str,
+    ) -> Result<
+        HeadObjectOutput,
+        aws_sdk_s3::error::SdkError<aws_sdk_s3::operation::head_object::HeadObjectError>,
+    > {
+        if tokio::fs::try_exists(self.object_path(bucket, prefix))
+            .await
+            .unwrap_or(false)
+        {
+            return Ok(HeadObjectOutput::builder().build());
+        }
+        self.inner.head_object(bucket, prefix).await
+    }
+}
+
+/// Returns true if the response is `304 Not Modified` to the conditional `GetObject` request
+fn is_not_modified(
+    err: &aws_sdk_s3::error::SdkError<aws_sdk_s3::operation::get_object::GetObjectError>,
+) -> bool {
+    err.raw_response()
+        .map(|response| response.status().as_u16() == 304)
+        .unwrap_or(false)
+}
+
+/// Waits until the object appears in the bucket.
+/// Instead of re-issuing the full `GetObject` requests we probe the object with the `HeadObject` ones
+/// with a small delay in between.
+/// Returns early on the non-retriable errors, so the following `GetObject` request surfaces them.
+/// The probes count towards the `attempt`s of the caller, so the `403 AccessDenied` answers are not waited for forever
+async fn wait_for_object(
+    lake_s3_client: &(impl S3Client + ?Sized),
+    s3_bucket_name: &str,
+    key: &str,
+    attempt: &mut u64,
+) {
+    loop {
+        *attempt += 1;
+        tokio::time::sleep(OBJECT_PROBE_INTERVAL).await;
+        match lake_s3_client.head_object(s3_bucket_name, key).await {
+            Ok(_) => return,
+            Err(err)
+                if !matches!(
+                    crate::types::ErrorKind::from_object_sdk_error(&err, *attempt),
+                    crate::types::ErrorKind::NotFoundYet | crate::types::ErrorKind::Transient
+                ) =>
+            {
+                return
+            }
+            Err(err) => {
+                tracing::debug!(
+                    target: crate::LAKE_FRAMEWORK,
+                    provider = PROVIDER,
+                    key,
+                    attempt,
+                    error = ?err,
+                    "Object is not available yet. Probing again in {:?}",
+                    OBJECT_PROBE_INTERVAL,
+                );
+            }
+        }
+    }
+}
+
 /// Queries the list of the objects in the bucket, grouped by "/" delimiter.
+/// Follows the continuation token for up to `max_pages` pages (1000 keys each).
 /// Returns the list of block heights that can be fetched
 pub(crate) async fn list_block_heights(
-    lake_s3_client: &impl S3Client,
+    lake_s3_client: &(impl S3Client + ?Sized),
     s3_bucket_name: &str,
+    key_layout: &dyn KeyLayout,
     start_from_block_height: crate::types::BlockHeight,
+    max_pages: usize,
 ) -> Result<Vec<crate::types::BlockHeight>, crate::types::LakeError> {
     tracing::debug!(
         target: crate::LAKE_FRAMEWORK,
-        "Fetching block heights from S3, after #{}...",
-        start_from_block_height
+        provider = PROVIDER,
+        block_height = start_from_block_height,
+        "Fetching block heights from S3...",
     );
-    let response = lake_s3_client
-        .list_objects(s3_bucket_name, &format!("{:0>12}", start_from_block_height))
+    let start_after = key_layout.list_start_after(start_from_block_height);
+    let mut block_heights = vec![];
+    let mut continuation_token = None;
+
+    for _ in 0..std::cmp::max(max_pages, 1) {
+        let response = lake_s3_client
+            .list_objects(s3_bucket_name, &start_after, continuation_token.take())
+            .await?;
+
+        if let Some(common_prefixes) = response.common_prefixes {
+            block_heights.extend(
+                common_prefixes
+                    .into_iter()
+                    .filter_map(|common_prefix| common_prefix.prefix)
+                    .filter_map(|prefix_string| key_layout.parse_block_height(&prefix_string)),
+            );
+        }
+
+        match response.next_continuation_token {
+            Some(next_continuation_token) => continuation_token = Some(next_continuation_token),
+            None => break,
+        }
+    }
+
+    Ok(block_heights)
+}
+
+/// Splits the height range starting from `start_from_block_height` into `concurrency` prefix ranges
+/// and lists them with concurrent S3 `List` requests, merging the results in order.
+///
+/// Each range spans slightly less heights than a single [list_block_heights] call can return,
+/// so a range that is listed in full always contains a height from the next range.
+/// The merge stops at the first range that doesn't reach the next one to never leave gaps
+/// in the returned heights.
+pub(crate) async fn list_block_heights_concurrently(
+    lake_s3_client: &(impl S3Client + ?Sized),
+    s3_bucket_name: &str,
+    key_layout: &dyn KeyLayout,
+    start_from_block_height: crate::types::BlockHeight,
+    max_pages: usize,
+    concurrency: usize,
+) -> Result<Vec<crate::types::BlockHeight>, crate::types::LakeError> {
+    if concurrency <= 1 {
+        return list_block_heights(
+            lake_s3_client,
+            s3_bucket_name,
+            key_layout,
+            start_from_block_height,
+            max_pages,
+        )
+        .await;
+    }
+
+    let range_span = (1000 * std::cmp::max(max_pages, 1) - 1) as u64;
+    let range_starts: Vec<crate::types::BlockHeight> = (0..concurrency as u64)
+        .map(|range_index| start_from_block_height + range_index * range_span)
+        .collect();
+
+    let ranges = futures::future::try_join_all(range_starts.iter().map(|range_start| {
+        list_block_heights(
+            lake_s3_client,
+            s3_bucket_name,
+            key_layout,
+            *range_start,
+            max_pages,
+        )
+    }))
+    .await?;
+
+    let mut block_heights = vec![];
+    for (range_index, range_block_heights) in ranges.into_iter().enumerate() {
+        let Some(next_range_start) = range_starts.get(range_index + 1).copied() else {
+            block_heights.extend(range_block_heights);
+            break;
+        };
+        let reaches_next_range = range_block_heights
+            .last()
+            .map(|block_height| *block_height >= next_range_start)
+            .unwrap_or(false);
+        block_heights.extend(
+            range_block_heights
+                .into_iter()
+                .take_while(|block_height| *block_height < next_range_start),
+        );
+        if !reaches_next_range {
+            break;
+        }
+    }
+
+    Ok(block_heights)
+}
+
+/// Finds the latest block height written to the bucket at or above `start_from_block_height`.
+/// Returns `None` if there are no such block heights in the bucket.
+///
+/// A `List` page that isn't full ends with the latest block height. Otherwise the height is
+/// galloped forward until the listing is empty and then binary searched, so the probe takes
+/// a logarithmic number of single-page S3 `List` requests of the distance to the tip.
+pub(crate) async fn find_latest_block_height(
+    lake_s3_client: &(impl S3Client + ?Sized),
+    s3_bucket_name: &str,
+    key_layout: &dyn KeyLayout,
+    start_from_block_height: crate::types::BlockHeight,
+) -> Result<Option<crate::types::BlockHeight>, crate::types::LakeError> {
+    const LIST_PAGE_SIZE: usize = 1000;
+
+    let block_heights = list_block_heights(
+        lake_s3_client,
+        s3_bucket_name,
+        key_layout,
+        start_from_block_height,
+        1,
+    )
+    .await?;
+    // the latest block height is known to be at or above `low` and below `high`
+    let mut low = match block_heights.last() {
+        None => return Ok(None),
+        Some(last) if block_heights.len() < LIST_PAGE_SIZE => return Ok(Some(*last)),
+        Some(last) => *last,
+    };
+    let mut high: Option<crate::types::BlockHeight> = None;
+    let mut step = LIST_PAGE_SIZE as u64;
+
+    loop {
+        let probe_block_height = match high {
+            Some(high) if high - low <= 1 => return Ok(Some(low)),
+            Some(high) => low + (high - low) / 2,
+            None => {
+                step = step.saturating_mul(2);
+                low.saturating_add(step)
+            }
+        };
+        let block_heights = list_block_heights(
+            lake_s3_client,
+            s3_bucket_name,
+            key_layout,
+            probe_block_height,
+            1,
+        )
         .await?;
+        match block_heights.last() {
+            None => high = Some(probe_block_height),
+            Some(last) if block_heights.len() < LIST_PAGE_SIZE => return Ok(Some(*last)),
+            Some(last) => low = *last,
+        }
+    }
+}
 
-    Ok(match response.common_prefixes {
-        None => vec![],
-        Some(common_prefixes) => common_prefixes
-            .into_iter()
-            .filter_map(|common_prefix| common_prefix.prefix)
-            .collect::<Vec<String>>()
-            .into_iter()
-            .filter_map(|prefix_string| {
-                prefix_string
-                    .split('/')
-                    .next()
-                    .map(u64::from_str)
-                    .and_then(|num| num.ok())
-            })
-            .collect(),
-    })
+/// Checks whether the object exists in the bucket with the `HeadObject` request, which is cheaper than getting it.
+/// Returns `None` if the check has failed, so the caller gets the object to find out
+pub(crate) async fn object_exists(
+    lake_s3_client: &(impl S3Client + ?Sized),
+    s3_bucket_name: &str,
+    key: &str,
+) -> Option<bool> {
+    match lake_s3_client.head_object(s3_bucket_name, key).await {
+        Ok(_) => Some(true),
+        Err(err) => match crate::types::ErrorKind::from_object_sdk_error(&err, 1) {
+            crate::types::ErrorKind::NotFoundYet => Some(false),
+            _ => {
+                tracing::debug!(
+                    target: crate::LAKE_FRAMEWORK,
+                    provider = PROVIDER,
+                    key,
+                    error = ?err,
+                    "Failed to check whether the object exists",
+                );
+                None
+            }
+        },
+    }
+}
+
+/// By the given block height tries to get the `block.json` object and parses it as a JSON.
+/// Returns `None` if the object doesn't exist in the bucket, which means the block
+/// is either skipped or not written yet.
+///
+/// `attempt` is the number of the previous attempts to get the block, so the `403 AccessDenied` answers
+/// are not taken for the missing block forever, see [crate::types::ErrorKind::from_object_sdk_error]
+pub(crate) async fn fetch_block_view_if_exists(
+    lake_s3_client: &(impl S3Client + ?Sized),
+    s3_bucket_name: &str,
+    key_layout: &dyn KeyLayout,
+    block_height: crate::types::BlockHeight,
+    mut attempt: u64,
+) -> Result<Option<crate::near_indexer_primitives::views::BlockView>, crate::types::LakeError> {
+    let body_bytes = loop {
+        attempt += 1;
+        match lake_s3_client
+            .get_object(s3_bucket_name, &key_layout.block_key(block_height))
+            .await
+        {
+            Ok(response) => match response.body.collect().await {
+                Ok(bytes_stream) => break bytes_stream.into_bytes(),
+                Err(err) => {
+                    tracing::debug!(
+                        target: crate::LAKE_FRAMEWORK,
+                        provider = PROVIDER,
+                        block_height,
+                        attempt,
+                        error = ?err,
+                        "Failed to read bytes from the block response. Retrying in 1s...",
+                    );
+                    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                }
+            },
+            Err(err) => match crate::types::ErrorKind::from_object_sdk_error(&err, attempt) {
+                crate::types::ErrorKind::NotFoundYet => return Ok(None),
+                crate::types::ErrorKind::Transient => {
+                    tracing::debug!(
+                        target: crate::LAKE_FRAMEWORK,
+                        provider = PROVIDER,
+                        block_height,
+                        attempt,
+                        error = ?err,
+                        "Failed to get the block. Retrying immediately",
+                    );
+                }
+                _ => return Err(crate::types::LakeError::from(err).at_block(block_height, None)),
+            },
+        }
+    };
+
+    serde_json::from_slice::<crate::near_indexer_primitives::views::BlockView>(body_bytes.as_ref())
+        .map(Some)
+        .map_err(|err| crate::types::LakeError::from(err).at_block(block_height, None))
+}
+
+/// Gets the height of the block with the given hash from the index object.
+/// Returns `None` if the bucket doesn't index the hash
+pub(crate) async fn fetch_block_height_by_hash(
+    lake_s3_client: &(impl S3Client + ?Sized),
+    s3_bucket_name: &str,
+    key_layout: &dyn KeyLayout,
+    block_hash: &near_lake_primitives::CryptoHash,
+) -> Result<Option<crate::types::BlockHeight>, crate::types::LakeError> {
+    let mut attempt: u64 = 0;
+    let body_bytes = loop {
+        attempt += 1;
+        match lake_s3_client
+            .get_object(s3_bucket_name, &key_layout.block_hash_key(block_hash))
+            .await
+        {
+            Ok(response) => match response.body.collect().await {
+                Ok(bytes_stream) => break bytes_stream.into_bytes(),
+                Err(err) => {
+                    tracing::debug!(
+                        target: crate::LAKE_FRAMEWORK,
+                        provider = PROVIDER,
+                        %block_hash,
+                        attempt,
+                        error = ?err,
+                        "Failed to read bytes from the block hash response. Retrying immediately",
+                    );
+                }
+            },
+            Err(err) => match crate::types::ErrorKind::from_object_sdk_error(&err, attempt) {
+                crate::types::ErrorKind::NotFoundYet => return Ok(None),
+                crate::types::ErrorKind::Transient => {
+                    tracing::debug!(
+                        target: crate::LAKE_FRAMEWORK,
+                        provider = PROVIDER,
+                        %block_hash,
+                        attempt,
+                        error = ?err,
+                        "Failed to get the block hash. Retrying immediately",
+                    );
+                }
+                _ => return Err(err.into()),
+            },
+        }
+    };
+
+    Ok(Some(serde_json::from_slice(body_bytes.as_ref())?))
 }
 
 /// By the given block height gets the objects:
 /// - block.json
-/// - shard_N.json
+/// - shard_N.json (only for the `track_shards` if provided)
+///
 /// Reads the content of the objects and parses as a JSON.
 /// Returns the result in `near_indexer_primitives::StreamerMessage`
 pub(crate) async fn fetch_streamer_message(
-    lake_s3_client: &impl S3Client,
+    lake_s3_client: &(impl S3Client + ?Sized),
     s3_bucket_name: &str,
+    key_layout: &dyn KeyLayout,
     block_height: crate::types::BlockHeight,
+    track_shards: Option<&[u64]>,
+    parser: &crate::parser::ParserPool,
 ) -> Result<near_lake_primitives::StreamerMessage, crate::types::LakeError> {
     let block_view = {
+        let block_key = key_layout.block_key(block_height);
+        let mut attempt: u64 = 0;
         let body_bytes = loop {
-            match lake_s3_client
-                .get_object(s3_bucket_name, &format!("{:0>12}/block.json", block_height))
-                .await
-            {
+            attempt += 1;
+            match lake_s3_client.get_object(s3_bucket_name, &block_key).await {
                 Ok(response) => {
-                    match response.body.collect().await {
-                        Ok(bytes_stream) => break bytes_stream.into_bytes(),
+                    match parser
+                        .read_body(response.body, response.content_length)
+                        .await
+                    {
+                        Ok(body) => break body,
                         Err(err) => {
                             tracing::debug!(
                                 target: crate::LAKE_FRAMEWORK,
-                                "Failed to read bytes from the block #{:0>12} response. Retrying immediately.\n{:#?}",
+                                provider = PROVIDER,
                                 block_height,
-                                err,
+                                attempt,
+                                error = ?err,
+                                "Failed to read bytes from the block response. Retrying immediately",
                             );
                         }
                     };
                 }
-                Err(err) => {
-                    tracing::debug!(
-                        target: crate::LAKE_FRAMEWORK,
-                        "Failed to get {:0>12}/block.json. Retrying immediately\n{:#?}",
-                        block_height,
-                        err
-                    );
-                }
+                Err(err) => match crate::types::ErrorKind::from_object_sdk_error(&err, attempt) {
+                    crate::types::ErrorKind::NotFoundYet => {
+                        tracing::debug!(
+                            target: crate::LAKE_FRAMEWORK,
+                            provider = PROVIDER,
+                            block_height,
+                            attempt,
+                            key = %block_key,
+                            "The block is not written yet. Waiting for it to appear...",
+                        );
+                        wait_for_object(lake_s3_client, s3_bucket_name, &block_key, &mut attempt)
+                            .await;
+                    }
+                    crate::types::ErrorKind::Transient => {
+                        tracing::debug!(
+                            target: crate::LAKE_FRAMEWORK,
+                            provider = PROVIDER,
+                            block_height,
+                            attempt,
+                            key = %block_key,
+                            error = ?err,
+                            "Failed to get the block. Retrying immediately",
+                        );
+                    }
+                    _ => {
+                        return Err(crate::types::LakeError::from(err).at_block(block_height, None))
+                    }
+                },
             };
         };
 
-        serde_json::from_slice::<crate::near_indexer_primitives::views::BlockView>(
-            body_bytes.as_ref(),
-        )?
+        parser
+            .parse::<crate::near_indexer_primitives::views::BlockView>(body_bytes)
+            .await
+            .map_err(|err| err.at_block(block_height, None))?
     };
 
+    fetch_streamer_message_with_block_view(
+        lake_s3_client,
+        s3_bucket_name,
+        key_layout,
+        block_view,
+        track_shards,
+        parser,
+    )
+    .await
+}
+
+/// Gets the shard_N.json objects (only for the `track_shards` if provided) of the block
+/// which `block.json` has been fetched already, see [fetch_streamer_message]
+pub(crate) async fn fetch_streamer_message_with_block_view(
+    lake_s3_client: &(impl S3Client + ?Sized),
+    s3_bucket_name: &str,
+    key_layout: &dyn KeyLayout,
+    block_view: crate::near_indexer_primitives::views::BlockView,
+    track_shards: Option<&[u64]>,
+    parser: &crate::parser::ParserPool,
+) -> Result<near_lake_primitives::StreamerMessage, crate::types::LakeError> {
+    let block_height = block_view.header.height;
     let fetch_shards_futures = (0..block_view.chunks.len() as u64)
+        .filter(|shard_id| match track_shards {
+            Some(track_shards) => track_shards.contains(shard_id),
+            None => true,
+        })
         .collect::<Vec<u64>>()
         .into_iter()
         .map(|shard_id| {
-            fetch_shard_or_retry(lake_s3_client, s3_bucket_name, block_height, shard_id)
+            fetch_shard_or_retry(
+                lake_s3_client,
+                s3_bucket_name,
+                key_layout,
+                block_height,
+                shard_id,
+                parser,
+            )
         });
 
     let shards = futures::future::try_join_all(fetch_shards_futures).await?;
@@ -173,29 +1051,33 @@ pub(crate) async fn fetch_streamer_message(
 
 /// Fetches the shard data JSON from AWS S3 and returns the `IndexerShard`
 async fn fetch_shard_or_retry(
-    lake_s3_client: &impl S3Client,
+    lake_s3_client: &(impl S3Client + ?Sized),
     s3_bucket_name: &str,
+    key_layout: &dyn KeyLayout,
     block_height: crate::types::BlockHeight,
     shard_id: u64,
+    parser: &crate::parser::ParserPool,
 ) -> Result<near_lake_primitives::IndexerShard, crate::types::LakeError> {
+    let shard_key = key_layout.shard_key(block_height, shard_id);
+    let mut attempt: u64 = 0;
     let body_bytes = loop {
-        match lake_s3_client
-            .get_object(
-                s3_bucket_name,
-                &format!("{:0>12}/shard_{}.json", block_height, shard_id),
-            )
-            .await
-        {
+        attempt += 1;
+        match lake_s3_client.get_object(s3_bucket_name, &shard_key).await {
             Ok(response) => {
-                let body_bytes = match response.body.collect().await {
-                    Ok(body) => body.into_bytes(),
+                let body_bytes = match parser
+                    .read_body(response.body, response.content_length)
+                    .await
+                {
+                    Ok(body) => body,
                     Err(err) => {
                         tracing::debug!(
                             target: crate::LAKE_FRAMEWORK,
-                            "Failed to read the {:0>12}/shard_{}.json. Retrying in 1s...\n {:#?}",
+                            provider = PROVIDER,
                             block_height,
                             shard_id,
-                            err,
+                            attempt,
+                            error = ?err,
+                            "Failed to read bytes from the shard response. Retrying in 1s...",
                         );
                         tokio::time::sleep(std::time::Duration::from_secs(1)).await;
                         continue;
@@ -204,18 +1086,43 @@ async fn fetch_shard_or_retry(
 
                 break body_bytes;
             }
-            Err(err) => {
-                tracing::debug!(
-                    target: crate::LAKE_FRAMEWORK,
-                    "Failed to fetch shard #{}, retrying immediately\n{:#?}",
-                    shard_id,
-                    err
-                );
-            }
+            Err(err) => match crate::types::ErrorKind::from_object_sdk_error(&err, attempt) {
+                crate::types::ErrorKind::NotFoundYet => {
+                    tracing::debug!(
+                        target: crate::LAKE_FRAMEWORK,
+                        provider = PROVIDER,
+                        block_height,
+                        shard_id,
+                        attempt,
+                        key = %shard_key,
+                        "The shard is not written yet. Waiting for it to appear...",
+                    );
+                    wait_for_object(lake_s3_client, s3_bucket_name, &shard_key, &mut attempt).await;
+                }
+                crate::types::ErrorKind::Transient => {
+                    tracing::debug!(
+                        target: crate::LAKE_FRAMEWORK,
+                        provider = PROVIDER,
+                        block_height,
+                        shard_id,
+                        attempt,
+                        error = ?err,
+                        "Failed to fetch the shard. Retrying immediately",
+                    );
+                }
+                _ => {
+                    return Err(
+                        crate::types::LakeError::from(err).at_block(block_height, Some(shard_id))
+                    )
+                }
+            },
         }
     };
 
-    Ok(serde_json::from_slice::<near_lake_primitives::IndexerShard>(body_bytes.as_ref())?)
+    parser
+        .parse::<near_lake_primitives::IndexerShard>(body_bytes)
+        .await
+        .map_err(|err| err.at_block(block_height, Some(shard_id)))
 }
 
 #[cfg(test)]
@@ -253,22 +1160,40 @@ mod test {
             &self,
             _bucket: &str,
             _start_after: &str,
+            _continuation_token: Option<String>,
         ) -> Result<
             ListObjectsV2Output,
             aws_sdk_s3::error::SdkError<aws_sdk_s3::operation::list_objects_v2::ListObjectsV2Error>,
         > {
             Ok(ListObjectsV2OutputBuilder::default().build())
         }
+
+        async fn head_object(
+            &self,
+            _bucket: &str,
+            _prefix: &str,
+        ) -> Result<
+            HeadObjectOutput,
+            aws_sdk_s3::error::SdkError<aws_sdk_s3::operation::head_object::HeadObjectError>,
+        > {
+            Ok(HeadObjectOutput::builder().build())
+        }
     }
 
     #[tokio::test]
     async fn deserializes_meta_transactions() {
         let lake_client = LakeS3Client {};
 
-        let streamer_message =
-            fetch_streamer_message(&lake_client, "near-lake-data-mainnet", 879765)
-                .await
-                .unwrap();
+        let streamer_message = fetch_streamer_message(
+            &lake_client,
+            "near-lake-data-mainnet",
+            &DefaultKeyLayout,
+            879765,
+            None,
+            &crate::parser::ParserPool::default(),
+        )
+        .await
+        .unwrap();
 
         let delegate_action = &streamer_message.shards[0]
             .chunk
@@ -305,4 +1230,660 @@ mod test {
             })
         );
     }
+
+    #[tokio::test]
+    async fn fetches_tracked_shards_only() {
+        let lake_client = LakeS3Client {};
+
+        let streamer_message = fetch_streamer_message(
+            &lake_client,
+            "near-lake-data-mainnet",
+            &DefaultKeyLayout,
+            879765,
+            Some(&[]),
+            &crate::parser::ParserPool::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(streamer_message.block.header.height, 879765);
+        assert!(streamer_message.shards.is_empty());
+    }
+
+    /// Points the shard keys to the `block.json` objects, so the shards fail to parse
+    #[derive(Debug)]
+    struct BrokenShardKeyLayout;
+
+    impl KeyLayout for BrokenShardKeyLayout {
+        fn block_key(&self, block_height: crate::types::BlockHeight) -> String {
+            DefaultKeyLayout.block_key(block_height)
+        }
+
+        fn shard_key(&self, block_height: crate::types::BlockHeight, _shard_id: u64) -> String {
+            DefaultKeyLayout.block_key(block_height)
+        }
+
+        fn list_start_after(&self, block_height: crate::types::BlockHeight) -> String {
+            DefaultKeyLayout.list_start_after(block_height)
+        }
+
+        fn parse_block_height(&self, prefix: &str) -> Option<crate::types::BlockHeight> {
+            DefaultKeyLayout.parse_block_height(prefix)
+        }
+    }
+
+    #[tokio::test]
+    async fn attaches_block_height_and_shard_id_to_errors() {
+        let lake_client = LakeS3Client {};
+
+        let err = fetch_streamer_message(
+            &lake_client,
+            "near-lake-data-mainnet",
+            &BrokenShardKeyLayout,
+            879765,
+            Some(&[0]),
+            &crate::parser::ParserPool::default(),
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(err.block_height(), Some(879765));
+        assert_eq!(err.shard_id(), Some(0));
+        assert!(err
+            .to_string()
+            .starts_with("Failed to fetch block #879765 (shard #0): "));
+    }
+
+    /// Serves the block heights in pages of 2 prefixes
+    #[derive(Clone, Debug)]
+    pub struct PaginatedS3Client {}
+
+    #[async_trait]
+    impl S3Client for PaginatedS3Client {
+        async fn get_object(
+            &self,
+            _bucket: &str,
+            _prefix: &str,
+        ) -> Result<
+            GetObjectOutput,
+            aws_sdk_s3::error::SdkError<aws_sdk_s3::operation::get_object::GetObjectError>,
+        > {
+            unimplemented!()
+        }
+
+        async fn list_objects(
+            &self,
+            _bucket: &str,
+            _start_after: &str,
+            continuation_token: Option<String>,
+        ) -> Result<
+            ListObjectsV2Output,
+            aws_sdk_s3::error::SdkError<aws_sdk_s3::operation::list_objects_v2::ListObjectsV2Error>,
+        > {
+            let page: u64 = continuation_token
+                .map(|token| token.parse().unwrap())
+                .unwrap_or(0);
+            let common_prefixes = (page * 2..page * 2 + 2)
+                .map(|block_height| {
+                    aws_sdk_s3::types::CommonPrefix::builder()
+                        .prefix(format!("{:0>12}/", block_height))
+                        .build()
+                })
+                .collect();
+            Ok(ListObjectsV2OutputBuilder::default()
+                .set_common_prefixes(Some(common_prefixes))
+                .next_continuation_token((page + 1).to_string())
+                .build())
+        }
+
+        async fn head_object(
+            &self,
+            _bucket: &str,
+            _prefix: &str,
+        ) -> Result<
+            HeadObjectOutput,
+            aws_sdk_s3::error::SdkError<aws_sdk_s3::operation::head_object::HeadObjectError>,
+        > {
+            Ok(HeadObjectOutput::builder().build())
+        }
+    }
+
+    #[tokio::test]
+    async fn lists_block_heights_from_multiple_pages() {
+        let lake_client = PaginatedS3Client {};
+
+        let block_heights = list_block_heights(
+            &lake_client,
+            "near-lake-data-mainnet",
+            &DefaultKeyLayout,
+            0,
+            3,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(block_heights, vec![0, 1, 2, 3, 4, 5]);
+    }
+
+    /// Lists up to 1000 consecutive block heights (up to #2500) starting from the `start_after` one
+    #[derive(Debug)]
+    pub struct DenseS3Client {}
+
+    #[async_trait]
+    impl S3Client for DenseS3Client {
+        async fn get_object(
+            &self,
+            _bucket: &str,
+            _prefix: &str,
+        ) -> Result<
+            GetObjectOutput,
+            aws_sdk_s3::error::SdkError<aws_sdk_s3::operation::get_object::GetObjectError>,
+        > {
+            unimplemented!()
+        }
+
+        async fn list_objects(
+            &self,
+            _bucket: &str,
+            start_after: &str,
+            _continuation_token: Option<String>,
+        ) -> Result<
+            ListObjectsV2Output,
+            aws_sdk_s3::error::SdkError<aws_sdk_s3::operation::list_objects_v2::ListObjectsV2Error>,
+        > {
+            let start: u64 = start_after.parse().unwrap();
+            let common_prefixes = (start..std::cmp::min(start + 1000, 2501))
+                .map(|block_height| {
+                    aws_sdk_s3::types::CommonPrefix::builder()
+                        .prefix(format!("{:0>12}/", block_height))
+                        .build()
+                })
+                .collect();
+            Ok(ListObjectsV2OutputBuilder::default()
+                .set_common_prefixes(Some(common_prefixes))
+                .build())
+        }
+
+        async fn head_object(
+            &self,
+            _bucket: &str,
+            _prefix: &str,
+        ) -> Result<
+            HeadObjectOutput,
+            aws_sdk_s3::error::SdkError<aws_sdk_s3::operation::head_object::HeadObjectError>,
+        > {
+            unimplemented!()
+        }
+    }
+
+    #[tokio::test]
+    async fn lists_block_heights_concurrently() {
+        let lake_client = DenseS3Client {};
+
+        let block_heights = list_block_heights_concurrently(
+            &lake_client,
+            "near-lake-data-mainnet",
+            &DefaultKeyLayout,
+            0,
+            1,
+            3,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(block_heights, (0..=2500).collect::<Vec<u64>>());
+    }
+
+    #[tokio::test]
+    async fn lists_block_heights_concurrently_without_gaps() {
+        let lake_client = PaginatedS3Client {};
+
+        let block_heights = list_block_heights_concurrently(
+            &lake_client,
+            "near-lake-data-mainnet",
+            &DefaultKeyLayout,
+            0,
+            1,
+            3,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(block_heights, vec![0, 1]);
+    }
+
+    #[tokio::test]
+    async fn finds_latest_block_height() {
+        let lake_client = DenseS3Client {};
+
+        let latest_block_height =
+            find_latest_block_height(&lake_client, "near-lake-data-mainnet", &DefaultKeyLayout, 0)
+                .await
+                .unwrap();
+        let missing_block_height = find_latest_block_height(
+            &lake_client,
+            "near-lake-data-mainnet",
+            &DefaultKeyLayout,
+            3000,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(latest_block_height, Some(2500));
+        assert_eq!(missing_block_height, None);
+    }
+
+    #[tokio::test]
+    async fn parses_objects_on_dedicated_threads() {
+        let lake_client = LakeS3Client {};
+
+        let streamer_message = fetch_streamer_message(
+            &lake_client,
+            "near-lake-data-mainnet",
+            &DefaultKeyLayout,
+            879765,
+            None,
+            &crate::parser::ParserPool::new(2),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(streamer_message.block.header.height, 879765);
+        assert_eq!(streamer_message.shards.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn serves_fetched_objects_from_disk_cache() {
+        let cache_dir = std::env::temp_dir().join(format!(
+            "near-lake-framework-disk-cache-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&cache_dir);
+
+        let lake_client =
+            DiskCacheS3Client::new(std::sync::Arc::new(LakeS3Client {}), cache_dir.clone());
+        let streamer_message = fetch_streamer_message(
+            &lake_client,
+            "near-lake-data-mainnet",
+            &DefaultKeyLayout,
+            879765,
+            None,
+            &crate::parser::ParserPool::default(),
+        )
+        .await
+        .unwrap();
+
+        // `PaginatedS3Client` can't serve the objects, so they must come from the disk
+        let cached_client =
+            DiskCacheS3Client::new(std::sync::Arc::new(PaginatedS3Client {}), cache_dir.clone());
+        let cached_streamer_message = fetch_streamer_message(
+            &cached_client,
+            "near-lake-data-mainnet",
+            &DefaultKeyLayout,
+            879765,
+            None,
+            &crate::parser::ParserPool::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            serde_json::to_value(cached_streamer_message).unwrap(),
+            serde_json::to_value(streamer_message).unwrap()
+        );
+        std::fs::remove_dir_all(&cache_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn serves_full_list_pages_from_disk_cache() {
+        let cache_dir = std::env::temp_dir().join(format!(
+            "near-lake-framework-disk-cache-list-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&cache_dir);
+
+        let lake_client =
+            DiskCacheS3Client::new(std::sync::Arc::new(PaginatedS3Client {}), cache_dir.clone());
+        let block_heights = list_block_heights(
+            &lake_client,
+            "near-lake-data-mainnet",
+            &DefaultKeyLayout,
+            0,
+            3,
+        )
+        .await
+        .unwrap();
+
+        // `LakeS3Client` lists nothing, so the pages must come from the disk
+        let cached_client =
+            DiskCacheS3Client::new(std::sync::Arc::new(LakeS3Client {}), cache_dir.clone());
+        let cached_block_heights = list_block_heights(
+            &cached_client,
+            "near-lake-data-mainnet",
+            &DefaultKeyLayout,
+            0,
+            3,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(cached_block_heights, block_heights);
+        std::fs::remove_dir_all(&cache_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn evicts_damaged_objects_from_disk_cache() {
+        let cache_dir = std::env::temp_dir().join(format!(
+            "near-lake-framework-disk-cache-damaged-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&cache_dir);
+
+        let lake_client =
+            DiskCacheS3Client::new(std::sync::Arc::new(LakeS3Client {}), cache_dir.clone());
+        let block_path =
+            lake_client.object_path("near-lake-data-mainnet", "000000879765/block.json");
+        let parser = crate::parser::ParserPool::default();
+        let fetch_streamer_message = || {
+            fetch_streamer_message(
+                &lake_client,
+                "near-lake-data-mainnet",
+                &DefaultKeyLayout,
+                879765,
+                None,
+                &parser,
+            )
+        };
+        fetch_streamer_message().await.unwrap();
+        std::fs::write(&block_path, b"{\"header\":").unwrap();
+
+        let streamer_message = fetch_streamer_message().await.unwrap();
+
+        assert_eq!(streamer_message.block.header.height, 879765);
+        assert!(
+            serde_json::from_slice::<serde_json::Value>(&std::fs::read(&block_path).unwrap())
+                .is_ok()
+        );
+        // no temporary files are left behind
+        assert_eq!(
+            std::fs::read_dir(block_path.parent().unwrap())
+                .unwrap()
+                .count(),
+            2
+        );
+        std::fs::remove_dir_all(&cache_dir).unwrap();
+    }
+
+    #[test]
+    fn etag_cache_evicts_oldest_objects() {
+        let mut cache = EtagCache::with_capacity(2);
+        cache.insert("a".to_string(), "\"1\"".to_string(), vec![1]);
+        cache.insert("b".to_string(), "\"2\"".to_string(), vec![2]);
+        cache.insert("c".to_string(), "\"3\"".to_string(), vec![3]);
+
+        assert!(cache.get("a").is_none());
+        assert_eq!(cache.get("b"), Some(&("\"2\"".to_string(), vec![2])));
+        assert_eq!(cache.get("c"), Some(&("\"3\"".to_string(), vec![3])));
+    }
+
+    /// Serves the fixture blocks with `GetObject` only, the missing objects are `404 Not Found`
+    #[derive(Debug)]
+    pub struct GetOnlyS3Client {}
+
+    #[async_trait]
+    impl S3Client for GetOnlyS3Client {
+        async fn get_object(
+            &self,
+            _bucket: &str,
+            prefix: &str,
+        ) -> Result<
+            GetObjectOutput,
+            aws_sdk_s3::error::SdkError<aws_sdk_s3::operation::get_object::GetObjectError>,
+        > {
+            let path = format!("{}/blocks/{}", env!("CARGO_MANIFEST_DIR"), prefix);
+            match tokio::fs::read(path).await {
+                Ok(file_bytes) => Ok(GetObjectOutputBuilder::default()
+                    .body(ByteStream::new(SdkBody::from(file_bytes)))
+                    .build()),
+                Err(_) => Err(aws_sdk_s3::error::SdkError::service_error(
+                    aws_sdk_s3::operation::get_object::GetObjectError::NoSuchKey(
+                        aws_sdk_s3::types::error::NoSuchKey::builder().build(),
+                    ),
+                    aws_sdk_s3::config::http::HttpResponse::new(
+                        404u16.try_into().unwrap(),
+                        SdkBody::empty(),
+                    ),
+                )),
+            }
+        }
+
+        async fn list_objects(
+            &self,
+            _bucket: &str,
+            _start_after: &str,
+            _continuation_token: Option<String>,
+        ) -> Result<
+            ListObjectsV2Output,
+            aws_sdk_s3::error::SdkError<aws_sdk_s3::operation::list_objects_v2::ListObjectsV2Error>,
+        > {
+            unimplemented!()
+        }
+    }
+
+    #[tokio::test]
+    async fn head_object_falls_back_to_get_object() {
+        let lake_client = GetOnlyS3Client {};
+
+        assert!(lake_client
+            .head_object("near-lake-data-mainnet", "000000879765/block.json")
+            .await
+            .is_ok());
+        let err = lake_client
+            .head_object("near-lake-data-mainnet", "000000879766/block.json")
+            .await
+            .unwrap_err();
+        assert_eq!(
+            crate::types::ErrorKind::from_sdk_error(&err),
+            crate::types::ErrorKind::NotFoundYet
+        );
+        assert_eq!(
+            object_exists(
+                &lake_client,
+                "near-lake-data-mainnet",
+                "000000879766/block.json"
+            )
+            .await,
+            Some(false)
+        );
+    }
+
+    /// Answers `403 AccessDenied` for the first `GetObject` request of every key, as S3 does for the missing keys
+    /// when the credentials lack the `s3:ListBucket` permission, and serves the fixture blocks afterwards
+    #[derive(Debug, Default)]
+    pub struct AccessDeniedS3Client {
+        requested_keys: std::sync::Mutex<std::collections::HashSet<String>>,
+    }
+
+    fn access_denied<E>(error: E) -> aws_sdk_s3::error::SdkError<E> {
+        aws_sdk_s3::error::SdkError::service_error(
+            error,
+            aws_sdk_s3::config::http::HttpResponse::new(
+                403u16.try_into().unwrap(),
+                SdkBody::empty(),
+            ),
+        )
+    }
+
+    #[async_trait]
+    impl S3Client for AccessDeniedS3Client {
+        async fn get_object(
+            &self,
+            bucket: &str,
+            prefix: &str,
+        ) -> Result<
+            GetObjectOutput,
+            aws_sdk_s3::error::SdkError<aws_sdk_s3::operation::get_object::GetObjectError>,
+        > {
+            if self
+                .requested_keys
+                .lock()
+                .unwrap()
+                .insert(prefix.to_string())
+            {
+                return Err(access_denied(
+                    aws_sdk_s3::operation::get_object::GetObjectError::generic(
+                        aws_sdk_s3::error::ErrorMetadata::builder()
+                            .code("AccessDenied")
+                            .message("Access Denied")
+                            .build(),
+                    ),
+                ));
+            }
+            LakeS3Client {}.get_object(bucket, prefix).await
+        }
+
+        async fn list_objects(
+            &self,
+            _bucket: &str,
+            _start_after: &str,
+            _continuation_token: Option<String>,
+        ) -> Result<
+            ListObjectsV2Output,
+            aws_sdk_s3::error::SdkError<aws_sdk_s3::operation::list_objects_v2::ListObjectsV2Error>,
+        > {
+            unimplemented!()
+        }
+
+        async fn head_object(
+            &self,
+            _bucket: &str,
+            _prefix: &str,
+        ) -> Result<
+            HeadObjectOutput,
+            aws_sdk_s3::error::SdkError<aws_sdk_s3::operation::head_object::HeadObjectError>,
+        > {
+            Ok(HeadObjectOutput::builder().build())
+        }
+    }
+
+    #[tokio::test]
+    async fn waits_for_access_denied_objects() {
+        let lake_client = AccessDeniedS3Client::default();
+
+        let streamer_message = fetch_streamer_message(
+            &lake_client,
+            "near-lake-data-mainnet",
+            &DefaultKeyLayout,
+            879765,
+            None,
+            &crate::parser::ParserPool::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(streamer_message.block.header.height, 879765);
+        assert_eq!(streamer_message.shards.len(), 1);
+    }
+
+    /// Answers `403 AccessDenied` to every `GetObject` and `HeadObject` request
+    #[derive(Debug)]
+    pub struct ForbiddenS3Client {}
+
+    #[async_trait]
+    impl S3Client for ForbiddenS3Client {
+        async fn get_object(
+            &self,
+            _bucket: &str,
+            _prefix: &str,
+        ) -> Result<
+            GetObjectOutput,
+            aws_sdk_s3::error::SdkError<aws_sdk_s3::operation::get_object::GetObjectError>,
+        > {
+            Err(access_denied(
+                aws_sdk_s3::operation::get_object::GetObjectError::generic(
+                    aws_sdk_s3::error::ErrorMetadata::builder()
+                        .code("AccessDenied")
+                        .build(),
+                ),
+            ))
+        }
+
+        async fn list_objects(
+            &self,
+            _bucket: &str,
+            _start_after: &str,
+            _continuation_token: Option<String>,
+        ) -> Result<
+            ListObjectsV2Output,
+            aws_sdk_s3::error::SdkError<aws_sdk_s3::operation::list_objects_v2::ListObjectsV2Error>,
+        > {
+            unimplemented!()
+        }
+
+        async fn head_object(
+            &self,
+            _bucket: &str,
+            _prefix: &str,
+        ) -> Result<
+            HeadObjectOutput,
+            aws_sdk_s3::error::SdkError<aws_sdk_s3::operation::head_object::HeadObjectError>,
+        > {
+            Err(access_denied(
+                aws_sdk_s3::operation::head_object::HeadObjectError::generic(
+                    aws_sdk_s3::error::ErrorMetadata::builder().build(),
+                ),
+            ))
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn gives_up_waiting_for_access_denied_objects() {
+        let err = fetch_streamer_message(
+            &ForbiddenS3Client {},
+            "near-lake-data-mainnet",
+            &DefaultKeyLayout,
+            879765,
+            None,
+            &crate::parser::ParserPool::default(),
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(err.kind(), crate::types::ErrorKind::Unauthorized);
+        assert_eq!(err.block_height(), Some(879765));
+    }
+
+    #[test]
+    fn classifies_access_denied_objects_as_not_found_yet() {
+        let err = access_denied(
+            aws_sdk_s3::operation::head_object::HeadObjectError::generic(
+                aws_sdk_s3::error::ErrorMetadata::builder().build(),
+            ),
+        );
+        assert_eq!(
+            crate::types::ErrorKind::from_object_sdk_error(&err, 1),
+            crate::types::ErrorKind::NotFoundYet
+        );
+        assert_eq!(
+            crate::types::ErrorKind::from_sdk_error(&err),
+            crate::types::ErrorKind::Unauthorized
+        );
+        assert_eq!(
+            crate::types::ErrorKind::from_object_sdk_error(
+                &err,
+                crate::types::ACCESS_DENIED_ATTEMPTS + 1
+            ),
+            crate::types::ErrorKind::Unauthorized
+        );
+
+        let err = access_denied(aws_sdk_s3::operation::get_object::GetObjectError::generic(
+            aws_sdk_s3::error::ErrorMetadata::builder()
+                .code("InvalidAccessKeyId")
+                .build(),
+        ));
+        assert_eq!(
+            crate::types::ErrorKind::from_object_sdk_error(&err, 1),
+            crate::types::ErrorKind::Unauthorized
+        );
+    }
 }
@@ -0,0 +1,121 @@
+//! Re-publication of the blocks to the user's own bucket in the lake layout, see [S3RepublishSink]
+use near_lake_primitives::near_indexer_primitives::StreamerMessage;
+
+use crate::s3_fetchers::{DefaultKeyLayout, KeyLayout};
+use crate::types::LakeError;
+
+/// Sink writing the blocks back to the user's own bucket in the same layout the NEAR Lake buckets have,
+/// so the trimmed private lakes (e.g. only the blocks touching the team's contracts, or only some of the shards)
+/// can be re-streamed by the downstream jobs with this framework.
+///
+/// The shard objects are written before the `block.json` one. The skipped blocks leave gaps in the heights,
+/// so stream such a lake with [LakeBuilder::allow_gaps](crate::LakeBuilder::allow_gaps).
+/// ```no_run
+/// use near_lake_framework::S3RepublishSink;
+///
+/// # #[tokio::main]
+/// # async fn main() -> anyhow::Result<()> {
+///    let aws_config = aws_config::from_env().load().await;
+///    let sink = S3RepublishSink::new(aws_sdk_s3::Client::new(&aws_config), "my-private-lake");
+///    let contract_id: near_lake_primitives::AccountId = "contract.near".parse()?;
+///
+///    near_lake_framework::LakeBuilder::default()
+///        .mainnet()
+///        .start_block_height(88444526)
+///        .build()?
+///        .run_async(|mut block| {
+///            let (sink, contract_id) = (&sink, &contract_id);
+///            async move {
+///                if block.receipts().any(|receipt| receipt.receiver_id() == contract_id) {
+///                    sink.publish(block.streamer_message()).await?;
+///                }
+///                Ok::<_, near_lake_framework::LakeError>(())
+///            }
+///        })
+///        .await?;
+///    Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct S3RepublishSink {
+    s3: aws_sdk_s3::Client,
+    bucket: String,
+    key_layout: std::sync::Arc<dyn KeyLayout>,
+    index_block_hashes: bool,
+}
+
+impl S3RepublishSink {
+    pub fn new(s3: aws_sdk_s3::Client, bucket: impl Into<String>) -> Self {
+        Self {
+            s3,
+            bucket: bucket.into(),
+            key_layout: std::sync::Arc::new(DefaultKeyLayout),
+            index_block_hashes: false,
+        }
+    }
+
+    /// Sets up the custom [KeyLayout] of the object keys, [DefaultKeyLayout] by default
+    pub fn with_key_layout(mut self, key_layout: impl KeyLayout + 'static) -> Self {
+        self.key_layout = std::sync::Arc::new(key_layout);
+        self
+    }
+
+    /// Writes the block height by the block hash as well (see [KeyLayout::block_hash_key]), so the lake
+    /// can be looked up with [Lake::fetch_block_by_hash](crate::Lake::fetch_block_by_hash)
+    pub fn with_block_hash_index(mut self) -> Self {
+        self.index_block_hashes = true;
+        self
+    }
+
+    /// Writes the block and its shards to the bucket. The content of the shards can be trimmed before publishing
+    /// to keep only the relevant data. If some shards are dropped altogether, stream the lake with
+    /// [LakeBuilder::track_shards](crate::LakeBuilder::track_shards), otherwise the streamer waits for them
+    pub async fn publish(&self, streamer_message: &StreamerMessage) -> Result<(), LakeError> {
+        let block_height = streamer_message.block.header.height;
+
+        futures::future::try_join_all(streamer_message.shards.iter().map(|shard| {
+            self.put(
+                self.key_layout.shard_key(block_height, shard.shard_id),
+                serde_json::to_vec(shard),
+            )
+        }))
+        .await
+        .map_err(|err| err.at_block(block_height, None))?;
+        self.put(
+            self.key_layout.block_key(block_height),
+            serde_json::to_vec(&streamer_message.block),
+        )
+        .await
+        .map_err(|err| err.at_block(block_height, None))?;
+        if self.index_block_hashes {
+            self.put(
+                self.key_layout
+                    .block_hash_key(&streamer_message.block.header.hash),
+                serde_json::to_vec(&block_height),
+            )
+            .await
+            .map_err(|err| err.at_block(block_height, None))?;
+        }
+
+        tracing::debug!(
+            target: crate::LAKE_FRAMEWORK,
+            block_height,
+            bucket = %self.bucket,
+            shards = streamer_message.shards.len(),
+            "Published block",
+        );
+        Ok(())
+    }
+
+    async fn put(&self, key: String, body: serde_json::Result<Vec<u8>>) -> Result<(), LakeError> {
+        self.s3
+            .put_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .content_type("application/json")
+            .body(body?.into())
+            .send()
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,47 @@
+//! Graceful shutdown on SIGTERM and SIGINT, see [LakeBuilder::graceful_shutdown](crate::LakeBuilder::graceful_shutdown)
+use crate::control::ControlHandle;
+
+/// Stops listening for the signals on drop
+pub(crate) struct SignalHandler(tokio::task::JoinHandle<()>);
+
+impl Drop for SignalHandler {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Spawns the task calling [ControlHandle::shutdown] on the first SIGTERM or SIGINT
+pub(crate) fn spawn(control: ControlHandle) -> SignalHandler {
+    SignalHandler(tokio::spawn(async move {
+        wait_for_signal().await;
+        tracing::info!(
+            target: crate::LAKE_FRAMEWORK,
+            "Received the shutdown signal, draining the blocks being handled...",
+        );
+        control.shutdown();
+    }))
+}
+
+#[cfg(unix)]
+async fn wait_for_signal() {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut sigterm = match signal(SignalKind::terminate()) {
+        Ok(sigterm) => sigterm,
+        Err(err) => {
+            tracing::warn!(
+                target: crate::LAKE_FRAMEWORK,
+                error = %err,
+                "Failed to listen for SIGTERM, listening for SIGINT only",
+            );
+            let _ = tokio::signal::ctrl_c().await;
+            return;
+        }
+    };
+    futures::future::select(Box::pin(sigterm.recv()), Box::pin(tokio::signal::ctrl_c())).await;
+}
+
+#[cfg(not(unix))]
+async fn wait_for_signal() {
+    let _ = tokio::signal::ctrl_c().await;
+}
@@ -0,0 +1,389 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::path::PathBuf;
+
+use crate::types::BlockHeight;
+use async_trait::async_trait;
+use tokio::sync::Mutex;
+
+use crate::types::LakeError;
+
+/// The key the last processed block height is stored under
+pub const LAST_HEIGHT_KEY: &str = "last_height";
+
+/// The key the high watermark is published under, see [LakeBuilder::watermark_store](crate::LakeBuilder::watermark_store)
+pub const WATERMARK_KEY: &str = "watermark";
+
+/// Persistent key-value storage for the indexer state (the last processed block height,
+/// arbitrary checkpoints and cursors).
+///
+/// The framework provides [FileStateStore], [S3StateStore] and `PostgresStateStore` (with the `postgres-sink` feature).
+/// To keep the state in another database implement the trait with the client you already use in the indexer.
+#[async_trait]
+pub trait StateStore: Debug + Send + Sync {
+    /// Returns the value stored under the key, if any
+    async fn get(&self, key: &str) -> Result<Option<String>, LakeError>;
+
+    /// Stores the value under the key, overwriting the previous one
+    async fn set(&self, key: &str, value: &str) -> Result<(), LakeError>;
+
+    /// Returns the last processed block height, if any
+    async fn last_height(&self) -> Result<Option<BlockHeight>, LakeError> {
+        self.get(LAST_HEIGHT_KEY)
+            .await?
+            .map(|value| {
+                value.parse().map_err(|_| LakeError::StateStoreError {
+                    error_message: format!("Invalid last height value: {}", value),
+                })
+            })
+            .transpose()
+    }
+
+    /// Stores the last processed block height
+    async fn set_last_height(&self, block_height: BlockHeight) -> Result<(), LakeError> {
+        self.set(LAST_HEIGHT_KEY, &block_height.to_string()).await
+    }
+}
+
+/// Returns the block height following the stored watermark or last processed block height, whichever is higher
+pub(crate) async fn resume_block_height(
+    state_store: &dyn StateStore,
+) -> Result<Option<BlockHeight>, LakeError> {
+    let watermark = state_store
+        .get(WATERMARK_KEY)
+        .await?
+        .map(|value| {
+            value
+                .parse::<BlockHeight>()
+                .map_err(|_| LakeError::StateStoreError {
+                    error_message: format!("Invalid watermark value: {}", value),
+                })
+        })
+        .transpose()?;
+    let last_height = state_store.last_height().await?;
+    Ok(std::cmp::max(watermark, last_height).map(|block_height| block_height + 1))
+}
+
+/// [StateStore] keeping the state as a JSON object in a local file
+/// ```
+/// # use near_lake_framework::{FileStateStore, StateStore};
+/// # #[tokio::main]
+/// # async fn main() -> anyhow::Result<()> {
+/// # let path = std::env::temp_dir().join("near-lake-framework-state-store-doctest.json");
+/// # let _ = std::fs::remove_file(&path);
+///    let state_store = FileStateStore::new(&path);
+///    state_store.set_last_height(112205773).await?;
+///    assert_eq!(state_store.last_height().await?, Some(112205773));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct FileStateStore {
+    path: PathBuf,
+    // serializes the read-modify-write cycles of `set`
+    lock: Mutex<()>,
+}
+
+impl FileStateStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    async fn read(&self) -> Result<HashMap<String, String>, LakeError> {
+        match tokio::fs::read(&self.path).await {
+            Ok(content) => Ok(serde_json::from_slice(&content)?),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(HashMap::new()),
+            Err(err) => Err(LakeError::StateStoreError {
+                error_message: err.to_string(),
+            }),
+        }
+    }
+}
+
+#[async_trait]
+impl StateStore for FileStateStore {
+    async fn get(&self, key: &str) -> Result<Option<String>, LakeError> {
+        let _guard = self.lock.lock().await;
+        Ok(self.read().await?.remove(key))
+    }
+
+    async fn set(&self, key: &str, value: &str) -> Result<(), LakeError> {
+        let _guard = self.lock.lock().await;
+        let mut state = self.read().await?;
+        state.insert(key.to_string(), value.to_string());
+        // write to a temporary file and rename it to never leave a half-written state behind
+        let tmp_path = self.path.with_extension("tmp");
+        let write = async {
+            tokio::fs::write(&tmp_path, serde_json::to_vec(&state)?).await?;
+            tokio::fs::rename(&tmp_path, &self.path).await
+        };
+        write
+            .await
+            .map_err(|err: std::io::Error| LakeError::StateStoreError {
+                error_message: err.to_string(),
+            })
+    }
+}
+
+/// [StateStore] keeping every key as a separate object under the prefix in an S3 bucket
+/// ```no_run
+/// # use near_lake_framework::{S3StateStore, StateStore};
+/// # #[tokio::main]
+/// # async fn main() -> anyhow::Result<()> {
+///    let aws_config = aws_config::from_env().load().await;
+///    let state_store = S3StateStore::new(
+///        aws_sdk_s3::Client::new(&aws_config),
+///        "my-indexer-state",
+///        "nft-indexer/",
+///    );
+///    let start_block_height = state_store.last_height().await?.unwrap_or(112205773);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct S3StateStore {
+    s3: aws_sdk_s3::Client,
+    bucket: String,
+    prefix: String,
+}
+
+impl S3StateStore {
+    pub fn new(
+        s3: aws_sdk_s3::Client,
+        bucket: impl Into<String>,
+        prefix: impl Into<String>,
+    ) -> Self {
+        Self {
+            s3,
+            bucket: bucket.into(),
+            prefix: prefix.into(),
+        }
+    }
+
+    fn object_key(&self, key: &str) -> String {
+        format!("{}{}", self.prefix, key)
+    }
+}
+
+#[async_trait]
+impl StateStore for S3StateStore {
+    async fn get(&self, key: &str) -> Result<Option<String>, LakeError> {
+        let response = match self
+            .s3
+            .get_object()
+            .bucket(&self.bucket)
+            .key(self.object_key(key))
+            .send()
+            .await
+        {
+            Ok(response) => response,
+            Err(err)
+                if err
+                    .as_service_error()
+                    .map_or(false, |err| err.is_no_such_key()) =>
+            {
+                return Ok(None)
+            }
+            Err(err) => return Err(err.into()),
+        };
+        let bytes = response
+            .body
+            .collect()
+            .await
+            .map_err(|err| LakeError::StateStoreError {
+                error_message: err.to_string(),
+            })?
+            .into_bytes();
+        String::from_utf8(bytes.to_vec())
+            .map(Some)
+            .map_err(|err| LakeError::StateStoreError {
+                error_message: err.to_string(),
+            })
+    }
+
+    async fn set(&self, key: &str, value: &str) -> Result<(), LakeError> {
+        self.s3
+            .put_object()
+            .bucket(&self.bucket)
+            .key(self.object_key(key))
+            .body(value.as_bytes().to_vec().into())
+            .send()
+            .await
+            .map_err(|err| LakeError::StateStoreError {
+                error_message: err.to_string(),
+            })?;
+        Ok(())
+    }
+}
+
+/// [StateStore] keeping every key as a row of a Postgres table. Requires the `postgres-sink` feature
+///
+/// The table has the `key` and `value` text columns, create it with [PostgresStateStore::create_table]
+/// ```no_run
+/// # use near_lake_framework::{PostgresStateStore, StateStore};
+/// # #[tokio::main]
+/// # async fn main() -> anyhow::Result<()> {
+///    let (client, connection) =
+///        tokio_postgres::connect("host=localhost user=postgres", tokio_postgres::NoTls).await?;
+///    tokio::spawn(connection);
+///
+///    let state_store = PostgresStateStore::new(client, "indexer_state");
+///    state_store.create_table().await?;
+///    let start_block_height = state_store.last_height().await?.unwrap_or(112205773);
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "postgres-sink")]
+pub struct PostgresStateStore {
+    client: tokio_postgres::Client,
+    table: String,
+}
+
+#[cfg(feature = "postgres-sink")]
+impl Debug for PostgresStateStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PostgresStateStore")
+            .field("table", &self.table)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "postgres-sink")]
+impl PostgresStateStore {
+    /// Creates the store keeping the state in the `table`. The table name is put into the statements as is, quote it if needed
+    pub fn new(client: tokio_postgres::Client, table: impl Into<String>) -> Self {
+        Self {
+            client,
+            table: table.into(),
+        }
+    }
+
+    /// Creates the table unless it exists
+    pub async fn create_table(&self) -> Result<(), LakeError> {
+        self.client
+            .batch_execute(&format!(
+                "CREATE TABLE IF NOT EXISTS {} (key TEXT PRIMARY KEY, value TEXT NOT NULL)",
+                self.table
+            ))
+            .await
+            .map_err(|err| LakeError::StateStoreError {
+                error_message: err.to_string(),
+            })
+    }
+}
+
+#[cfg(feature = "postgres-sink")]
+#[async_trait]
+impl StateStore for PostgresStateStore {
+    async fn get(&self, key: &str) -> Result<Option<String>, LakeError> {
+        let row = self
+            .client
+            .query_opt(
+                &format!("SELECT value FROM {} WHERE key = $1", self.table),
+                &[&key],
+            )
+            .await
+            .map_err(|err| LakeError::StateStoreError {
+                error_message: err.to_string(),
+            })?;
+        Ok(row.map(|row| row.get(0)))
+    }
+
+    async fn set(&self, key: &str, value: &str) -> Result<(), LakeError> {
+        self.client
+            .execute(
+                &format!(
+                    "INSERT INTO {} (key, value) VALUES ($1, $2) \
+                     ON CONFLICT (key) DO UPDATE SET value = EXCLUDED.value",
+                    self.table
+                ),
+                &[&key, &value],
+            )
+            .await
+            .map_err(|err| LakeError::StateStoreError {
+                error_message: err.to_string(),
+            })?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn state_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "near-lake-framework-state-store-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join("state.json")
+    }
+
+    #[tokio::test]
+    async fn reads_nothing_from_missing_file() {
+        let state_store = FileStateStore::new(state_path("missing"));
+
+        assert_eq!(state_store.get("cursor").await.unwrap(), None);
+        assert_eq!(state_store.last_height().await.unwrap(), None);
+        assert_eq!(resume_block_height(&state_store).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn replaces_file_atomically() {
+        let path = state_path("atomic");
+        let state_store = FileStateStore::new(&path);
+
+        state_store.set("cursor", "a").await.unwrap();
+        state_store.set_last_height(100).await.unwrap();
+        state_store.set("cursor", "b").await.unwrap();
+
+        // the temporary file is renamed over the state, so only the complete state is left behind
+        assert!(!path.with_extension("tmp").exists());
+        let state: HashMap<String, String> =
+            serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(
+            state,
+            HashMap::from([
+                ("cursor".to_string(), "b".to_string()),
+                (LAST_HEIGHT_KEY.to_string(), "100".to_string()),
+            ])
+        );
+
+        let state_store = FileStateStore::new(&path);
+        assert_eq!(
+            state_store.get("cursor").await.unwrap(),
+            Some("b".to_string())
+        );
+        assert_eq!(state_store.last_height().await.unwrap(), Some(100));
+    }
+
+    #[tokio::test]
+    async fn keeps_state_on_failed_write() {
+        let path = state_path("failed-write");
+        let state_store = FileStateStore::new(&path);
+        state_store.set_last_height(100).await.unwrap();
+
+        // the temporary file can't be created in place of a directory
+        std::fs::create_dir(path.with_extension("tmp")).unwrap();
+        assert!(state_store.set_last_height(200).await.is_err());
+
+        assert_eq!(state_store.last_height().await.unwrap(), Some(100));
+    }
+
+    #[tokio::test]
+    async fn resumes_after_highest_stored_block_height() {
+        let state_store = FileStateStore::new(state_path("resume"));
+
+        state_store.set_last_height(100).await.unwrap();
+        assert_eq!(resume_block_height(&state_store).await.unwrap(), Some(101));
+
+        state_store.set(WATERMARK_KEY, "150").await.unwrap();
+        assert_eq!(resume_block_height(&state_store).await.unwrap(), Some(151));
+    }
+}
@@ -1,50 +1,48 @@
 use aws_sdk_s3::Client;
 
+use futures::future::FutureExt;
 use futures::stream::StreamExt;
 use tokio::sync::mpsc;
-use tokio::sync::mpsc::error::SendError;
 
 use near_lake_primitives::near_indexer_primitives;
 
+use crate::provider::{BlockProvider, S3BlockProvider};
 use crate::{s3_fetchers, types};
 
-/// Creates [mpsc::Receiver<near_indexer_primitives::StreamerMessage>] and
-/// [mpsc::Sender<near_indexer_primitives::StreamerMessage>] spawns the streamer
-/// process that writes [near_idnexer_primitives::StreamerMessage] to the given `mpsc::channel`
-/// returns both `sender` and `receiver`
+/// Creates the channel of the configured [crate::ChannelStrategy], spawns the streamer
+/// process that writes [near_indexer_primitives::StreamerMessage] to it and
+/// returns the handle of the streamer and the `receiver`
 pub(crate) fn streamer(
     config: crate::Lake,
 ) -> (
     tokio::task::JoinHandle<Result<(), crate::types::LakeError>>,
-    mpsc::Receiver<near_indexer_primitives::StreamerMessage>,
+    crate::channel::StreamerMessageReceiver,
 ) {
-    let (sender, receiver) = mpsc::channel(config.blocks_preload_pool_size);
+    let (sender, receiver) = crate::channel::channel(&config);
     (tokio::spawn(start(sender, config)), receiver)
 }
 
 fn stream_block_heights<'a: 'b, 'b>(
-    lake_s3_client: &'a s3_fetchers::LakeS3Client,
-    s3_bucket_name: &'a str,
+    block_provider: &'a dyn BlockProvider,
     mut start_from_block_height: crate::types::BlockHeight,
-) -> impl futures::Stream<Item = u64> + 'b {
+    poll_interval: std::time::Duration,
+    control: &'a crate::ControlHandle,
+) -> impl futures::Stream<Item = Result<u64, crate::types::LakeError>> + 'b {
     async_stream::stream! {
         loop {
-            tracing::debug!(target: crate::LAKE_FRAMEWORK, "Fetching a list of blocks from S3...");
-            match s3_fetchers::list_block_heights(
-                lake_s3_client,
-                s3_bucket_name,
-                start_from_block_height,
-            )
-            .await {
+            tracing::debug!(target: crate::LAKE_FRAMEWORK, provider = block_provider.name(), "Fetching a list of blocks...");
+            match block_provider.list_block_heights(start_from_block_height).await {
                 Ok(block_heights) => {
                     if block_heights.is_empty() {
+                        control.record_caught_up(start_from_block_height.saturating_sub(1));
                         tracing::debug!(
                             target: crate::LAKE_FRAMEWORK,
-                            "There are no newer block heights than {} in bucket {}. Fetching again in 2s...",
-                            start_from_block_height,
-                            s3_bucket_name,
+                            provider = block_provider.name(),
+                            block_height = start_from_block_height,
+                            "There are no newer block heights. Fetching again in {:?}...",
+                            poll_interval,
                         );
-                        tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+                        tokio::time::sleep(poll_interval).await;
                         continue;
                     }
                     tracing::debug!(
@@ -55,50 +53,269 @@ fn stream_block_heights<'a: 'b, 'b>(
 
                     start_from_block_height = *block_heights.last().unwrap() + 1;
                     for block_height in block_heights {
-                        tracing::debug!(target: crate::LAKE_FRAMEWORK, "Yielding {} block height...", block_height);
-                        yield block_height;
+                        tracing::debug!(target: crate::LAKE_FRAMEWORK, provider = block_provider.name(), block_height, "Yielding block height...");
+                        yield Ok(block_height);
                     }
                 }
-                Err(err) => {
+                Err(err) if matches!(err.kind(), crate::types::ErrorKind::Transient | crate::types::ErrorKind::NotFoundYet) => {
                     tracing::warn!(
                         target: crate::LAKE_FRAMEWORK,
-                        "Failed to get block heights from bucket {}: {}. Retrying in 1s...",
-                        s3_bucket_name,
-                        err,
+                        provider = block_provider.name(),
+                        block_height = start_from_block_height,
+                        error = %err,
+                        "Failed to get block heights. Retrying in 1s...",
                     );
                     tokio::time::sleep(std::time::Duration::from_secs(1)).await;
                 }
+                Err(err) => {
+                    yield Err(err);
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// The block height to stream, along with its block if it has been fetched by the stream of the block heights already
+type PendingBlock = (
+    crate::types::BlockHeight,
+    Option<near_indexer_primitives::views::BlockView>,
+);
+
+/// How many block heights ahead we probe to tell a skipped block from the one not written yet
+/// when streaming without S3 `List` requests.
+const NO_LIST_SKIP_LOOKAHEAD: u64 = 10;
+
+/// How many times in a row the block has to be missing before we probe the block heights ahead of it.
+/// Usually the missing block is just not written yet, so the first miss is not worth the probes
+const NO_LIST_SKIP_PROBE_AFTER_MISSES: u64 = 2;
+
+/// The longest interval (in the misses of the same block) between the probes of the block heights ahead,
+/// the probes are backed off exponentially up to it
+const NO_LIST_MAX_MISSES_BETWEEN_PROBES: u64 = 64;
+
+/// Alternative to [stream_block_heights] that doesn't issue S3 `List` requests.
+/// It attempts to get `{block_height}/block.json` for consecutive block heights instead,
+/// and yields the fetched block along with its height, so the block is not fetched again.
+///
+/// If the block is missing for [NO_LIST_SKIP_PROBE_AFTER_MISSES] polls we probe the next [NO_LIST_SKIP_LOOKAHEAD] block heights
+/// with the `HeadObject` requests (on the 2nd, 4th, 8th and so on miss, but at least every [NO_LIST_MAX_MISSES_BETWEEN_PROBES]th one),
+/// and use the `prev_height` of the first found block to figure out whether the missing block was skipped
+/// by the network or is not written to the bucket yet. If none of them exists, a single `List` request
+/// finds the next block after the longer gap.
+fn stream_block_heights_without_list<'a: 'b, 'b>(
+    lake_s3_client: &'a dyn s3_fetchers::S3Client,
+    s3_bucket_name: &'a str,
+    key_layout: &'a dyn s3_fetchers::KeyLayout,
+    mut start_from_block_height: crate::types::BlockHeight,
+    poll_interval: std::time::Duration,
+    control: &'a crate::ControlHandle,
+) -> impl futures::Stream<Item = Result<PendingBlock, crate::types::LakeError>> + 'b {
+    async_stream::stream! {
+        // how many times the block at `start_from_block_height` has been missing
+        let mut misses: u64 = 0;
+        // once a block has been read the access is granted, so `403 AccessDenied` means the block is missing
+        let mut fetched_any = false;
+        'heights: loop {
+            match s3_fetchers::fetch_block_view_if_exists(
+                lake_s3_client,
+                s3_bucket_name,
+                key_layout,
+                start_from_block_height,
+                if fetched_any { 0 } else { misses },
+            )
+            .await {
+                Ok(Some(block_view)) => {
+                    tracing::debug!(target: crate::LAKE_FRAMEWORK, provider = s3_fetchers::PROVIDER, block_height = start_from_block_height, "Yielding block height...");
+                    yield Ok((start_from_block_height, Some(block_view)));
+                    start_from_block_height += 1;
+                    misses = 0;
+                    fetched_any = true;
+                    continue 'heights;
+                }
+                Ok(None) => misses += 1,
+                Err(err) => {
+                    yield Err(err);
+                    return;
+                }
+            }
+
+            if misses >= NO_LIST_SKIP_PROBE_AFTER_MISSES
+                && (misses.is_power_of_two() || misses % NO_LIST_MAX_MISSES_BETWEEN_PROBES == 0)
+            {
+                let mut probed_block = ProbedBlock::Missing;
+                for next_block_height in (start_from_block_height + 1)..=(start_from_block_height + NO_LIST_SKIP_LOOKAHEAD) {
+                    let exists = s3_fetchers::object_exists(
+                        lake_s3_client,
+                        s3_bucket_name,
+                        &key_layout.block_key(next_block_height),
+                    )
+                    .await;
+                    if exists == Some(false) {
+                        continue;
+                    }
+                    probed_block = probe_block_above(lake_s3_client, s3_bucket_name, key_layout, start_from_block_height, next_block_height).await;
+                    if !matches!(probed_block, ProbedBlock::Missing) {
+                        break;
+                    }
+                }
+                if matches!(probed_block, ProbedBlock::Missing) {
+                    // the gap is longer than the lookahead (or the next blocks are not written yet either)
+                    match s3_fetchers::list_block_heights(
+                        lake_s3_client,
+                        s3_bucket_name,
+                        key_layout,
+                        start_from_block_height + NO_LIST_SKIP_LOOKAHEAD + 1,
+                        1,
+                    )
+                    .await {
+                        Ok(block_heights) => {
+                            if let Some(next_block_height) = block_heights.first() {
+                                probed_block = probe_block_above(lake_s3_client, s3_bucket_name, key_layout, start_from_block_height, *next_block_height).await;
+                            }
+                        }
+                        Err(err) => {
+                            tracing::debug!(
+                                target: crate::LAKE_FRAMEWORK,
+                                provider = s3_fetchers::PROVIDER,
+                                block_height = start_from_block_height,
+                                bucket = s3_bucket_name,
+                                error = %err,
+                                "Failed to list the block heights after the lookahead",
+                            );
+                        }
+                    }
+                }
+                if let ProbedBlock::AfterSkipped(block_view) = probed_block {
+                    let next_block_height = block_view.header.height;
+                    tracing::debug!(
+                        target: crate::LAKE_FRAMEWORK,
+                        provider = s3_fetchers::PROVIDER,
+                        block_height = next_block_height,
+                        skipped_from = start_from_block_height,
+                        "Block heights are skipped. Yielding block height...",
+                    );
+                    yield Ok((next_block_height, Some(*block_view)));
+                    start_from_block_height = next_block_height + 1;
+                    misses = 0;
+                    fetched_any = true;
+                    continue 'heights;
+                }
+            }
+
+            control.record_caught_up(start_from_block_height.saturating_sub(1));
+            tracing::debug!(
+                target: crate::LAKE_FRAMEWORK,
+                provider = s3_fetchers::PROVIDER,
+                block_height = start_from_block_height,
+                bucket = s3_bucket_name,
+                "The block is not available in the bucket yet. Fetching again in {:?}...",
+                poll_interval,
+            );
+            tokio::time::sleep(poll_interval).await;
+        }
+    }
+}
+
+/// The block above the missing one probed by [stream_block_heights_without_list]
+enum ProbedBlock {
+    /// The block doesn't exist (yet)
+    Missing,
+    /// The missing block heights are skipped by the network, the block follows them
+    AfterSkipped(Box<near_indexer_primitives::views::BlockView>),
+    /// The missing block exists but is not written to the bucket yet (or the block has failed to be fetched)
+    AfterNotWritten,
+}
+
+/// Gets the block at `block_height` and tells by its `prev_height` whether the `missing_block_height` is skipped
+async fn probe_block_above(
+    lake_s3_client: &dyn s3_fetchers::S3Client,
+    s3_bucket_name: &str,
+    key_layout: &dyn s3_fetchers::KeyLayout,
+    missing_block_height: crate::types::BlockHeight,
+    block_height: crate::types::BlockHeight,
+) -> ProbedBlock {
+    match s3_fetchers::fetch_block_view_if_exists(
+        lake_s3_client,
+        s3_bucket_name,
+        key_layout,
+        block_height,
+        0,
+    )
+    .await
+    {
+        Ok(Some(block_view))
+            if block_view
+                .header
+                .prev_height
+                .map_or(false, |prev_height| prev_height < missing_block_height) =>
+        {
+            ProbedBlock::AfterSkipped(Box::new(block_view))
+        }
+        Ok(Some(_)) => ProbedBlock::AfterNotWritten,
+        Ok(None) => ProbedBlock::Missing,
+        Err(err) => {
+            tracing::warn!(
+                target: crate::LAKE_FRAMEWORK,
+                provider = s3_fetchers::PROVIDER,
+                block_height,
+                bucket = s3_bucket_name,
+                error = %err,
+                "Failed to get the block from the bucket",
+            );
+            ProbedBlock::AfterNotWritten
+        }
+    }
+}
+
+/// Ends the stream of the block heights right after the end block height (or before the first one above it, if it is skipped),
+/// so the stream doesn't wait for the next block height to be listed once the end one is the tip
+fn until_end_block_height<T>(
+    pending_block_heights: impl futures::Stream<
+        Item = Result<(crate::types::BlockHeight, T), crate::types::LakeError>,
+    >,
+    end_block_height: Option<crate::types::BlockHeight>,
+) -> impl futures::Stream<Item = Result<(crate::types::BlockHeight, T), crate::types::LakeError>> {
+    async_stream::stream! {
+        tokio::pin!(pending_block_heights);
+        while let Some(pending_block) = pending_block_heights.next().await {
+            match (pending_block, end_block_height) {
+                (Ok((block_height, _)), Some(end_block_height)) if block_height > end_block_height => break,
+                (Ok((block_height, block)), Some(end_block_height)) if block_height == end_block_height => {
+                    yield Ok((block_height, block));
+                    break;
+                }
+                (pending_block, _) => yield pending_block,
             }
         }
     }
 }
 
 // The only consumer of the BlockHeights Streamer
-async fn prefetch_block_heights_into_pool(
+async fn prefetch_block_heights_into_pool<T>(
     pending_block_heights: &mut std::pin::Pin<
-        &mut impl tokio_stream::Stream<Item = crate::types::BlockHeight>,
+        &mut impl tokio_stream::Stream<Item = Result<T, crate::types::LakeError>>,
     >,
     limit: usize,
     await_for_at_least_one: bool,
-) -> Result<Vec<crate::types::BlockHeight>, crate::types::LakeError> {
+) -> Result<Vec<T>, crate::types::LakeError> {
     let mut block_heights = Vec::with_capacity(limit);
     for remaining_limit in (0..limit).rev() {
         tracing::debug!(target: crate::LAKE_FRAMEWORK, "Polling for the next block height without awaiting... (up to {} block heights are going to be fetched)", remaining_limit);
         match futures::poll!(pending_block_heights.next()) {
             std::task::Poll::Ready(Some(block_height)) => {
-                block_heights.push(block_height);
+                block_heights.push(block_height?);
             }
             std::task::Poll::Pending => {
                 if await_for_at_least_one && block_heights.is_empty() {
                     tracing::debug!(target: crate::LAKE_FRAMEWORK, "There were no block heights available immediatelly, and the prefetching blocks queue is empty, so we need to await for at least a single block height to be available before proceeding...");
                     match pending_block_heights.next().await {
                         Some(block_height) => {
-                            block_heights.push(block_height);
+                            block_heights.push(block_height?);
                         }
                         None => {
-                            return Err(crate::types::LakeError::InternalError {
-                                error_message: "This state should be unreachable as the block heights stream should be infinite.".to_string()
-                            });
+                            tracing::debug!(target: crate::LAKE_FRAMEWORK, "The block heights stream has reached the end block height");
+                            break;
                         }
                     }
                     continue;
@@ -107,38 +324,304 @@ async fn prefetch_block_heights_into_pool(
                 break;
             }
             std::task::Poll::Ready(None) => {
-                return Err(
-                    crate::types::LakeError::InternalError {
-                        error_message: "This state should be unreachable as the block heights stream should be infinite.".to_string()
+                // The block heights stream is finite only when the `end_block_height` is set
+                tracing::debug!(target: crate::LAKE_FRAMEWORK, "The block heights stream has reached the end block height");
+                break;
+            }
+        }
+    }
+    Ok(block_heights)
+}
+
+/// The outcome of merging a single batch of the backfilled blocks
+enum BackfillBatch {
+    /// All the blocks of the batch have been sent to the channel
+    Completed,
+    /// Received a block which `prev_hash` doesn't match the previously streamed block
+    Interrupted,
+    /// The channel is closed, the streamer should stop
+    ChannelClosed,
+}
+
+/// Streams the historical blocks using [crate::Lake::backfill_workers] parallel workers until the stream catches up with the tip
+/// of the network. Every batch of block heights from S3 `List` is partitioned across the workers
+/// (the worker N gets every N-th block height), the results are merged back in order.
+///
+/// Returns the block height the regular streaming should continue from,
+/// or `None` if the channel is closed and the streamer should stop.
+async fn backfill(
+    streamer_message_sink: &crate::channel::StreamerMessageSender,
+    lake_s3_client: &std::sync::Arc<dyn s3_fetchers::S3Client>,
+    config: &crate::Lake,
+    mut start_from_block_height: types::BlockHeight,
+    last_processed_block_hash: &mut Option<near_indexer_primitives::CryptoHash>,
+    parser: &crate::parser::ParserPool,
+) -> Result<Option<types::BlockHeight>, crate::types::LakeError> {
+    let s3_bucket_name = config.s3_bucket_name.as_str();
+    let key_layout = config.key_layout();
+    let workers = config.backfill_workers;
+    let workers_pool_size = std::cmp::max(config.blocks_preload_pool_size / workers, 1);
+
+    loop {
+        let mut block_heights = s3_fetchers::list_block_heights_concurrently(
+            &**lake_s3_client,
+            s3_bucket_name,
+            &*key_layout,
+            start_from_block_height,
+            config.list_max_pages,
+            config.list_concurrency,
+        )
+        .await?;
+        if let Some(end_block_height) = config.end_block_height {
+            block_heights.retain(|block_height| *block_height <= end_block_height);
+        }
+
+        if block_heights.is_empty() {
+            tracing::debug!(
+                target: crate::LAKE_FRAMEWORK,
+                provider = s3_fetchers::PROVIDER,
+                block_height = start_from_block_height,
+                "Backfill has reached the tip of the network (or the end block height), switching to the regular streaming",
+            );
+            return Ok(Some(start_from_block_height));
+        }
+
+        tracing::debug!(
+            target: crate::LAKE_FRAMEWORK,
+            "Backfilling {} block heights with {} workers...",
+            block_heights.len(),
+            workers,
+        );
+
+        let mut handles = Vec::with_capacity(workers);
+        let mut receivers = Vec::with_capacity(workers);
+        for worker_id in 0..workers {
+            let (sender, receiver) = mpsc::channel(workers_pool_size);
+            let worker_block_heights: Vec<types::BlockHeight> = block_heights
+                .iter()
+                .skip(worker_id)
+                .step_by(workers)
+                .copied()
+                .collect();
+            let lake_s3_client = lake_s3_client.clone();
+            let s3_bucket_name = s3_bucket_name.to_string();
+            let key_layout = key_layout.clone();
+            let track_shards = config.track_shards.clone();
+            let parser = parser.clone();
+            handles.push(tokio::spawn(async move {
+                for block_height in worker_block_heights {
+                    let streamer_message_result = s3_fetchers::fetch_streamer_message(
+                        &*lake_s3_client,
+                        &s3_bucket_name,
+                        &*key_layout,
+                        block_height,
+                        track_shards.as_deref(),
+                        &parser,
+                    )
+                    .await;
+                    if sender.send(streamer_message_result).await.is_err() {
+                        // the merging side has stopped, no need to fetch further
+                        break;
+                    }
+                }
+            }));
+            receivers.push(receiver);
+        }
+
+        let merge_result: Result<BackfillBatch, crate::types::LakeError> = async {
+            for index in 0..block_heights.len() {
+                let streamer_message = receivers[index % workers]
+                    .recv()
+                    .await
+                    .ok_or_else(|| crate::types::LakeError::InternalError {
+                        error_message: "Backfill worker has stopped unexpectedly".to_string(),
+                    })??;
+
+                if let Some(prev_block_hash) = last_processed_block_hash {
+                    if *prev_block_hash != streamer_message.block.header.prev_hash
+                        && !config.allow_gaps
+                    {
+                        tracing::warn!(
+                            target: crate::LAKE_FRAMEWORK,
+                            provider = s3_fetchers::PROVIDER,
+                            block_height = start_from_block_height,
+                            "`prev_hash` does not match during the backfill, switching to the regular streaming",
+                        );
+                        return Ok(BackfillBatch::Interrupted);
                     }
+                }
+
+                *last_processed_block_hash = Some(streamer_message.block.header.hash);
+                start_from_block_height = streamer_message.block.header.height + 1;
+
+                if !streamer_message_sink.send(streamer_message).await {
+                    tracing::debug!(
+                        target: crate::LAKE_FRAMEWORK,
+                        provider = s3_fetchers::PROVIDER,
+                        block_height = start_from_block_height - 1,
+                        "Failed to send StreamerMessage to the channel. Channel is closed, exiting",
+                    );
+                    return Ok(BackfillBatch::ChannelClosed);
+                }
+                config.control.record_streamed(start_from_block_height - 1);
+            }
+            Ok(BackfillBatch::Completed)
+        }
+        .await;
+
+        // stop the workers that might still be running if the merging has been interrupted
+        for handle in handles {
+            handle.abort();
+        }
+
+        match merge_result {
+            Ok(BackfillBatch::Completed) => continue,
+            Ok(BackfillBatch::Interrupted) => return Ok(Some(start_from_block_height)),
+            Ok(BackfillBatch::ChannelClosed) => return Ok(None),
+            Err(err) => {
+                tracing::error!(
+                    target: crate::LAKE_FRAMEWORK,
+                    provider = s3_fetchers::PROVIDER,
+                    block_height = err.block_height(),
+                    shard_id = err.shard_id(),
+                    error = %err,
+                    "Failed to backfill StreamerMessage",
                 );
+                return Err(err);
             }
         }
     }
-    Ok(block_heights)
+}
+
+/// Creates the [s3_fetchers::S3Client] the blocks are fetched with: the custom one or the built-in client
+/// configured by the [crate::Lake], wrapped into the disk cache if [crate::Lake::cache_dir] is set
+pub(crate) async fn lake_s3_client(
+    config: &crate::Lake,
+) -> std::sync::Arc<dyn s3_fetchers::S3Client> {
+    let lake_s3_client: std::sync::Arc<dyn s3_fetchers::S3Client> =
+        if let Some(s3_client) = config.s3_client.clone() {
+            s3_client
+        } else {
+            let s3_config = if let Some(config) = config.s3_config.clone() {
+                config
+            } else {
+                let aws_config = aws_config::from_env().load().await;
+                aws_sdk_s3::config::Builder::from(&aws_config)
+                    .region(aws_types::region::Region::new(
+                        config.s3_region_name.clone(),
+                    ))
+                    .build()
+            };
+            let mut s3_config_builder = s3_config.to_builder();
+            if let Some(cache_endpoint) = &config.cache_endpoint {
+                // The cache serves the bucket under the path rather than the virtual host
+                s3_config_builder.set_endpoint_url(Some(cache_endpoint.clone()));
+                s3_config_builder.set_force_path_style(Some(true));
+            }
+            if config.anonymous || config.cache_endpoint.is_some() {
+                // Dropping the credentials provider makes the SDK send the requests unsigned
+                s3_config_builder.set_credentials_provider(None);
+            }
+            std::sync::Arc::new(
+                s3_fetchers::LakeS3Client::new(
+                    Client::from_conf(s3_config_builder.build()),
+                    config.s3_requests_counter.clone(),
+                    config.requester_pays && config.cache_endpoint.is_none(),
+                )
+                .with_etag_cache(config.etag_cache_size),
+            )
+        };
+    match &config.cache_dir {
+        Some(cache_dir) => std::sync::Arc::new(s3_fetchers::DiskCacheS3Client::new(
+            lake_s3_client,
+            cache_dir,
+        )),
+        None => lake_s3_client,
+    }
 }
 
 #[allow(unused_labels)] // we use loop labels for code-readability
 pub(crate) async fn start(
-    streamer_message_sink: mpsc::Sender<near_indexer_primitives::StreamerMessage>,
+    streamer_message_sink: crate::channel::StreamerMessageSender,
     config: crate::Lake,
 ) -> Result<(), crate::types::LakeError> {
     let mut start_from_block_height = config.start_block_height;
 
-    let s3_client = if let Some(config) = config.s3_config {
-        Client::from_conf(config)
+    let parser = crate::parser::ParserPool::new(config.parser_threads);
+    // the backfill and the streaming without `List` requests are specific to the bucket
+    let (block_provider, lake_s3_client): (std::sync::Arc<dyn BlockProvider>, _) =
+        match config.block_provider.clone() {
+            Some(block_provider) => (block_provider, None),
+            None => {
+                let lake_s3_client = lake_s3_client(&config).await;
+                (
+                    std::sync::Arc::new(S3BlockProvider::new(
+                        lake_s3_client.clone(),
+                        &config,
+                        parser.clone(),
+                    )),
+                    Some(lake_s3_client),
+                )
+            }
+        };
+    // the bucket blocks are served by the switching provider below the first switch height
+    let (block_provider, lake_s3_client) = if config.provider_switches.is_empty() {
+        (block_provider, lake_s3_client)
     } else {
-        let aws_config = aws_config::from_env().load().await;
-        let s3_config = aws_sdk_s3::config::Builder::from(&aws_config)
-            .region(aws_types::region::Region::new(config.s3_region_name))
-            .build();
-        Client::from_conf(s3_config)
+        (
+            crate::provider::with_switches(block_provider, &config),
+            None,
+        )
     };
-    let lake_s3_client = s3_fetchers::LakeS3Client::new(s3_client.clone());
+    let key_layout = config.key_layout();
+    let provider = block_provider.name();
+    // the blocks fetched by the stream of the block heights already (without `List` requests) miss the shards only
+    let fetch_streamer_message =
+        |(block_height, block_view): PendingBlock| match (block_view, lake_s3_client.as_ref()) {
+            (Some(block_view), Some(lake_s3_client)) => {
+                s3_fetchers::fetch_streamer_message_with_block_view(
+                    &**lake_s3_client,
+                    &config.s3_bucket_name,
+                    &*key_layout,
+                    block_view,
+                    config.track_shards.as_deref(),
+                    &parser,
+                )
+                .boxed()
+            }
+            _ => block_provider.fetch_streamer_message(block_height),
+        };
 
     let mut last_processed_block_hash: Option<near_indexer_primitives::CryptoHash> = None;
 
+    if let Some(lake_s3_client) = lake_s3_client
+        .as_ref()
+        .filter(|_| config.backfill_workers > 1 && !config.no_list)
+    {
+        match backfill(
+            &streamer_message_sink,
+            lake_s3_client,
+            &config,
+            start_from_block_height,
+            &mut last_processed_block_hash,
+            &parser,
+        )
+        .await?
+        {
+            Some(block_height) => start_from_block_height = block_height,
+            None => return Ok(()),
+        }
+    }
+
     'main: loop {
+        if matches!(config.end_block_height, Some(end_block_height) if start_from_block_height > end_block_height)
+        {
+            tracing::debug!(
+                target: crate::LAKE_FRAMEWORK,
+                "The end block height has been streamed, exiting",
+            );
+            return Ok(());
+        }
         // In the beginning of the 'main' loop we create a Block Heights stream
         // and prefetch the initial data in that pool.
         // Later the 'stream' loop might exit to this 'main' one to repeat the procedure.
@@ -146,11 +629,27 @@ pub(crate) async fn start(
         // in some cases, write N+1 block before it finishes writing the N block.
         // We require to stream blocks consistently, so we need to try to load the block again.
 
-        let pending_block_heights = stream_block_heights(
-            &lake_s3_client,
-            &config.s3_bucket_name,
-            start_from_block_height,
-        );
+        let pending_block_heights = match lake_s3_client.as_ref().filter(|_| config.no_list) {
+            Some(lake_s3_client) => stream_block_heights_without_list(
+                &**lake_s3_client,
+                &config.s3_bucket_name,
+                &*key_layout,
+                start_from_block_height,
+                config.poll_interval,
+                &config.control,
+            )
+            .left_stream(),
+            None => stream_block_heights(
+                &*block_provider,
+                start_from_block_height,
+                config.poll_interval,
+                &config.control,
+            )
+            .map(|block_height| block_height.map(|block_height| (block_height, None)))
+            .right_stream(),
+        };
+        let pending_block_heights =
+            until_end_block_height(pending_block_heights, config.end_block_height);
         tokio::pin!(pending_block_heights);
 
         let mut streamer_messages_futures = futures::stream::FuturesOrdered::new();
@@ -160,21 +659,25 @@ pub(crate) async fn start(
             config.blocks_preload_pool_size
         );
 
+        let initial_block_heights = prefetch_block_heights_into_pool(
+            &mut pending_block_heights,
+            config.blocks_preload_pool_size,
+            true,
+        )
+        .await?;
+
+        if initial_block_heights.is_empty() {
+            tracing::debug!(
+                target: crate::LAKE_FRAMEWORK,
+                "There are no more blocks to stream up to the end block height, exiting",
+            );
+            return Ok(());
+        }
+
         streamer_messages_futures.extend(
-            prefetch_block_heights_into_pool(
-                &mut pending_block_heights,
-                config.blocks_preload_pool_size,
-                true,
-            )
-            .await?
-            .into_iter()
-            .map(|block_height| {
-                s3_fetchers::fetch_streamer_message(
-                    &lake_s3_client,
-                    &config.s3_bucket_name,
-                    block_height,
-                )
-            }),
+            initial_block_heights
+                .into_iter()
+                .map(fetch_streamer_message),
         );
 
         tracing::debug!(
@@ -185,29 +688,37 @@ pub(crate) async fn start(
             let streamer_message = streamer_message_result.map_err(|err| {
                 tracing::error!(
                     target: crate::LAKE_FRAMEWORK,
-                    "Failed to fetch StreamerMessage with error: \n{:#?}",
-                    err,
+                    provider,
+                    block_height = err.block_height(),
+                    shard_id = err.shard_id(),
+                    error = %err,
+                    "Failed to fetch StreamerMessage",
                 );
                 err
             })?;
 
             tracing::debug!(
                 target: crate::LAKE_FRAMEWORK,
-                "Received block #{} ({})",
-                streamer_message.block.header.height,
-                streamer_message.block.header.hash
+                provider,
+                block_height = streamer_message.block.header.height,
+                block_hash = %streamer_message.block.header.hash,
+                "Received block",
             );
             // check if we have `last_processed_block_hash` (might be None only on start)
             if let Some(prev_block_hash) = last_processed_block_hash {
                 // compare last_processed_block_hash` with `block.header.prev_hash` of the current
                 // block (ensure we don't miss anything from S3)
                 // retrieve the data from S3 if prev_hashes don't match and repeat the main loop step
-                if prev_block_hash != streamer_message.block.header.prev_hash {
+                if prev_block_hash != streamer_message.block.header.prev_hash && !config.allow_gaps
+                {
                     tracing::warn!(
                         target: crate::LAKE_FRAMEWORK,
-                        "`prev_hash` does not match, refetching the data from S3 in 200ms",
+                        provider,
+                        block_height = streamer_message.block.header.height,
+                        "`prev_hash` does not match, refetching the data in {:?}",
+                        config.prev_hash_mismatch_delay,
                     );
-                    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+                    tokio::time::sleep(config.prev_hash_mismatch_delay).await;
                     break 'stream;
                 }
             }
@@ -224,9 +735,10 @@ pub(crate) async fn start(
             );
             tracing::debug!(
                 target: crate::LAKE_FRAMEWORK,
-                "Streaming block #{} ({})",
-                streamer_message.block.header.height,
-                streamer_message.block.header.hash
+                provider,
+                block_height = streamer_message.block.header.height,
+                block_hash = %streamer_message.block.header.hash,
+                "Streaming block",
             );
             let blocks_preload_pool_current_len = streamer_messages_futures.len();
 
@@ -240,23 +752,21 @@ pub(crate) async fn start(
 
             let streamer_message_sink_send_future = streamer_message_sink.send(streamer_message);
 
-            let (prefetch_res, send_res): (
-                Result<Vec<types::BlockHeight>, crate::types::LakeError>,
-                Result<_, SendError<near_indexer_primitives::StreamerMessage>>,
-            ) = futures::join!(
+            let (prefetch_res, sent): (Result<Vec<PendingBlock>, crate::types::LakeError>, bool) = futures::join!(
                 prefetched_block_heights_future,
                 streamer_message_sink_send_future,
             );
 
-            if let Err(SendError(err)) = send_res {
+            if !sent {
                 tracing::debug!(
                     target: crate::LAKE_FRAMEWORK,
-                    "Failed to send StreamerMessage (#{:0>12}) to the channel. Channel is closed, exiting \n{:?}",
-                    start_from_block_height - 1,
-                    err,
+                    provider,
+                    block_height = start_from_block_height - 1,
+                    "Failed to send StreamerMessage to the channel. Channel is closed, exiting",
                 );
                 return Ok(());
             }
+            config.control.record_streamed(start_from_block_height - 1);
 
             streamer_messages_futures.extend(
                 prefetch_res
@@ -269,23 +779,130 @@ pub(crate) async fn start(
                         err
                     })?
                     .into_iter()
-                    .map(|block_height| {
-                        s3_fetchers::fetch_streamer_message(
-                            &lake_s3_client,
-                            &config.s3_bucket_name,
-                            block_height,
-                        )
-                    }
-            ));
+                    .map(fetch_streamer_message),
+            );
         }
 
+        if matches!(config.end_block_height, Some(end_block_height) if start_from_block_height > end_block_height)
+        {
+            continue 'main;
+        }
         tracing::warn!(
             target: crate::LAKE_FRAMEWORK,
+            provider,
+            block_height = start_from_block_height,
             "Exited from the 'stream' loop. It may happen in two cases:\n
             1. Blocks has ended (impossible, might be an error on the Lake Buckets),\n
             2. Received a Block which prev_hash doesn't match the previously streamed block.\n
-            Will attempt to restart the stream from block #{}",
-            start_from_block_height,
+            Will attempt to restart the stream",
         );
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::s3_fetchers::KeyLayout;
+
+    use async_trait::async_trait;
+    use aws_sdk_s3::error::SdkError;
+    use aws_sdk_s3::operation::get_object::{GetObjectError, GetObjectOutput};
+    use aws_sdk_s3::operation::list_objects_v2::{ListObjectsV2Error, ListObjectsV2Output};
+
+    /// Serves the `block.json` objects of the given block heights made out of the fixture block,
+    /// the blocks in between are skipped by the network
+    #[derive(Debug)]
+    struct SkippingS3Client {
+        block_heights: Vec<types::BlockHeight>,
+    }
+
+    #[async_trait]
+    impl s3_fetchers::S3Client for SkippingS3Client {
+        async fn get_object(
+            &self,
+            _bucket: &str,
+            prefix: &str,
+        ) -> Result<GetObjectOutput, SdkError<GetObjectError>> {
+            let block_height = s3_fetchers::DefaultKeyLayout
+                .parse_block_height(prefix)
+                .filter(|block_height| self.block_heights.contains(block_height));
+            let Some(block_height) = block_height else {
+                return Err(SdkError::service_error(
+                    GetObjectError::NoSuchKey(
+                        aws_sdk_s3::types::error::NoSuchKey::builder().build(),
+                    ),
+                    aws_sdk_s3::config::http::HttpResponse::new(
+                        404u16.try_into().unwrap(),
+                        aws_smithy_types::body::SdkBody::empty(),
+                    ),
+                ));
+            };
+            let prev_height = self
+                .block_heights
+                .iter()
+                .rev()
+                .find(|prev_height| **prev_height < block_height);
+            let mut block: serde_json::Value =
+                serde_json::from_str(include_str!("../blocks/000000879765/block.json")).unwrap();
+            block["header"]["height"] = block_height.into();
+            block["header"]["prev_height"] = serde_json::to_value(prev_height).unwrap();
+            Ok(GetObjectOutput::builder()
+                .body(aws_sdk_s3::primitives::ByteStream::from(
+                    block.to_string().into_bytes(),
+                ))
+                .build())
+        }
+
+        async fn list_objects(
+            &self,
+            _bucket: &str,
+            start_after: &str,
+            _continuation_token: Option<String>,
+        ) -> Result<ListObjectsV2Output, SdkError<ListObjectsV2Error>> {
+            let common_prefixes = self
+                .block_heights
+                .iter()
+                .map(|block_height| format!("{:0>12}/", block_height))
+                .filter(|prefix| prefix.as_str() > start_after)
+                .map(|prefix| {
+                    aws_sdk_s3::types::CommonPrefix::builder()
+                        .prefix(prefix)
+                        .build()
+                })
+                .collect();
+            Ok(ListObjectsV2Output::builder()
+                .set_common_prefixes(Some(common_prefixes))
+                .build())
+        }
+    }
+
+    #[tokio::test]
+    async fn streams_without_list_over_gap_longer_than_lookahead() {
+        let lake_s3_client = SkippingS3Client {
+            block_heights: vec![
+                100,
+                101,
+                102 + NO_LIST_SKIP_LOOKAHEAD * 3,
+                103 + NO_LIST_SKIP_LOOKAHEAD * 3,
+            ],
+        };
+        let control = crate::ControlHandle::default();
+
+        let block_heights = stream_block_heights_without_list(
+            &lake_s3_client,
+            "near-lake-data-mainnet",
+            &s3_fetchers::DefaultKeyLayout,
+            100,
+            std::time::Duration::from_millis(1),
+            &control,
+        )
+        .map(|pending_block| pending_block.unwrap().0)
+        .take(4)
+        .collect::<Vec<_>>();
+        let block_heights = tokio::time::timeout(std::time::Duration::from_secs(10), block_heights)
+            .await
+            .expect("The stream has stalled at the gap");
+
+        assert_eq!(block_heights, lake_s3_client.block_heights);
+    }
+}
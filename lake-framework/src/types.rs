@@ -15,8 +15,8 @@ pub type BlockHeight = u64;
 ///        .expect("Failed to build Lake");
 /// # }
 /// ```
-#[derive(Default, Builder, Debug)]
-#[builder(pattern = "owned")]
+#[derive(Default, Builder, Debug, Clone)]
+#[builder(pattern = "owned", build_fn(validate = "Self::validate"))]
 pub struct Lake {
     /// AWS S3 Bucket name
     #[builder(setter(into))]
@@ -26,6 +26,12 @@ pub struct Lake {
     pub(crate) s3_region_name: String,
     /// Defines the block height to start indexing from
     pub(crate) start_block_height: u64,
+    /// Defines the block height to stop indexing at (inclusive). Default: None (stream forever)
+    ///
+    /// When set, the streamer stops and [Lake::run](crate::Lake::run) returns once all the blocks up to this height are streamed.
+    /// It is useful for the backfill jobs that need to process a specific range of blocks.
+    #[builder(setter(strip_option), default)]
+    pub(crate) end_block_height: Option<u64>,
     /// Custom aws_sdk_s3::config::Config
     /// ## Use-case: custom endpoint
     /// You might want to stream data from the custom S3-compatible source () . In order to do that you'd need to pass `aws_sdk_s3::config::Config` configured
@@ -50,6 +56,24 @@ pub struct Lake {
     /// ```
     #[builder(setter(strip_option), default)]
     pub(crate) s3_config: Option<aws_sdk_s3::config::Config>,
+    /// Custom [S3Client](crate::S3Client) to fetch the objects with, e.g. a caching client or a mock for tests.
+    /// Mutually exclusive with [LakeBuilder::s3_config]
+    ///
+    /// *Note*: [LakeBuilder::anonymous], [LakeBuilder::requester_pays], [LakeBuilder::cache_endpoint], [LakeBuilder::etag_cache_size]
+    /// and [LakeBuilder::s3_requests_counter] configure the built-in client only and are ignored for the custom one
+    #[builder(setter(custom), default)]
+    pub(crate) s3_client: Option<std::sync::Arc<dyn crate::S3Client>>,
+    /// Custom [BlockProvider](crate::BlockProvider) to stream the blocks from instead of the bucket,
+    /// e.g. an internal archive, a database or a proxy. Default: None (the NEAR Lake bucket)
+    ///
+    /// *Note*: The settings of the bucket and the S3 requests (including [LakeBuilder::backfill_workers]
+    /// and [LakeBuilder::no_list]) are ignored for the custom provider
+    #[builder(setter(custom), default)]
+    pub(crate) block_provider: Option<std::sync::Arc<dyn crate::BlockProvider>>,
+    /// The block heights to switch the stream over to the other [BlockProvider](crate::BlockProvider)s at.
+    /// Set up with [LakeBuilder::switch_provider_at]
+    #[builder(setter(custom), default)]
+    pub(crate) provider_switches: Vec<(BlockHeight, std::sync::Arc<dyn crate::BlockProvider>)>,
     /// Defines how many *block heights* Lake Framework will try to preload into memory to avoid S3 `List` requests.
     /// Default: 100
     ///
@@ -58,13 +82,599 @@ pub struct Lake {
     /// This parameter is useful for historical indexing.
     #[builder(default = "100")]
     pub(crate) blocks_preload_pool_size: usize,
+    /// The type of the channel the blocks are delivered to the handlers through, see [ChannelStrategy](crate::ChannelStrategy).
+    /// Default: the bounded channel with the capacity of `blocks_preload_pool_size`
+    #[builder(setter(strip_option), default)]
+    pub(crate) channel_strategy: Option<crate::ChannelStrategy>,
     /// Number of concurrent blocks to process. Default: 1
     /// **WARNING**: Increase this value only if your block handling logic doesn't have to rely on previous blocks and can be processed in parallel
     #[builder(default = "1")]
     pub(crate) concurrency: usize,
+    /// Number of parallel workers to fetch the historical blocks with. Default: 1 (parallel backfill is disabled)
+    ///
+    /// When set to a value greater than 1, Lake Framework partitions every batch of block heights returned by S3 `List`
+    /// across N workers, each running on its own task, and merges their results back in order.
+    /// Once the stream catches up with the tip of the network, it switches back to the regular streaming.
+    ///
+    /// *Note*: This parameter is useful for historical indexing only.
+    #[builder(default = "1")]
+    pub(crate) backfill_workers: usize,
+    /// Stream the blocks without S3 `List` requests. Default: false
+    ///
+    /// In this mode Lake Framework attempts to get `{block_height}/block.json` for the consecutive block heights
+    /// instead of listing the bucket, and detects the skipped block heights by the `prev_height` of the next available block
+    /// (the next block heights are probed with the `HeadObject` requests once the block has been missing for a couple of polls,
+    /// and a single `List` request finds the next block if the gap is longer). Every `block.json` is fetched once.
+    /// `List` requests are the most expensive part of following the tip of the network (see the cost estimates in the README),
+    /// this mode replaces them with the cheaper `Get` and `Head` requests.
+    ///
+    /// *Note*: [LakeBuilder::backfill_workers] relies on S3 `List` requests, so it is ignored in this mode.
+    #[builder(default = "false")]
+    pub(crate) no_list: bool,
+    /// Skips the `prev_hash` continuity check of the streamed blocks, so the trimmed lakes with the blocks missing
+    /// on purpose (e.g. published with the [S3RepublishSink](crate::S3RepublishSink)) can be streamed.
+    ///
+    /// *Note*: The blocks missing in the bucket by accident are not detected in this mode
+    #[builder(default = "false")]
+    pub(crate) allow_gaps: bool,
+    /// Counter of the S3 requests performed by Lake Framework. See [S3RequestsCounter] for details
+    ///
+    /// By default, Lake Framework creates its own counter. Pass a clone of your counter here to be able
+    /// to read the numbers while the indexer is running.
+    #[builder(default)]
+    pub(crate) s3_requests_counter: S3RequestsCounter,
+    /// How long to wait before checking the bucket again when there are no new blocks available. Default: 2s
+    ///
+    /// Decrease this value to lower the latency of following the tip of the network,
+    /// increase it to perform less S3 requests.
+    #[builder(default = "std::time::Duration::from_secs(2)")]
+    pub(crate) poll_interval: std::time::Duration,
+    /// How long to wait before refetching the data from S3 when the received block's `prev_hash`
+    /// doesn't match the previously streamed block. Default: 200ms
+    #[builder(default = "std::time::Duration::from_millis(200)")]
+    pub(crate) prev_hash_mismatch_delay: std::time::Duration,
+    /// The list of shard ids to fetch the data for. Default: None (all the shards are fetched)
+    ///
+    /// When set, Lake Framework fetches only the `shard_N.json` objects of the given shards, reducing the number of S3 requests
+    /// and the bandwidth for the indexers that care about the specific shards only.
+    ///
+    /// *Note*: The [Block](near_lake_primitives::block::Block) will contain the data from the tracked shards only.
+    #[builder(setter(strip_option), default)]
+    pub(crate) track_shards: Option<Vec<u64>>,
+    /// Send the S3 requests unsigned (anonymous access). Default: false
+    ///
+    /// Some mirrors of the NEAR Lake buckets allow anonymous read-only access, in this case you don't need the AWS credentials at all.
+    /// Lake Framework drops the credentials provider from the S3 config (including the one passed with [LakeBuilder::s3_config]).
+    /// ```
+    /// use near_lake_framework::LakeBuilder;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    ///     let s3_config = aws_sdk_s3::Config::builder()
+    ///         .endpoint_url("https://near-lake-mirror.example.com")
+    ///         .region(aws_sdk_s3::config::Region::new("eu-central-1"))
+    ///         .build();
+    ///
+    ///     let lake = LakeBuilder::default()
+    ///         .s3_config(s3_config)
+    ///         .s3_bucket_name("near-lake-data-mainnet")
+    ///         .s3_region_name("eu-central-1")
+    ///         .anonymous(true)
+    ///         .start_block_height(1)
+    ///         .build()
+    ///         .expect("Failed to build Lake");
+    /// # }
+    /// ```
+    #[builder(default = "false")]
+    pub(crate) anonymous: bool,
+    /// Send the `x-amz-request-payer: requester` header with the S3 requests. Default: true
+    ///
+    /// The official NEAR Lake buckets are configured with "Requester Pays", so the header is required to read from them.
+    /// Disable it if you use a self-hosted bucket (or S3-compatible storage) that rejects the header.
+    #[builder(default = "true")]
+    pub(crate) requester_pays: bool,
+    /// Defines the URL of the read-through HTTP cache (e.g. CloudFront or nginx) in front of the bucket
+    /// to send the S3 requests to instead of the bucket itself. Default: None (the requests go to S3)
+    ///
+    /// The requests are sent unsigned (see [LakeBuilder::anonymous]) with the path-style addressing (`{cache_endpoint}/{bucket}/{key}`)
+    /// and without the `x-amz-request-payer` header (see [LakeBuilder::requester_pays]), so the identical requests of the different indexers
+    /// are served from the cache, and only the cache pays for fetching the objects from the bucket once.
+    ///
+    /// *Note*: The cache signs the requests to the bucket itself. Cache the objects (they never change) for long,
+    /// but the `List` requests (with the `list-type=2` query) for no longer than the [LakeBuilder::poll_interval],
+    /// otherwise the indexers following the tip of the network get the stale block heights.
+    /// Combine with [LakeBuilder::etag_cache_size] to revalidate the refetched objects with `304 Not Modified`
+    /// ```
+    /// use near_lake_framework::LakeBuilder;
+    ///
+    /// # fn main() {
+    ///     let lake = LakeBuilder::default()
+    ///         .mainnet()
+    ///         .cache_endpoint("https://near-lake-cache.internal")
+    ///         .start_block_height(65231161)
+    ///         .build()
+    ///         .expect("Failed to build Lake");
+    /// # }
+    /// ```
+    #[builder(setter(into, strip_option), default)]
+    pub(crate) cache_endpoint: Option<String>,
+    /// Defines how many pages (up to 1000 block heights each) of the S3 `List` response to follow
+    /// with the continuation token in a single batch. Default: 1
+    ///
+    /// Larger batches keep the prefetching pool and the [LakeBuilder::backfill_workers] busy when resuming far behind the tip of the network.
+    #[builder(default = "1")]
+    pub(crate) list_max_pages: usize,
+    /// Defines how many S3 `List` requests to run concurrently, each one for its own range of block heights. Default: 1
+    ///
+    /// Useful for deep backfills with fast handlers: the ranges are listed in parallel and merged in order,
+    /// so the prefetching pool stays full. See also [LakeBuilder::list_max_pages]
+    #[builder(default = "1")]
+    pub(crate) list_concurrency: usize,
+    /// Defines how many recently fetched objects to keep in memory along with their ETags. Default: 0 (disabled)
+    ///
+    /// When enabled, repeated `GetObject` requests (e.g. refetching blocks after a restart of the stream)
+    /// are sent with the `If-None-Match` header, so a CDN or a caching proxy in front of the S3-compatible storage
+    /// can respond with `304 Not Modified` instead of transferring the whole object again.
+    #[builder(default = "0")]
+    pub(crate) etag_cache_size: usize,
+    /// Defines the local directory to cache the fetched objects in. Default: None (disabled)
+    ///
+    /// The objects (and the full pages of the historical `List` responses) are served from the disk if they
+    /// have been fetched before, which makes re-running the indexer over the same range of blocks
+    /// (e.g. during development) free after the first pass. The missing ones are fetched from the bucket
+    /// (or the custom [S3Client](crate::S3Client)) and written back to the disk.
+    /// *Note*: The cache is never evicted, clean the directory up manually
+    #[builder(setter(into, strip_option), default)]
+    pub(crate) cache_dir: Option<std::path::PathBuf>,
+    /// Defines how many dedicated threads parse the fetched JSON objects. Default: 0 (parsed on the async runtime)
+    ///
+    /// Parsing the large blocks is CPU-heavy, so during the backfills (see [LakeBuilder::backfill_workers])
+    /// the dedicated threads let the parsing throughput scale with the CPU cores.
+    #[builder(default = "0")]
+    pub(crate) parser_threads: usize,
+    /// Defines the address to serve the Prometheus metrics (`/metrics`) and the health status (`/healthz`) on.
+    /// Default: None (not served). Requires the `http-server` feature
+    #[cfg(feature = "http-server")]
+    #[builder(setter(strip_option), default)]
+    pub(crate) http_server_addr: Option<std::net::SocketAddr>,
+    /// Defines the address to share the fetched objects with the peer indexers on. Default: None (not shared).
+    /// Requires the `block-share` feature
+    ///
+    /// A fleet of the indexers streaming the same blocks fetches every object from the bucket only once:
+    /// one of them serves the objects it has fetched recently (and fetches the missing ones on behalf of the peers),
+    /// and the peers set their [LakeBuilder::cache_endpoint] to `http://{block_share_addr}` with the same bucket name.
+    ///
+    /// *Note*: The listener is unauthenticated and serves the block and shard objects of the bucket to anyone who can reach it,
+    /// bind it to the private address of the fleet network
+    /// ```no_run
+    /// # fn main() {
+    ///     // the indexer fetching from the bucket
+    ///     let lake = near_lake_framework::LakeBuilder::default()
+    ///         .mainnet()
+    ///         .block_share_addr("10.0.0.1:8090".parse().unwrap())
+    ///         .start_block_height(65231161)
+    ///         .build()
+    ///         .expect("Failed to build Lake");
+    ///
+    ///     // the peers
+    ///     let lake = near_lake_framework::LakeBuilder::default()
+    ///         .mainnet()
+    ///         .cache_endpoint("http://10.0.0.1:8090")
+    ///         .start_block_height(65231161)
+    ///         .build()
+    ///         .expect("Failed to build Lake");
+    /// # }
+    /// ```
+    #[cfg(feature = "block-share")]
+    #[builder(setter(strip_option), default)]
+    pub(crate) block_share_addr: Option<std::net::SocketAddr>,
+    /// Defines how often to report the progress towards the tip of the network. Default: None (not reported)
+    ///
+    /// The [Progress] (blocks processed, blocks remaining, rate and ETA) is logged when the stream is behind
+    /// and passed to the [Lake::on_progress](crate::Lake::on_progress) callback, so the multi-day backfills are observable.
+    /// The target is the [LakeBuilder::end_block_height] if set, otherwise the latest block height in the bucket
+    /// found with a few S3 `List` requests on every report.
+    #[builder(setter(strip_option), default)]
+    pub(crate) progress_interval: Option<std::time::Duration>,
+    /// Defines the path of the Unix socket to serve the admin commands (`status`, `pause`, `resume`, `seek <block_height>`, `limit <blocks_per_second|off>`) on.
+    /// Default: None (not served). Requires the `admin-socket` feature
+    ///
+    /// The commands are executed with the [ControlHandle](crate::ControlHandle) of the indexer
+    #[cfg(all(unix, feature = "admin-socket"))]
+    #[builder(setter(into, strip_option), default)]
+    pub(crate) admin_socket_path: Option<std::path::PathBuf>,
+    /// Shut down gracefully on SIGTERM and SIGINT (Ctrl+C). Default: false. Requires the `signals` feature
+    ///
+    /// The stream stops taking the new blocks, the blocks being handled are finished and the last processed
+    /// block height is stored to the [LakeBuilder::watermark_store] (see [StateStore::last_height](crate::StateStore::last_height)),
+    /// so the indexer deployed to Kubernetes resumes exactly where it has stopped. See [ControlHandle::shutdown](crate::ControlHandle::shutdown)
+    #[cfg(feature = "signals")]
+    #[builder(default = "false")]
+    pub(crate) graceful_shutdown: bool,
+    /// Defines how often to probe the chain head (the latest block height in the bucket) to measure the [Lag]. Default: None (not probed)
+    ///
+    /// Every probe takes a few S3 `List` requests. The lag is exported with the metrics of the `http-server` feature
+    /// and passed to the [Lake::on_lag_alert](crate::Lake::on_lag_alert) callback when it exceeds the threshold
+    #[builder(setter(strip_option), default)]
+    pub(crate) lag_probe_interval: Option<std::time::Duration>,
+    /// Defines the scheme of the object keys in the bucket. Default: [DefaultKeyLayout](crate::DefaultKeyLayout)
+    ///
+    /// See [KeyLayout](crate::KeyLayout) for the details
+    #[builder(setter(custom), default)]
+    pub(crate) key_layout: Option<std::sync::Arc<dyn crate::KeyLayout>>,
+    /// Abort the stream and return the first handler error from [Lake::run](crate::Lake::run). Default: false (the handler errors are logged and skipped)
+    ///
+    /// *Note*: The [Lake::on_error](crate::Lake::on_error) callback takes precedence over this flag if set
+    #[builder(default = "false")]
+    pub(crate) stop_on_handler_error: bool,
+    /// Limits how many times in a row the block is retried on [ErrorDecision::Retry]. Default: 3
+    ///
+    /// Every retry fetches the block again. Once the retries are exhausted, the handler error is skipped or aborts
+    /// the indexer as if there were no [Lake::on_error](crate::Lake::on_error) callback, and the streamer error aborts it
+    #[builder(default = "3")]
+    pub(crate) max_retries: u64,
+    /// Limits the number of blocks passed to the handler per second. Default: None (no limit)
+    ///
+    /// Protects the shared downstream databases from being overloaded by the historical reprocessing.
+    /// The limit applies after the blocks are fetched, so the prefetching still amortizes the S3 latency.
+    /// Adjust it at runtime with [ControlHandle::set_max_blocks_per_second](crate::ControlHandle::set_max_blocks_per_second)
+    #[builder(setter(strip_option), default)]
+    pub(crate) max_blocks_per_second: Option<f64>,
+    /// Defines how long the handler may process a single block. Default: None (no timeout)
+    ///
+    /// When exceeded, the handler future is dropped and [LakeError::HandlerTimeout] goes through the configured
+    /// error policy ([Lake::on_error](crate::Lake::on_error) or [LakeBuilder::stop_on_handler_error]),
+    /// so a single block with a pathological payload can't wedge the whole pipeline silently.
+    #[builder(setter(strip_option), default)]
+    pub(crate) handler_timeout: Option<std::time::Duration>,
+    /// Defines how long the stream may deliver no blocks, while the bucket has the newer ones, before it is restarted.
+    /// Default: None (not watched)
+    ///
+    /// Recovers from the wedged connections without an external supervision. The time is measured while the handlers
+    /// are idle only, so the slow handlers don't trigger the restart. Every check of the bucket takes a few S3 `List` requests.
+    /// Set up the [Lake::on_stall](crate::Lake::on_stall) callback to be notified and to decide whether to restart
+    #[builder(setter(strip_option), default)]
+    pub(crate) stall_timeout: Option<std::time::Duration>,
+    /// Defines where to publish the high watermark (the height of the last block such that it and all
+    /// the blocks before it have been processed by the handler). Default: None (not published)
+    ///
+    /// The watermark is stored under the [WATERMARK_KEY](crate::WATERMARK_KEY) of the [StateStore](crate::StateStore),
+    /// so the downstream consumers of the indexer's outputs know how fresh the data is.
+    /// Use [S3StateStore](crate::S3StateStore) or implement the trait to publish to Redis, an HTTP endpoint, etc.
+    #[builder(setter(custom), default)]
+    pub(crate) watermark_store: Option<std::sync::Arc<dyn crate::StateStore>>,
+    /// Defines the [StateStore](crate::StateStore) to resume the indexing from. Default: None (starts from the [LakeBuilder::start_block_height])
+    ///
+    /// The indexer starts from the block following the stored watermark or last processed block height (whichever is higher)
+    /// if it is above the start block height. Unless the [LakeBuilder::watermark_store] is set, the store gets the watermark too,
+    /// so the restarted indexer picks up where it has stopped
+    #[builder(setter(custom), default)]
+    pub(crate) resume_store: Option<std::sync::Arc<dyn crate::StateStore>>,
+    /// Defines where to report the fetch failures and the handler errors to. Default: None (only logged)
+    ///
+    /// See [ErrorReporter](crate::ErrorReporter) for the example of the Sentry integration
+    #[builder(setter(custom), default)]
+    pub(crate) error_reporter: Option<std::sync::Arc<dyn crate::ErrorReporter>>,
+    /// Callback to decide what to do on errors. Set up with [Lake::on_error](crate::Lake::on_error)
+    #[builder(setter(skip))]
+    pub(crate) on_error: Option<ErrorCallback>,
+    /// Callback to receive the [Progress] reports. Set up with [Lake::on_progress](crate::Lake::on_progress)
+    #[builder(setter(skip))]
+    pub(crate) on_progress: Option<ProgressCallback>,
+    /// Callback to notify about catching up with the chain head. Set up with [Lake::on_caught_up](crate::Lake::on_caught_up)
+    #[builder(setter(skip))]
+    pub(crate) on_caught_up: Option<CaughtUpCallback>,
+    /// Callback to alert on the [Lag] exceeding the threshold. Set up with [Lake::on_lag_alert](crate::Lake::on_lag_alert)
+    #[builder(setter(skip))]
+    pub(crate) on_lag_alert: Option<LagAlert>,
+    /// Handle to control the running indexer, see [Lake::control_handle](crate::Lake::control_handle)
+    #[builder(setter(skip))]
+    pub(crate) control: crate::ControlHandle,
+    /// Callback to decide whether to restart the stalled stream. Set up with [Lake::on_stall](crate::Lake::on_stall)
+    #[builder(setter(skip))]
+    pub(crate) on_stall: Option<StallCallback>,
+    /// Callback to report the [StreamStatus](crate::StreamStatus) periodically. Set up with [Lake::on_heartbeat](crate::Lake::on_heartbeat)
+    #[builder(setter(skip))]
+    pub(crate) on_heartbeat: Option<Heartbeat>,
+    /// Functions enriching the blocks before the handler. Set up with [Lake::enrich](crate::Lake::enrich)
+    #[builder(setter(skip))]
+    pub(crate) enrichers: Vec<Enricher>,
+}
+
+impl Lake {
+    /// Returns the configured [KeyLayout](crate::KeyLayout) or the [DefaultKeyLayout](crate::DefaultKeyLayout)
+    pub(crate) fn key_layout(&self) -> std::sync::Arc<dyn crate::KeyLayout> {
+        self.key_layout
+            .clone()
+            .unwrap_or_else(|| std::sync::Arc::new(crate::DefaultKeyLayout))
+    }
+
+    /// Returns the name of the configured [BlockProvider](crate::BlockProvider)
+    pub(crate) fn provider_name(&self) -> &'static str {
+        if !self.provider_switches.is_empty() {
+            return crate::provider::SWITCHING_PROVIDER;
+        }
+        self.block_provider
+            .as_ref()
+            .map(|block_provider| block_provider.name())
+            .unwrap_or(crate::s3_fetchers::PROVIDER)
+    }
+}
+
+/// Counter of the S3 `Get`, `Head` and `List` requests performed by Lake Framework.
+///
+/// The counter is cheap to clone, all the clones share the same numbers. It allows you to verify the cost estimates
+/// from the README against your actual workload.
+/// ```no_run
+/// use near_lake_framework::{LakeBuilder, S3Pricing, S3RequestsCounter};
+///
+/// # fn main() -> anyhow::Result<()> {
+///    let s3_requests_counter = S3RequestsCounter::default();
+///
+///    LakeBuilder::default()
+///        .testnet()
+///        .start_block_height(82422587)
+///        .s3_requests_counter(s3_requests_counter.clone())
+///        .build()?
+///        .run(handle_block)?;
+///
+///    println!(
+///        "GET: {}, LIST: {}, estimated cost: ${:.4}",
+///        s3_requests_counter.get_requests(),
+///        s3_requests_counter.list_requests(),
+///        s3_requests_counter.estimated_cost(&S3Pricing::default()),
+///    );
+/// #   Ok(())
+/// # }
+///
+/// # async fn handle_block(_block: near_lake_primitives::block::Block) -> anyhow::Result<()> { Ok(()) }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct S3RequestsCounter {
+    get_requests: std::sync::Arc<std::sync::atomic::AtomicU64>,
+    head_requests: std::sync::Arc<std::sync::atomic::AtomicU64>,
+    list_requests: std::sync::Arc<std::sync::atomic::AtomicU64>,
+}
+
+impl S3RequestsCounter {
+    /// Returns the number of S3 `Get` requests performed so far
+    pub fn get_requests(&self) -> u64 {
+        self.get_requests.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Returns the number of S3 `Head` requests performed so far
+    pub fn head_requests(&self) -> u64 {
+        self.head_requests
+            .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Returns the number of S3 `List` requests performed so far
+    pub fn list_requests(&self) -> u64 {
+        self.list_requests
+            .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Returns the estimated cost (in dollars) of the performed requests according to the given [S3Pricing]
+    ///
+    /// *Note*: `Head` requests are charged the same as the `Get` ones.
+    pub fn estimated_cost(&self, pricing: &S3Pricing) -> f64 {
+        (self.get_requests() + self.head_requests()) as f64 / 1000.0 * pricing.get_per_1000_requests
+            + self.list_requests() as f64 / 1000.0 * pricing.list_per_1000_requests
+    }
+
+    pub(crate) fn inc_get_requests(&self) {
+        self.get_requests
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    pub(crate) fn inc_head_requests(&self) {
+        self.head_requests
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    pub(crate) fn inc_list_requests(&self) {
+        self.list_requests
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    }
+}
+
+/// Prices (in dollars) of the S3 requests used to estimate the cost of the indexer run.
+///
+/// Defaults to the [Amazon S3 prices](https://aws.amazon.com/s3/pricing/?nc1=h_ls) the cost estimates from the README are based on.
+#[derive(Clone, Debug)]
+pub struct S3Pricing {
+    /// Price of 1000 `Get` requests
+    pub get_per_1000_requests: f64,
+    /// Price of 1000 `List` requests
+    pub list_per_1000_requests: f64,
+}
+
+impl Default for S3Pricing {
+    fn default() -> Self {
+        Self {
+            get_per_1000_requests: 0.00043,
+            list_per_1000_requests: 0.0054,
+        }
+    }
 }
 
 impl LakeBuilder {
+    /// Sets up the custom [S3Client](crate::S3Client) to fetch the objects with.
+    /// Mutually exclusive with [LakeBuilder::s3_config]
+    /// ```
+    /// use async_trait::async_trait;
+    /// use aws_sdk_s3::error::SdkError;
+    /// use aws_sdk_s3::operation::get_object::{GetObjectError, GetObjectOutput};
+    /// use aws_sdk_s3::operation::head_object::{HeadObjectError, HeadObjectOutput};
+    /// use aws_sdk_s3::operation::list_objects_v2::{ListObjectsV2Error, ListObjectsV2Output};
+    /// use near_lake_framework::{LakeBuilder, S3Client};
+    ///
+    /// #[derive(Debug)]
+    /// struct CachingS3Client {
+    ///     inner: aws_sdk_s3::Client,
+    /// }
+    ///
+    /// #[async_trait]
+    /// impl S3Client for CachingS3Client {
+    ///     async fn get_object(
+    ///         &self,
+    ///         bucket: &str,
+    ///         prefix: &str,
+    ///     ) -> Result<GetObjectOutput, SdkError<GetObjectError>> {
+    ///         // look up the cache first
+    ///         self.inner.get_object().bucket(bucket).key(prefix).send().await
+    ///     }
+    ///
+    ///     async fn list_objects(
+    ///         &self,
+    ///         bucket: &str,
+    ///         start_after: &str,
+    ///         continuation_token: Option<String>,
+    ///     ) -> Result<ListObjectsV2Output, SdkError<ListObjectsV2Error>> {
+    ///         self.inner
+    ///             .list_objects_v2()
+    ///             .bucket(bucket)
+    ///             .delimiter("/")
+    ///             .start_after(start_after)
+    ///             .set_continuation_token(continuation_token)
+    ///             .send()
+    ///             .await
+    ///     }
+    ///
+    ///     async fn head_object(
+    ///         &self,
+    ///         bucket: &str,
+    ///         prefix: &str,
+    ///     ) -> Result<HeadObjectOutput, SdkError<HeadObjectError>> {
+    ///         self.inner.head_object().bucket(bucket).key(prefix).send().await
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    ///     let aws_config = aws_config::from_env().load().await;
+    ///     let s3_client = CachingS3Client {
+    ///         inner: aws_sdk_s3::Client::new(&aws_config),
+    ///     };
+    ///
+    ///     let lake = LakeBuilder::default()
+    ///         .mainnet()
+    ///         .s3_client(s3_client)
+    ///         .start_block_height(65231161)
+    ///         .build()
+    ///         .expect("Failed to build Lake");
+    /// # }
+    /// ```
+    pub fn s3_client(mut self, s3_client: impl crate::S3Client + 'static) -> Self {
+        self.s3_client = Some(Some(std::sync::Arc::new(s3_client)));
+        self
+    }
+
+    /// Sets up the custom [BlockProvider](crate::BlockProvider) to stream the blocks from, see the trait for the example
+    pub fn block_provider(mut self, block_provider: impl crate::BlockProvider + 'static) -> Self {
+        self.block_provider = Some(Some(std::sync::Arc::new(block_provider)));
+        self
+    }
+
+    /// Switches the stream over to the given [BlockProvider](crate::BlockProvider) from the block height on,
+    /// e.g. to read the old blocks from the bucket and the recent ones from a faster source.
+    /// The bucket (or the [LakeBuilder::block_provider]) serves the blocks below the first switch height,
+    /// every provider serves the blocks from its switch height up to the next one.
+    ///
+    /// The `prev_hash` continuity of the blocks is checked across the boundaries as usual. If a provider has no blocks left
+    /// before the next switch height, the stream continues with the next provider right away.
+    ///
+    /// *Note*: [LakeBuilder::backfill_workers] and [LakeBuilder::no_list] are ignored once the switches are set up
+    /// ```no_run
+    /// # use near_lake_framework::{BlockProvider, LakeBuilder};
+    /// # fn build(fast_provider: impl BlockProvider + 'static) {
+    ///    let lake = LakeBuilder::default()
+    ///        .mainnet()
+    ///        .start_block_height(65231161)
+    ///        .switch_provider_at(112205773, fast_provider)
+    ///        .build()
+    ///        .expect("Failed to build Lake");
+    /// # }
+    /// # fn main() {}
+    /// ```
+    pub fn switch_provider_at(
+        mut self,
+        block_height: BlockHeight,
+        block_provider: impl crate::BlockProvider + 'static,
+    ) -> Self {
+        self.provider_switches
+            .get_or_insert_with(Vec::new)
+            .push((block_height, std::sync::Arc::new(block_provider)));
+        self
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if matches!(self.s3_client, Some(Some(_))) && matches!(self.s3_config, Some(Some(_))) {
+            return Err("`s3_client` and `s3_config` are mutually exclusive".to_string());
+        }
+        if matches!(self.s3_client, Some(Some(_))) && matches!(self.cache_endpoint, Some(Some(_))) {
+            return Err("`s3_client` and `cache_endpoint` are mutually exclusive".to_string());
+        }
+        if let Some(provider_switches) = &self.provider_switches {
+            let mut block_heights: Vec<_> = provider_switches
+                .iter()
+                .map(|(block_height, _)| *block_height)
+                .collect();
+            block_heights.sort_unstable();
+            if block_heights.windows(2).any(|pair| pair[0] == pair[1]) {
+                return Err("`switch_provider_at` block heights must be unique".to_string());
+            }
+        }
+        if let Some(Some(crate::ChannelStrategy::Bounded(0))) = self.channel_strategy {
+            return Err("the capacity of the bounded channel must be positive".to_string());
+        }
+        if let Some(Some(max_blocks_per_second)) = self.max_blocks_per_second {
+            if !(max_blocks_per_second.is_finite() && max_blocks_per_second > 0.0) {
+                return Err("`max_blocks_per_second` must be a positive number".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets up the custom [KeyLayout](crate::KeyLayout) of the object keys in the bucket
+    pub fn key_layout(mut self, key_layout: impl crate::KeyLayout + 'static) -> Self {
+        self.key_layout = Some(Some(std::sync::Arc::new(key_layout)));
+        self
+    }
+
+    /// Sets up the [StateStore](crate::StateStore) to publish the high watermark to
+    pub fn watermark_store(mut self, watermark_store: impl crate::StateStore + 'static) -> Self {
+        self.watermark_store = Some(Some(std::sync::Arc::new(watermark_store)));
+        self
+    }
+
+    /// Sets up the [StateStore](crate::StateStore) to resume the indexing from, see [LakeBuilder::resume_store]
+    ///```no_run
+    /// use near_lake_framework::FileStateStore;
+    ///
+    ///# fn main() -> anyhow::Result<()> {
+    ///    near_lake_framework::LakeBuilder::default()
+    ///        .mainnet()
+    ///        .start_block_height(88444526)
+    ///        .resume_from(FileStateStore::new("/var/lib/indexer/state.json"))
+    ///        .build()?
+    ///        .run(handle_block)?;
+    ///    Ok(())
+    ///# }
+    ///
+    /// # async fn handle_block(_block: near_lake_primitives::block::Block) -> anyhow::Result<()> { Ok(()) }
+    ///```
+    pub fn resume_from(mut self, state_store: impl crate::StateStore + 'static) -> Self {
+        self.resume_store = Some(Some(std::sync::Arc::new(state_store)));
+        self
+    }
+
+    /// Sets up the [ErrorReporter](crate::ErrorReporter) to report the errors to
+    pub fn error_reporter(mut self, error_reporter: impl crate::ErrorReporter + 'static) -> Self {
+        self.error_reporter = Some(Some(std::sync::Arc::new(error_reporter)));
+        self
+    }
+
     /// Shortcut to set up [LakeBuilder::s3_bucket_name] for mainnet
     /// ```
     /// use near_lake_framework::LakeBuilder;
@@ -118,10 +728,36 @@ impl LakeBuilder {
         self.s3_region_name = Some("us-east-1".to_string());
         self
     }
+
+    /// Shortcut to stream the block headers only. Lake Framework fetches the `block.json` objects
+    /// and skips all the shard data (see [LakeBuilder::track_shards])
+    ///
+    /// It is useful for the monitoring tools that only need heights, timestamps, and validator info.
+    /// ```
+    /// use near_lake_framework::LakeBuilder;
+    ///
+    /// # fn main() {
+    ///    let lake = LakeBuilder::default()
+    ///        .mainnet()
+    ///        .headers_only()
+    ///        .start_block_height(65231161)
+    ///        .build()
+    ///        .expect("Failed to build Lake");
+    /// # }
+    /// ```
+    pub fn headers_only(mut self) -> Self {
+        self.track_shards = Some(Some(vec![]));
+        self
+    }
 }
 
+/// The error returned by Lake Framework, both by [Lake::run](crate::Lake::run) and the streamer task.
+///
+/// The enum is `#[non_exhaustive]`, so new error categories can be added without breaking
+/// the downstream code. Always include a wildcard arm when matching on it.
 #[allow(clippy::enum_variant_names)]
 #[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
 pub enum LakeError {
     #[error("Failed to parse structure from JSON: {error_message}")]
     ParseError {
@@ -130,14 +766,17 @@ pub enum LakeError {
     },
     #[error("AWS S3 error: {error}")]
     AwsGetObjectError {
-        #[from]
-        error: aws_sdk_s3::error::SdkError<aws_sdk_s3::operation::get_object::GetObjectError>,
+        error: Box<aws_sdk_s3::error::SdkError<aws_sdk_s3::operation::get_object::GetObjectError>>,
     },
     #[error("AWS S3 error: {error}")]
     AwsLisObjectsV2Error {
-        #[from]
-        error:
+        error: Box<
             aws_sdk_s3::error::SdkError<aws_sdk_s3::operation::list_objects_v2::ListObjectsV2Error>,
+        >,
+    },
+    #[error("AWS S3 error: {error}")]
+    AwsPutObjectError {
+        error: Box<aws_sdk_s3::error::SdkError<aws_sdk_s3::operation::put_object::PutObjectError>>,
     },
     #[error("Failed to convert integer: {error}")]
     IntConversionError {
@@ -156,6 +795,550 @@ pub enum LakeError {
     },
     #[error("Internal error: {error_message}")]
     InternalError { error_message: String },
+    #[error("Invalid configuration: {error_message}")]
+    ConfigError { error_message: String },
+    #[error("State store error: {error_message}")]
+    StateStoreError { error_message: String },
+    #[error(
+        "Block #{block_height} doesn't exist, the height is either skipped or not produced yet"
+    )]
+    BlockNotFound { block_height: BlockHeight },
+    #[error("Handler failed to process block #{block_height}: {error_message}")]
+    HandlerError {
+        block_height: BlockHeight,
+        error_message: String,
+    },
+    #[error("Handler has timed out processing block #{block_height} after {timeout:?}")]
+    HandlerTimeout {
+        block_height: BlockHeight,
+        timeout: std::time::Duration,
+    },
+    #[error(
+        "Failed to fetch block #{block_height}{}: {error}",
+        shard_id.map(|shard_id| format!(" (shard #{})", shard_id)).unwrap_or_default()
+    )]
+    BlockError {
+        block_height: BlockHeight,
+        shard_id: Option<u64>,
+        #[source]
+        error: Box<LakeError>,
+    },
+}
+
+/// Summary of the indexer run returned by [Lake::run](crate::Lake::run) and its siblings on completion
+/// ```no_run
+/// # fn main() -> anyhow::Result<()> {
+///    let summary = near_lake_framework::LakeBuilder::default()
+///        .testnet()
+///        .start_block_height(112205773)
+///        .end_block_height(112205873)
+///        .build()?
+///        .run(handle_block)?;
+///
+///    println!(
+///        "Processed {} blocks (#{:?}..=#{:?}) in {:?}, {} handler failures",
+///        summary.blocks_processed,
+///        summary.first_block_height,
+///        summary.last_block_height,
+///        summary.elapsed,
+///        summary.handler_failures,
+///    );
+///    Ok(())
+/// # }
+///
+/// # async fn handle_block(_block: near_lake_primitives::block::Block) -> anyhow::Result<()> { Ok(()) }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RunSummary {
+    /// Number of blocks the handler has finished with (including the skipped failed ones)
+    pub blocks_processed: u64,
+    /// The lowest processed block height
+    pub first_block_height: Option<BlockHeight>,
+    /// The highest processed block height
+    pub last_block_height: Option<BlockHeight>,
+    /// Number of times the handler has returned an error (each retry is counted)
+    pub handler_failures: u64,
+    /// Time elapsed since the start of the run
+    pub elapsed: std::time::Duration,
+}
+
+/// Collects the [RunSummary] while the handlers are running concurrently
+#[derive(Debug)]
+pub(crate) struct RunStats {
+    started_at: std::time::Instant,
+    blocks_processed: std::sync::atomic::AtomicU64,
+    first_block_height: std::sync::atomic::AtomicU64,
+    last_block_height: std::sync::atomic::AtomicU64,
+    last_block_timestamp_nanosec: std::sync::atomic::AtomicU64,
+    handler_failures: std::sync::atomic::AtomicU64,
+    /// The latest block height known to be in the bucket, 0 until probed
+    chain_head_height: std::sync::atomic::AtomicU64,
+    stream_failures: std::sync::atomic::AtomicU64,
+    in_flight: std::sync::atomic::AtomicUsize,
+    queued: std::sync::atomic::AtomicUsize,
+}
+
+impl RunStats {
+    pub(crate) fn new() -> Self {
+        Self {
+            started_at: std::time::Instant::now(),
+            blocks_processed: std::sync::atomic::AtomicU64::new(0),
+            first_block_height: std::sync::atomic::AtomicU64::new(BlockHeight::MAX),
+            last_block_height: std::sync::atomic::AtomicU64::new(0),
+            last_block_timestamp_nanosec: std::sync::atomic::AtomicU64::new(0),
+            handler_failures: std::sync::atomic::AtomicU64::new(0),
+            chain_head_height: std::sync::atomic::AtomicU64::new(0),
+            stream_failures: std::sync::atomic::AtomicU64::new(0),
+            in_flight: std::sync::atomic::AtomicUsize::new(0),
+            queued: std::sync::atomic::AtomicUsize::new(0),
+        }
+    }
+
+    pub(crate) fn record_block(&self, block_height: BlockHeight, block_timestamp_nanosec: u64) {
+        use std::sync::atomic::Ordering::Relaxed;
+
+        self.blocks_processed.fetch_add(1, Relaxed);
+        self.first_block_height.fetch_min(block_height, Relaxed);
+        self.last_block_height.fetch_max(block_height, Relaxed);
+        // the timestamps grow with the heights, so the max one belongs to the last block
+        self.last_block_timestamp_nanosec
+            .fetch_max(block_timestamp_nanosec, Relaxed);
+    }
+
+    /// Returns the number of the stream failures including this one
+    pub(crate) fn record_stream_failure(&self) -> u64 {
+        self.stream_failures
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+            + 1
+    }
+
+    pub(crate) fn record_in_flight(&self, blocks: usize) {
+        self.in_flight
+            .store(blocks, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_queued(&self, blocks: usize) {
+        self.queued
+            .store(blocks, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    /// Returns the [StreamStatus](crate::StreamStatus) of the run
+    pub(crate) fn status(&self, paused: bool) -> crate::StreamStatus {
+        use std::sync::atomic::Ordering::Relaxed;
+
+        let summary = self.summary();
+        crate::StreamStatus {
+            paused,
+            blocks_processed: summary.blocks_processed,
+            last_block_height: summary.last_block_height,
+            in_flight: self.in_flight.load(Relaxed),
+            queued: self.queued.load(Relaxed),
+            handler_failures: summary.handler_failures,
+            stream_failures: self.stream_failures.load(Relaxed),
+            elapsed: summary.elapsed,
+        }
+    }
+
+    pub(crate) fn record_chain_head(&self, block_height: BlockHeight) {
+        self.chain_head_height
+            .fetch_max(block_height, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    /// Returns the [Lag] behind the chain head, `None` until the chain head is probed
+    pub(crate) fn lag(&self) -> Option<Lag> {
+        use std::sync::atomic::Ordering::Relaxed;
+
+        let chain_head_height = match self.chain_head_height.load(Relaxed) {
+            0 => return None,
+            chain_head_height => chain_head_height,
+        };
+        let summary = self.summary();
+        let last_block_timestamp_nanosec = self.last_block_timestamp_nanosec.load(Relaxed);
+        Some(Lag {
+            chain_head_height,
+            last_block_height: summary.last_block_height,
+            blocks: summary
+                .last_block_height
+                .map(|last_block_height| chain_head_height.saturating_sub(last_block_height)),
+            seconds: summary.last_block_height.map(|_| {
+                std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .saturating_sub(std::time::Duration::from_nanos(
+                        last_block_timestamp_nanosec,
+                    ))
+            }),
+        })
+    }
+
+    pub(crate) fn record_handler_failure(&self) {
+        self.handler_failures
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    pub(crate) fn summary(&self) -> RunSummary {
+        use std::sync::atomic::Ordering::Relaxed;
+
+        let blocks_processed = self.blocks_processed.load(Relaxed);
+        RunSummary {
+            blocks_processed,
+            first_block_height: (blocks_processed > 0)
+                .then(|| self.first_block_height.load(Relaxed)),
+            last_block_height: (blocks_processed > 0).then(|| self.last_block_height.load(Relaxed)),
+            handler_failures: self.handler_failures.load(Relaxed),
+            elapsed: self.started_at.elapsed(),
+        }
+    }
+}
+
+/// Progress of the indexer towards the tip of the network (or the [LakeBuilder::end_block_height]),
+/// reported every [LakeBuilder::progress_interval]
+#[derive(Clone, Debug, Default, PartialEq)]
+#[non_exhaustive]
+pub struct Progress {
+    /// Number of blocks the handler has finished with since the start of the run
+    pub blocks_processed: u64,
+    /// The highest processed block height
+    pub last_block_height: Option<BlockHeight>,
+    /// The block height the indexer is catching up with
+    pub target_block_height: BlockHeight,
+    /// Number of block heights between the last processed and the target one (the skipped heights are counted too)
+    pub blocks_remaining: u64,
+    /// The rate the stream has advanced at since the previous report, in block heights per second
+    pub blocks_per_second: f64,
+    /// Estimated time to reach the target block height, `None` if the stream hasn't advanced since the previous report
+    pub eta: Option<std::time::Duration>,
+}
+
+/// Lag of the indexer behind the chain head (the latest block height in the bucket),
+/// probed every [LakeBuilder::lag_probe_interval]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Lag {
+    /// The latest block height in the bucket
+    pub chain_head_height: BlockHeight,
+    /// The highest processed block height
+    pub last_block_height: Option<BlockHeight>,
+    /// Number of block heights between the last processed one and the chain head, `None` until the first block is processed
+    pub blocks: Option<u64>,
+    /// Time elapsed since the timestamp of the last processed block, `None` until the first block is processed
+    pub seconds: Option<std::time::Duration>,
+}
+
+/// Filter of the events for [Lake::subscribe_events](crate::Lake::subscribe_events).
+/// The event has to match all the provided criteria, the criteria that are not provided match any event
+/// ```
+/// use near_lake_framework::EventFilter;
+///
+/// let filter = EventFilter::default()
+///     .standard("nep171")
+///     .event("nft_mint")
+///     .contract("x.paras.near".parse().unwrap());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct EventFilter {
+    standard: Option<String>,
+    event: Option<String>,
+    contract_ids: Vec<near_lake_primitives::AccountId>,
+}
+
+impl EventFilter {
+    /// Matches the events of the given standard only, e.g. `nep141`
+    pub fn standard(mut self, standard: impl Into<String>) -> Self {
+        self.standard = Some(standard.into());
+        self
+    }
+
+    /// Matches the events with the given name only, e.g. `ft_transfer`
+    pub fn event(mut self, event: impl Into<String>) -> Self {
+        self.event = Some(event.into());
+        self
+    }
+
+    /// Matches the events emitted by the given contract. Can be called multiple times to match any of the contracts
+    pub fn contract(mut self, contract_id: near_lake_primitives::AccountId) -> Self {
+        self.contract_ids.push(contract_id);
+        self
+    }
+
+    /// Returns true if the event matches the filter
+    pub fn matches(&self, event: &near_lake_primitives::events::Event) -> bool {
+        self.standard
+            .as_deref()
+            .map(|standard| event.standard() == standard)
+            .unwrap_or(true)
+            && self
+                .event
+                .as_deref()
+                .map(|event_name| event.event() == event_name)
+                .unwrap_or(true)
+            && (self.contract_ids.is_empty()
+                || self
+                    .contract_ids
+                    .iter()
+                    .any(|contract_id| event.is_emitted_by_contract(contract_id)))
+    }
+}
+
+/// The decision returned by the [Lake::on_error](crate::Lake::on_error) callback
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorDecision {
+    /// Skip the block the error has happened at and continue with the next one
+    Skip,
+    /// Retry the block the error has happened at
+    Retry,
+    /// Stop the indexer and return the error from [Lake::run](crate::Lake::run)
+    Abort,
+}
+
+type ErrorCallbackFn = dyn Fn(&LakeError, Option<BlockHeight>) -> ErrorDecision + Send + Sync;
+
+/// Wrapper around the [Lake::on_error](crate::Lake::on_error) callback
+#[derive(Clone)]
+pub(crate) struct ErrorCallback(pub(crate) std::sync::Arc<ErrorCallbackFn>);
+
+impl std::fmt::Debug for ErrorCallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ErrorCallback").finish_non_exhaustive()
+    }
+}
+
+type ProgressCallbackFn = dyn Fn(&Progress) + Send + Sync;
+
+/// Wrapper around the [Lake::on_progress](crate::Lake::on_progress) callback
+#[derive(Clone)]
+pub(crate) struct ProgressCallback(pub(crate) std::sync::Arc<ProgressCallbackFn>);
+
+impl std::fmt::Debug for ProgressCallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProgressCallback").finish_non_exhaustive()
+    }
+}
+
+type CaughtUpCallbackFn = dyn Fn(BlockHeight) + Send + Sync;
+
+/// Wrapper around the [Lake::on_caught_up](crate::Lake::on_caught_up) callback
+#[derive(Clone)]
+pub(crate) struct CaughtUpCallback(pub(crate) std::sync::Arc<CaughtUpCallbackFn>);
+
+impl std::fmt::Debug for CaughtUpCallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CaughtUpCallback").finish_non_exhaustive()
+    }
+}
+
+type LagAlertFn = dyn Fn(&Lag) + Send + Sync;
+
+/// Wrapper around the [Lake::on_lag_alert](crate::Lake::on_lag_alert) callback and its threshold in blocks
+#[derive(Clone)]
+pub(crate) struct LagAlert(pub(crate) u64, pub(crate) std::sync::Arc<LagAlertFn>);
+
+impl std::fmt::Debug for LagAlert {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LagAlert")
+            .field("threshold", &self.0)
+            .finish_non_exhaustive()
+    }
+}
+
+type StallCallbackFn = dyn Fn(BlockHeight, std::time::Duration) -> bool + Send + Sync;
+
+/// Wrapper around the [Lake::on_stall](crate::Lake::on_stall) callback
+#[derive(Clone)]
+pub(crate) struct StallCallback(pub(crate) std::sync::Arc<StallCallbackFn>);
+
+impl std::fmt::Debug for StallCallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StallCallback").finish_non_exhaustive()
+    }
+}
+
+type HeartbeatFn = dyn Fn(crate::StreamStatus) + Send + Sync;
+
+/// Wrapper around the [Lake::on_heartbeat](crate::Lake::on_heartbeat) callback and its interval
+#[derive(Clone)]
+pub(crate) struct Heartbeat(
+    pub(crate) std::time::Duration,
+    pub(crate) std::sync::Arc<HeartbeatFn>,
+);
+
+impl std::fmt::Debug for Heartbeat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Heartbeat")
+            .field("interval", &self.0)
+            .finish_non_exhaustive()
+    }
+}
+
+type EnricherFn = dyn Fn(
+        &near_lake_primitives::block::Block,
+    ) -> futures::future::BoxFuture<
+        'static,
+        Box<dyn FnOnce(&mut near_lake_primitives::block::Extensions) + Send>,
+    > + Send
+    + Sync;
+
+/// Wrapper around the [Lake::enrich](crate::Lake::enrich) function
+#[derive(Clone)]
+pub(crate) struct Enricher(pub(crate) std::sync::Arc<EnricherFn>);
+
+impl std::fmt::Debug for Enricher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Enricher").finish_non_exhaustive()
+    }
+}
+
+/// How many requests of the same block data object answered with `403 AccessDenied` are treated as the object
+/// is missing, see [ErrorKind::from_object_sdk_error]
+pub(crate) const ACCESS_DENIED_ATTEMPTS: u64 = 20;
+
+/// Classification of the [LakeError] (or the underlying S3 error) that tells whether it makes sense to retry
+/// ```
+/// use near_lake_framework::{ErrorKind, LakeError};
+///
+/// fn should_retry(err: &LakeError) -> bool {
+///     matches!(err.kind(), ErrorKind::Transient | ErrorKind::NotFoundYet)
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// Network failures, timeouts, throttling and 5xx responses. Retrying is expected to succeed eventually
+    Transient,
+    /// The error won't go away on retry, e.g. a malformed object or an invalid request
+    Fatal,
+    /// The credentials are missing, invalid or don't grant the access to the bucket
+    Unauthorized,
+    /// The object doesn't exist in the bucket (yet). It is either not written yet or the block is skipped
+    NotFoundYet,
+}
+
+impl ErrorKind {
+    /// Classifies the error returned by the AWS S3 SDK
+    pub fn from_sdk_error<E>(err: &aws_sdk_s3::error::SdkError<E>) -> Self {
+        use aws_sdk_s3::error::SdkError;
+
+        match err {
+            SdkError::TimeoutError(_)
+            | SdkError::DispatchFailure(_)
+            | SdkError::ResponseError(_) => Self::Transient,
+            SdkError::ServiceError(service_error) => match service_error.raw().status().as_u16() {
+                401 | 403 => Self::Unauthorized,
+                404 => Self::NotFoundYet,
+                408 | 429 | 500..=599 => Self::Transient,
+                _ => Self::Fatal,
+            },
+            _ => Self::Fatal,
+        }
+    }
+
+    /// Classifies the error of the `attempt`-th `GetObject` or `HeadObject` request of the same block data object.
+    ///
+    /// Without the `s3:ListBucket` permission S3 answers `403 AccessDenied` instead of `404 Not Found`
+    /// for the missing keys, so such errors are [ErrorKind::NotFoundYet] (the `HeadObject` responses have no error code at all)
+    /// for up to [ACCESS_DENIED_ATTEMPTS] attempts. After that the access is assumed to be denied indeed
+    /// and the error is [ErrorKind::Unauthorized]
+    pub(crate) fn from_object_sdk_error<E: aws_sdk_s3::error::ProvideErrorMetadata>(
+        err: &aws_sdk_s3::error::SdkError<E>,
+        attempt: u64,
+    ) -> Self {
+        match err {
+            aws_sdk_s3::error::SdkError::ServiceError(service_error)
+                if attempt <= ACCESS_DENIED_ATTEMPTS
+                    && service_error.raw().status().as_u16() == 403
+                    && matches!(service_error.err().code(), None | Some("AccessDenied")) =>
+            {
+                Self::NotFoundYet
+            }
+            _ => Self::from_sdk_error(err),
+        }
+    }
+}
+
+impl LakeError {
+    /// Returns the [ErrorKind] of the error, so the retry policies don't have to rely on the error text
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::AwsGetObjectError { error } => ErrorKind::from_sdk_error(error),
+            Self::AwsLisObjectsV2Error { error } => ErrorKind::from_sdk_error(error),
+            Self::AwsPutObjectError { error } => ErrorKind::from_sdk_error(error),
+            Self::BlockError { error, .. } => error.kind(),
+            Self::HandlerTimeout { .. } => ErrorKind::Transient,
+            Self::BlockNotFound { .. } => ErrorKind::NotFoundYet,
+            _ => ErrorKind::Fatal,
+        }
+    }
+
+    /// Attaches the block height (and the shard id if relevant) the error has happened at
+    pub(crate) fn at_block(self, block_height: BlockHeight, shard_id: Option<u64>) -> Self {
+        match self {
+            Self::BlockError { .. } => self,
+            error => Self::BlockError {
+                block_height,
+                shard_id,
+                error: Box::new(error),
+            },
+        }
+    }
+
+    /// Returns the block height the error has happened at, if known
+    pub fn block_height(&self) -> Option<BlockHeight> {
+        match self {
+            Self::BlockError { block_height, .. }
+            | Self::BlockNotFound { block_height }
+            | Self::HandlerError { block_height, .. }
+            | Self::HandlerTimeout { block_height, .. } => Some(*block_height),
+            _ => None,
+        }
+    }
+
+    /// Returns the shard id the error has happened at, if known
+    pub fn shard_id(&self) -> Option<u64> {
+        match self {
+            Self::BlockError { shard_id, .. } => *shard_id,
+            _ => None,
+        }
+    }
+}
+
+// the AWS SDK errors are boxed to keep the `Result<_, LakeError>` small
+impl From<aws_sdk_s3::error::SdkError<aws_sdk_s3::operation::get_object::GetObjectError>>
+    for LakeError
+{
+    fn from(
+        error: aws_sdk_s3::error::SdkError<aws_sdk_s3::operation::get_object::GetObjectError>,
+    ) -> Self {
+        Self::AwsGetObjectError {
+            error: Box::new(error),
+        }
+    }
+}
+
+impl From<aws_sdk_s3::error::SdkError<aws_sdk_s3::operation::list_objects_v2::ListObjectsV2Error>>
+    for LakeError
+{
+    fn from(
+        error: aws_sdk_s3::error::SdkError<
+            aws_sdk_s3::operation::list_objects_v2::ListObjectsV2Error,
+        >,
+    ) -> Self {
+        Self::AwsLisObjectsV2Error {
+            error: Box::new(error),
+        }
+    }
+}
+
+impl From<aws_sdk_s3::error::SdkError<aws_sdk_s3::operation::put_object::PutObjectError>>
+    for LakeError
+{
+    fn from(
+        error: aws_sdk_s3::error::SdkError<aws_sdk_s3::operation::put_object::PutObjectError>,
+    ) -> Self {
+        Self::AwsPutObjectError {
+            error: Box::new(error),
+        }
+    }
 }
 
 /// ### The concept of Context for the Lake Framework
@@ -317,9 +1500,194 @@ pub enum LakeError {
 /// The `ParentTransactionCache` defines the `execute_before_run` and `execute_after_run` methods. So when we call `run_with_context` method
 /// the Lake Framework will call `execute_before_run` and `execute_after_run` methods for us.
 /// And we didn't need to implement them in our `ApplicationDataContext` struct because `LakeContext` derive macro did it for us automatically.
+///
+/// #### Tuples of contexts
+/// The simple compositions don't need a named struct at all: the tuples of up to 4 contexts implement the trait as well,
+/// calling `execute_before_run` in the order of the elements and `execute_after_run` and `finalize` in the reverse one.
+/// ```ignore
+/// let context = (metrics_context, parent_transaction_cache);
+/// lake.run_with_context(indexing_function, &context)?;
+///
+/// async fn indexing_function(
+///    block: near_lake_primitives::block::Block,
+///    (metrics, parent_transaction_cache): &(MetricsContext, ParentTransactionCache),
+/// ) -> anyhow::Result<()> {
+///     Ok(())
+/// }
+/// ```
+///
+/// #### Per-block data
+/// The data shared within the processing of a single block belongs to its [BlockScope](near_lake_primitives::block::BlockScope)
+/// rather than to the long-lived fields of the context: the before-hook populates it, the handler reads it with
+/// `block.scope()` and [LakeContextExt::execute_after_run_with_scope] receives it after the handler.
+/// ```ignore
+/// #[derive(Clone)]
+/// struct NftMints(Vec<near_lake_primitives::events::Event>);
+///
+/// impl LakeContextExt for NftMintsContext {
+///     fn execute_before_run(&self, block: &mut near_lake_primitives::block::Block) {
+///         let mints = block.events().filter(|event| event.event() == "nft_mint").cloned().collect();
+///         block.scope().insert(NftMints(mints));
+///     }
+///
+///     fn execute_after_run(&self) {}
+///
+///     fn execute_after_run_with_scope(&self, scope: &near_lake_primitives::block::BlockScope) {
+///         scope.with(|mints: &NftMints| self.mints_counter.inc_by(mints.0.len() as u64));
+///     }
+/// }
+/// ```
 pub trait LakeContextExt {
     /// This method will be called before the indexing process is started.
     fn execute_before_run(&self, block: &mut near_lake_primitives::block::Block);
     /// This method will be called after the indexing process is finished.
     fn execute_after_run(&self);
+    /// This method will be called after the indexing process is finished instead of [LakeContextExt::execute_after_run]
+    /// with the [BlockScope](near_lake_primitives::block::BlockScope) of the block, populated by the before-hooks and the handler.
+    ///
+    /// The default implementation calls [LakeContextExt::execute_after_run]
+    fn execute_after_run_with_scope(&self, _scope: &near_lake_primitives::block::BlockScope) {
+        self.execute_after_run()
+    }
+    /// This method will be called once the stream has terminated (reached the end block height, failed or has been
+    /// shut down with the [ControlHandle](crate::ControlHandle)), so the context can flush the buffered writes and close the connections.
+    /// It isn't called if the future running the indexer is dropped (or the task is aborted).
+    ///
+    /// The future is created from `&self`, but it is `Send`, so move the cloned handles into it if the context isn't `Sync`
+    /// ```ignore
+    /// impl LakeContextExt for BatchingContext {
+    ///     fn execute_before_run(&self, _block: &mut near_lake_primitives::block::Block) {}
+    ///     fn execute_after_run(&self) {}
+    ///
+    ///     fn finalize(&self) -> futures::future::BoxFuture<'_, ()> {
+    ///         Box::pin(async move {
+    ///             if let Err(err) = self.sink.flush().await {
+    ///                 eprintln!("Failed to flush the last batch: {}", err);
+    ///             }
+    ///         })
+    ///     }
+    /// }
+    /// ```
+    fn finalize(&self) -> futures::future::BoxFuture<'_, ()> {
+        Box::pin(async {})
+    }
+}
+
+impl<T: LakeContextExt + ?Sized> LakeContextExt for std::sync::Arc<T> {
+    fn execute_before_run(&self, block: &mut near_lake_primitives::block::Block) {
+        (**self).execute_before_run(block)
+    }
+
+    fn execute_after_run(&self) {
+        (**self).execute_after_run()
+    }
+
+    fn execute_after_run_with_scope(&self, scope: &near_lake_primitives::block::BlockScope) {
+        (**self).execute_after_run_with_scope(scope)
+    }
+
+    fn finalize(&self) -> futures::future::BoxFuture<'_, ()> {
+        (**self).finalize()
+    }
+}
+
+impl<T: LakeContextExt + ?Sized> LakeContextExt for Box<T> {
+    fn execute_before_run(&self, block: &mut near_lake_primitives::block::Block) {
+        (**self).execute_before_run(block)
+    }
+
+    fn execute_after_run(&self) {
+        (**self).execute_after_run()
+    }
+
+    fn execute_after_run_with_scope(&self, scope: &near_lake_primitives::block::BlockScope) {
+        (**self).execute_after_run_with_scope(scope)
+    }
+
+    fn finalize(&self) -> futures::future::BoxFuture<'_, ()> {
+        (**self).finalize()
+    }
+}
+
+/// Implements [LakeContextExt] for the tuple of the contexts, so the simple compositions don't need a named struct:
+/// `execute_before_run` is called in the order of the elements, `execute_after_run` and `finalize` in the reverse one
+macro_rules! impl_lake_context_for_tuple {
+    ($($index:tt => $context:ident),+; $($rev_index:tt),+) => {
+        impl<$($context: LakeContextExt),+> LakeContextExt for ($($context,)+) {
+            fn execute_before_run(&self, block: &mut near_lake_primitives::block::Block) {
+                $(self.$index.execute_before_run(block);)+
+            }
+
+            fn execute_after_run(&self) {
+                $(self.$rev_index.execute_after_run();)+
+            }
+
+            fn execute_after_run_with_scope(&self, scope: &near_lake_primitives::block::BlockScope) {
+                $(self.$rev_index.execute_after_run_with_scope(scope);)+
+            }
+
+            fn finalize(&self) -> futures::future::BoxFuture<'_, ()> {
+                let finalizers = [$(self.$rev_index.finalize()),+];
+                Box::pin(async move {
+                    for finalizer in finalizers {
+                        finalizer.await;
+                    }
+                })
+            }
+        }
+    };
+}
+
+impl_lake_context_for_tuple!(0 => A, 1 => B; 1, 0);
+impl_lake_context_for_tuple!(0 => A, 1 => B, 2 => C; 2, 1, 0);
+impl_lake_context_for_tuple!(0 => A, 1 => B, 2 => C, 3 => D; 3, 2, 1, 0);
+
+/// The support of the [LakeContext](crate::LakeContext) derive, not a public API.
+///
+/// The derive can't tell which fields implement [LakeContextExt], so it calls the hooks on every field wrapped into [Field](__derive::Field):
+/// the method resolution picks the [ContextField](__derive::ContextField) impl for the contexts
+/// and falls back to the no-op [PlainField](__derive::PlainField) one (through the autoref) for the plain data
+#[doc(hidden)]
+pub mod __derive {
+    pub use futures::future::BoxFuture;
+
+    use super::LakeContextExt;
+
+    pub struct Field<'a, T: ?Sized>(pub &'a T);
+
+    pub trait ContextField<'a> {
+        fn execute_before_run(&self, block: &mut near_lake_primitives::block::Block);
+        fn execute_after_run(&self);
+        fn execute_after_run_with_scope(&self, scope: &near_lake_primitives::block::BlockScope);
+        fn finalize(&self) -> BoxFuture<'a, ()>;
+    }
+
+    impl<'a, T: LakeContextExt + ?Sized> ContextField<'a> for Field<'a, T> {
+        fn execute_before_run(&self, block: &mut near_lake_primitives::block::Block) {
+            self.0.execute_before_run(block)
+        }
+
+        fn execute_after_run(&self) {
+            self.0.execute_after_run()
+        }
+
+        fn execute_after_run_with_scope(&self, scope: &near_lake_primitives::block::BlockScope) {
+            self.0.execute_after_run_with_scope(scope)
+        }
+
+        fn finalize(&self) -> BoxFuture<'a, ()> {
+            self.0.finalize()
+        }
+    }
+
+    pub trait PlainField<'a> {
+        fn execute_before_run(&self, _block: &mut near_lake_primitives::block::Block) {}
+        fn execute_after_run(&self) {}
+        fn execute_after_run_with_scope(&self, _scope: &near_lake_primitives::block::BlockScope) {}
+        fn finalize(&self) -> BoxFuture<'a, ()> {
+            Box::pin(async {})
+        }
+    }
+
+    impl<'a, T: ?Sized> PlainField<'a> for &Field<'a, T> {}
 }
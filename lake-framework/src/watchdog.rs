@@ -0,0 +1,81 @@
+//! Detection of the wedged stream, see [LakeBuilder::stall_timeout](crate::LakeBuilder::stall_timeout)
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use crate::types::{BlockHeight, Lake};
+
+/// Resolves once the stream has to be restarted: no block has been received for the
+/// [LakeBuilder::stall_timeout](crate::LakeBuilder::stall_timeout) while the handlers are idle, though the bucket
+/// has the newer blocks, and the [Lake::on_stall](crate::Lake::on_stall) callback (if any) agrees to restart.
+/// Never resolves if the timeout isn't set.
+///
+/// `last_activity` is the time a block has been received or handled last, `next_block_height`
+/// is the block height following the last received one (0 until the first block is received)
+pub(crate) async fn stalled(
+    config: &Lake,
+    last_activity: &Mutex<std::time::Instant>,
+    in_flight: &Mutex<BTreeSet<BlockHeight>>,
+    next_block_height: &AtomicU64,
+) {
+    let Some(stall_timeout) = config.stall_timeout else {
+        return futures::future::pending().await;
+    };
+    let block_provider = crate::provider::block_provider(config).await;
+
+    loop {
+        tokio::time::sleep(stall_timeout / 4).await;
+        let idle_for = last_activity.lock().unwrap().elapsed();
+        if idle_for < stall_timeout || !in_flight.lock().unwrap().is_empty() {
+            continue;
+        }
+
+        let awaited_block_height = match next_block_height.load(Ordering::Relaxed) {
+            0 => config.start_block_height,
+            next_block_height => next_block_height,
+        };
+        // the stream is idle for a reason if there are no newer blocks in the bucket
+        match block_provider
+            .latest_block_height(awaited_block_height)
+            .await
+        {
+            Ok(Some(_)) => {}
+            Ok(None) => {
+                *last_activity.lock().unwrap() = std::time::Instant::now();
+                continue;
+            }
+            Err(err) => {
+                tracing::warn!(
+                    target: crate::LAKE_FRAMEWORK,
+                    provider = block_provider.name(),
+                    block_height = awaited_block_height,
+                    error = %err,
+                    "Failed to check the bucket for the newer blocks",
+                );
+                continue;
+            }
+        }
+        // a block might have been received while probing the bucket
+        let idle_for = last_activity.lock().unwrap().elapsed();
+        if idle_for < stall_timeout || !in_flight.lock().unwrap().is_empty() {
+            continue;
+        }
+
+        tracing::warn!(
+            target: crate::LAKE_FRAMEWORK,
+            provider = block_provider.name(),
+            block_height = awaited_block_height,
+            "No blocks have been received for {:?} though the bucket has them",
+            idle_for,
+        );
+        let restart = config
+            .on_stall
+            .as_ref()
+            .map(|on_stall| (on_stall.0)(awaited_block_height, idle_for))
+            .unwrap_or(true);
+        if restart {
+            return;
+        }
+        *last_activity.lock().unwrap() = std::time::Instant::now();
+    }
+}
@@ -13,6 +13,18 @@ pub type ReceiptId = CryptoHash;
 pub type TransactionHash = CryptoHash;
 type Cache = SizedCache<ReceiptId, TransactionHash>;
 
+/// Fallback source of the parent transactions for the receipts that are not in the cache,
+/// e.g. because the transaction happened before the indexer started.
+///
+/// *Note*: NEAR RPC can't resolve the parent transaction by the receipt id alone
+/// (`EXPERIMENTAL_tx_status` requires the transaction hash), so the resolver is expected to query
+/// an archival source that keeps the receipt to transaction relation, e.g. your own indexer database.
+#[async_trait::async_trait]
+pub trait ParentTransactionResolver: std::fmt::Debug + Send + Sync {
+    /// Returns the parent transaction hash for a given receipt id, if known
+    async fn resolve(&self, receipt_id: &ReceiptId) -> Option<TransactionHash>;
+}
+
 #[derive(Debug, Builder)]
 #[builder(pattern = "owned")]
 pub struct ParentTransactionCache {
@@ -23,6 +35,8 @@ pub struct ParentTransactionCache {
     cache: std::sync::RwLock<Cache>,
     #[builder(setter(custom = true, name = "for_accounts"))]
     account_ids: Vec<AccountId>,
+    #[builder(setter(custom = true), default)]
+    fallback: Option<std::sync::Arc<dyn ParentTransactionResolver>>,
 }
 
 impl ParentTransactionCacheBuilder {
@@ -58,6 +72,15 @@ impl ParentTransactionCacheBuilder {
     }
 }
 
+impl ParentTransactionCacheBuilder {
+    /// Sets the [ParentTransactionResolver] to query when the receipt is not in the cache.
+    /// Used by [ParentTransactionCache::resolve_parent_transaction_hash] only.
+    pub fn fallback(mut self, resolver: impl ParentTransactionResolver + 'static) -> Self {
+        self.fallback = Some(Some(std::sync::Arc::new(resolver)));
+        self
+    }
+}
+
 impl LakeContextExt for ParentTransactionCache {
     /// The process to scan the [near_lake_primitives::Block](near_lake_framework::near_lake_primitives::block::Block) and update the cache
     /// with the new transactions and first expected receipts.
@@ -104,4 +127,20 @@ impl ParentTransactionCache {
         let mut cache = self.cache.write().unwrap();
         cache.cache_get(receipt_id).cloned()
     }
+
+    /// Returns the parent transaction hash for a given receipt id.
+    /// If the receipt id is not found in the cache, it queries the [fallback](ParentTransactionCacheBuilder::fallback)
+    /// resolver (if set) and caches the result.
+    pub async fn resolve_parent_transaction_hash(
+        &self,
+        receipt_id: &ReceiptId,
+    ) -> Option<TransactionHash> {
+        if let Some(parent_tx_hash) = self.get_parent_transaction_hash(receipt_id) {
+            return Some(parent_tx_hash);
+        }
+        let parent_tx_hash = self.fallback.as_ref()?.resolve(receipt_id).await?;
+        let mut cache = self.cache.write().unwrap();
+        cache.cache_set(*receipt_id, parent_tx_hash);
+        Some(parent_tx_hash)
+    }
 }
@@ -4,10 +4,11 @@ pub use near_indexer_primitives::{
 
 pub use types::{
     actions::{self, Action},
-    block::{self, Block, BlockHeader},
+    block::{self, Block, BlockHeader, BlockScope, ReceiptParent},
     delegate_actions::{self, DelegateAction},
     events::{self, Event, EventsTrait, RawEvent},
-    receipts::{self, Receipt, ReceiptKind},
+    receipts::{self, ExecutionOutcome, Receipt, ReceiptKind},
+    shards::{self, Shard},
     state_changes::{self, StateChange, StateChangeCause, StateChangeValue},
     transactions::{self, Transaction},
     ReceiptId,
@@ -29,23 +29,23 @@ impl ActionMetadata {
     }
 
     /// Returns the [AccountId] of the predecessor of the action.
-    pub fn predecessor_id(&self) -> AccountId {
-        self.predecessor_id.clone()
+    pub fn predecessor_id(&self) -> &AccountId {
+        &self.predecessor_id
     }
 
     /// Returns the [AccountId] of the receiver of the action.
-    pub fn receiver_id(&self) -> AccountId {
-        self.receiver_id.clone()
+    pub fn receiver_id(&self) -> &AccountId {
+        &self.receiver_id
     }
 
     /// Returns the [AccountId] of the signer of the action.
-    pub fn signer_id(&self) -> AccountId {
-        self.signer_id.clone()
+    pub fn signer_id(&self) -> &AccountId {
+        &self.signer_id
     }
 
     /// Returns the [PublicKey] of the signer of the action.
-    pub fn signer_public_key(&self) -> PublicKey {
-        self.signer_public_key.clone()
+    pub fn signer_public_key(&self) -> &PublicKey {
+        &self.signer_public_key
     }
 }
 
@@ -55,16 +55,16 @@ pub trait ActionMetaDataExt {
     fn receipt_id(&self) -> CryptoHash {
         self.metadata().receipt_id()
     }
-    fn predecessor_id(&self) -> AccountId {
+    fn predecessor_id(&self) -> &AccountId {
         self.metadata().predecessor_id()
     }
-    fn receiver_id(&self) -> AccountId {
+    fn receiver_id(&self) -> &AccountId {
         self.metadata().receiver_id()
     }
-    fn signer_id(&self) -> AccountId {
+    fn signer_id(&self) -> &AccountId {
         self.metadata().signer_id()
     }
-    fn signer_public_key(&self) -> PublicKey {
+    fn signer_public_key(&self) -> &PublicKey {
         self.metadata().signer_public_key()
     }
 }
@@ -134,6 +134,22 @@ impl Action {
     impl_as_action_for!(Delegate);
 }
 
+/// Deserializes the JSON arguments of the function call if they are not larger than `max_size` bytes.
+/// The nesting depth is limited by `serde_json` itself.
+pub(crate) fn args_json_with_limit<T: serde::de::DeserializeOwned>(
+    args: &[u8],
+    max_size: usize,
+) -> anyhow::Result<T> {
+    if args.len() > max_size {
+        anyhow::bail!(
+            "arguments are {} bytes long, which exceeds the limit of {} bytes",
+            args.len(),
+            max_size
+        );
+    }
+    Ok(serde_json::from_slice(args)?)
+}
+
 // Macro to implement ActionMetaDataExt trait for each Action variant.
 macro_rules! impl_action_metadata_ext {
     ($action:ident) => {
@@ -175,6 +191,16 @@ impl DeployContract {
     pub fn code(&self) -> &[u8] {
         &self.code
     }
+
+    /// Returns the size of the contract code in bytes.
+    pub fn code_size(&self) -> usize {
+        self.code.len()
+    }
+
+    /// Returns the contract code bytes if the code is not larger than `max_size` bytes, otherwise returns `None`.
+    pub fn code_with_limit(&self, max_size: usize) -> Option<&[u8]> {
+        (self.code.len() <= max_size).then_some(self.code.as_slice())
+    }
 }
 
 /// Structure representing the `FunctionCall` action.
@@ -198,6 +224,15 @@ impl FunctionCall {
         &self.args
     }
 
+    /// Deserializes the JSON arguments refusing the ones larger than `max_size` bytes,
+    /// to protect the indexer from the enormous payloads of the hostile contract calls.
+    pub fn args_json_with_limit<T: serde::de::DeserializeOwned>(
+        &self,
+        max_size: usize,
+    ) -> anyhow::Result<T> {
+        args_json_with_limit(&self.args, max_size)
+    }
+
     /// Returns the gas attached to this FunctionCall.
     pub fn gas(&self) -> Gas {
         self.gas
@@ -319,3 +354,18 @@ impl Delegate {
         &self.signature
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn decodes_args_within_limit() {
+        let args = br#"{"receiver_id":"alice.near","amount":"100"}"#;
+
+        let value: serde_json::Value = args_json_with_limit(args, args.len()).unwrap();
+        assert_eq!(value["receiver_id"], "alice.near");
+        assert!(args_json_with_limit::<serde_json::Value>(args, args.len() - 1).is_err());
+        assert!(args_json_with_limit::<serde_json::Value>(b"not json", 1024).is_err());
+    }
+}
@@ -1,11 +1,18 @@
 use std::collections::HashMap;
 
 use super::actions::{self, ActionMetaDataExt};
+pub use super::block_scope::BlockScope;
 use super::events::{self, EventsTrait};
+pub use super::extensions::Extensions;
 use super::receipts::{self};
+use super::shards;
 use super::state_changes;
 use super::transactions;
-use crate::near_indexer_primitives::{types::AccountId, views, CryptoHash, StreamerMessage};
+use crate::near_indexer_primitives::{
+    near_primitives::merkle,
+    types::{AccountId, ShardId},
+    views, CryptoHash, StreamerMessage,
+};
 
 /// A structure that represents an entire block in the NEAR blockchain.
 /// It is a high-level structure that is built on top of the low-level [StreamerMessage] structure.
@@ -27,11 +34,18 @@ use crate::near_indexer_primitives::{types::AccountId, views, CryptoHash, Stream
 pub struct Block {
     streamer_message: StreamerMessage,
     executed_receipts: Vec<receipts::Receipt>,
+    /// Index of the executed receipts by the receipt id
+    receipts_index: HashMap<super::ReceiptId, usize>,
     postponed_receipts: Vec<receipts::Receipt>,
     transactions: Vec<transactions::Transaction>,
     actions: Vec<actions::Action>,
+    /// Index of the actions by the receipt id, the actions of a receipt are stored contiguously
+    actions_index: HashMap<super::ReceiptId, std::ops::Range<usize>>,
     events: HashMap<super::ReceiptId, Vec<events::Event>>,
     state_changes: Vec<state_changes::StateChange>,
+    shards: Vec<shards::Shard>,
+    extensions: Extensions,
+    scope: BlockScope,
 }
 
 impl Block {
@@ -77,17 +91,25 @@ impl Block {
     /// along with the corresponding [near_primitives::views::ReceiptView].
     pub fn receipts(&mut self) -> impl Iterator<Item = &receipts::Receipt> {
         if self.executed_receipts.is_empty() {
-            self.executed_receipts = self
-                .streamer_message
-                .shards
-                .iter()
-                .flat_map(|shard| shard.receipt_execution_outcomes.iter())
-                .map(Into::into)
-                .collect();
+            self.build_receipts_cache();
         }
         self.executed_receipts.iter()
     }
 
+    /// The parallel version of [Block::receipts] running on the [rayon] thread pool,
+    /// for the CPU-heavy processing of every receipt (hashing, decoding) in the large blocks
+    #[cfg(feature = "rayon")]
+    pub fn par_receipts(
+        &mut self,
+    ) -> impl rayon::iter::IndexedParallelIterator<Item = &receipts::Receipt> {
+        use rayon::iter::IntoParallelRefIterator;
+
+        if self.executed_receipts.is_empty() {
+            self.build_receipts_cache();
+        }
+        self.executed_receipts.par_iter()
+    }
+
     /// Returns an iterator of [Receipt](crate::receipts::Receipt) included yet not executed in the [Block].
     ///
     /// [Receipts](crate::receipts::Receipt) included on the chain but not executed yet are called "postponed",
@@ -120,15 +142,7 @@ impl Block {
     /// [Receipts](crate::receipts::Receipt) are more important.
     pub fn transactions(&mut self) -> impl Iterator<Item = &transactions::Transaction> {
         if self.transactions.is_empty() {
-            self.transactions = self
-                .streamer_message
-                .shards
-                .iter()
-                .filter_map(|shard| shard.chunk.as_ref().map(|chunk| chunk.transactions.iter()))
-                .flatten()
-                .map(TryInto::try_into)
-                .filter_map(|transactions| transactions.ok())
-                .collect();
+            self.build_transactions_cache();
         }
         self.transactions.iter()
     }
@@ -154,6 +168,20 @@ impl Block {
         self.actions.iter()
     }
 
+    /// The parallel version of [Block::actions] running on the [rayon] thread pool,
+    /// for the CPU-heavy processing of every action (e.g. decoding the function call arguments) in the large blocks
+    #[cfg(feature = "rayon")]
+    pub fn par_actions(
+        &mut self,
+    ) -> impl rayon::iter::IndexedParallelIterator<Item = &actions::Action> {
+        use rayon::iter::IntoParallelRefIterator;
+
+        if self.actions.is_empty() {
+            self.build_actions_cache();
+        }
+        self.actions.par_iter()
+    }
+
     /// Returns an iterator of the [Events](crate::events::Event) emitted in the [Block]
     pub fn events(&mut self) -> impl Iterator<Item = &events::Event> {
         if self.events.is_empty() {
@@ -176,18 +204,100 @@ impl Block {
         self.state_changes.iter()
     }
 
+    /// Returns an iterator of the [Shards](crate::shards::Shard) of the [Block] with the entities split by the shards they belong to.
+    ///
+    /// **Heads up!** Most of the indexers don't need to care about the shards, see the notes on the [Block]
+    pub fn shards(&mut self) -> impl Iterator<Item = &shards::Shard> {
+        if self.shards.is_empty() {
+            self.shards = self
+                .streamer_message
+                .shards
+                .iter()
+                .map(Into::into)
+                .collect();
+        }
+        self.shards.iter()
+    }
+
+    /// Returns the headers of the chunks of all the shards in the [Block], including the ones of the shards
+    /// with the chunk missing (their headers are repeated from the previous blocks).
+    ///
+    /// **Heads up!** This methods returns types defined in the `near-primitives` crate as is.
+    /// It is a subject of change in the future (once we define the corresponding Lake Primitives types)
+    pub fn chunk_headers(&self) -> &[views::ChunkHeaderView] {
+        &self.streamer_message.block.chunks
+    }
+
+    /// Returns the `outcome_root` of the chunk header of the shard in the [Block].
+    ///
+    /// It commits to the outcomes of the *previous* chunk of the shard, see [ExecutionOutcome::verify](crate::receipts::ExecutionOutcome::verify)
+    pub fn chunk_outcome_root(&self, shard_id: ShardId) -> Option<CryptoHash> {
+        self.chunk_headers()
+            .iter()
+            .find(|chunk_header| chunk_header.shard_id == shard_id)
+            .map(|chunk_header| chunk_header.outcome_root)
+    }
+
+    /// Verifies that the `outcome_root` of the [BlockHeader] is the Merkle root of the `outcome_root`s of the chunk headers,
+    /// so the chunk outcome roots can be trusted as much as the block hash is
+    pub fn verify_chunk_outcome_roots(&self) -> bool {
+        let chunk_outcome_roots: Vec<CryptoHash> = self
+            .chunk_headers()
+            .iter()
+            .map(|chunk_header| chunk_header.outcome_root)
+            .collect();
+        merkle::merklize(&chunk_outcome_roots).0 == self.streamer_message.block.header.outcome_root
+    }
+
+    /// Returns the [Extensions] map with the data attached to the [Block], e.g. by the enrichment functions
+    /// registered with `Lake::enrich` in `near-lake-framework`
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    /// Returns the mutable [Extensions] map to attach arbitrary data to the [Block]
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
+    }
+
+    /// Returns the [BlockScope] shared by the hooks of the contexts and the handler while the [Block] is processed
+    pub fn scope(&self) -> &BlockScope {
+        &self.scope
+    }
+
     /// Helper to get all the [Actions](crate::actions::Action) by the single [Receipt](crate::receipts::Receipt)
     ///
     /// **Heads up!** This methods searches for the actions in the current [Block] only.
     pub fn actions_by_receipt_id<'a>(
         &'a mut self,
-        receipt_id: &'a super::ReceiptId,
-    ) -> impl Iterator<Item = &actions::Action> + 'a {
-        self.actions()
-            .filter(move |action| &action.receipt_id() == receipt_id)
+        receipt_id: &super::ReceiptId,
+    ) -> impl Iterator<Item = &'a actions::Action> + 'a {
+        if self.actions.is_empty() {
+            self.build_actions_cache();
+        }
+        let range = self
+            .actions_index
+            .get(receipt_id)
+            .cloned()
+            .unwrap_or_default();
+        self.actions[range].iter()
+    }
+
+    /// Helper to iterate over the [Events](crate::events::Event) emitted by the specific [Receipt](crate::receipts::Receipt)
+    /// without cloning them out of the cache
+    pub fn events_for(
+        &mut self,
+        receipt_id: &super::ReceiptId,
+    ) -> impl Iterator<Item = &events::Event> {
+        if self.events.is_empty() {
+            self.build_events_hashmap();
+        }
+        self.events.get(receipt_id).into_iter().flatten()
     }
 
     /// Helper to get all the [Events](crate::events::Event) emitted by the specific [Receipt](crate::receipts::Receipt)
+    ///
+    /// **Heads up!** The events are cloned, use [Block::events_for] to borrow them instead
     pub fn events_by_receipt_id(&mut self, receipt_id: &super::ReceiptId) -> Vec<events::Event> {
         if self.events.is_empty() {
             self.build_events_hashmap();
@@ -203,22 +313,115 @@ impl Block {
     pub fn events_by_contract_id<'a>(
         &'a mut self,
         account_id: &'a crate::near_indexer_primitives::types::AccountId,
-    ) -> impl Iterator<Item = &events::Event> + 'a {
+    ) -> impl Iterator<Item = &'a events::Event> + 'a {
+        self.events()
+            .filter(move |event| event.is_emitted_by_contract(account_id))
+    }
+
+    /// Helper to get all the [Events](crate::events::Event) of the specific standard, e.g. `nep171`
+    pub fn events_by_standard<'a>(
+        &'a mut self,
+        standard: &'a str,
+    ) -> impl Iterator<Item = &'a events::Event> + 'a {
         self.events()
-            .filter(move |event| event.is_emitted_by_contract(&account_id.clone()))
+            .filter(move |event| event.standard() == standard)
+    }
+
+    /// Helper to get all the [Events](crate::events::Event) with the specific name, e.g. `nft_mint`
+    pub fn events_named<'a>(
+        &'a mut self,
+        name: &'a str,
+    ) -> impl Iterator<Item = &'a events::Event> + 'a {
+        self.events().filter(move |event| event.event() == name)
+    }
+
+    /// Helper to find the [Transaction](crate::transactions::Transaction) or the [Receipt](crate::receipts::Receipt) that has produced
+    /// the given [Receipt](crate::receipts::Receipt), following the produced receipt ids of the execution outcomes.
+    ///
+    /// **Heads up!** This methods searches for the parent in the current [Block] only. The receipts are usually executed
+    /// in the block after the one they are produced in, so the parent is found here for the local receipts
+    /// (the ones with the same signer and receiver) and the receipts executed right away.
+    /// Use the `near-lake-parent-transaction-cache` crate to resolve the parent across the blocks.
+    pub fn receipt_parent(&mut self, receipt_id: &super::ReceiptId) -> Option<ReceiptParent<'_>> {
+        if self.transactions.is_empty() {
+            self.build_transactions_cache();
+        }
+        if self.executed_receipts.is_empty() {
+            self.build_receipts_cache();
+        }
+
+        if let Some(transaction) = self.transactions.iter().find(|transaction| {
+            transaction
+                .execution_outcome()
+                .receipt_ids()
+                .contains(receipt_id)
+        }) {
+            return Some(ReceiptParent::Transaction(transaction));
+        }
+        self.executed_receipts
+            .iter()
+            .find(|receipt| {
+                receipt
+                    .execution_outcome()
+                    .map_or(false, |execution_outcome| {
+                        execution_outcome.receipt_ids().contains(receipt_id)
+                    })
+            })
+            .map(ReceiptParent::Receipt)
     }
 
     /// Helper to get a specific [Receipt](crate::receipts::Receipt) by the [ReceiptId](crate::types::ReceiptId)
     pub fn receipt_by_id(&mut self, receipt_id: &super::ReceiptId) -> Option<&receipts::Receipt> {
-        self.receipts()
-            .find(|receipt| &receipt.receipt_id() == receipt_id)
+        if self.executed_receipts.is_empty() {
+            self.build_receipts_cache();
+        }
+        self.receipts_index
+            .get(receipt_id)
+            .map(|index| &self.executed_receipts[*index])
     }
 }
 
 impl Block {
+    // Internal method to build the cache of executed receipts on demand
+    fn build_receipts_cache(&mut self) {
+        self.executed_receipts = self
+            .streamer_message
+            .shards
+            .iter()
+            .flat_map(|shard| shard.receipt_execution_outcomes.iter())
+            .map(Into::into)
+            .collect();
+        self.receipts_index = self
+            .executed_receipts
+            .iter()
+            .enumerate()
+            .map(|(index, receipt)| (receipt.receipt_id(), index))
+            .collect();
+    }
+
+    // Internal method to build the cache of transactions on demand
+    fn build_transactions_cache(&mut self) {
+        self.transactions = self
+            .streamer_message
+            .shards
+            .iter()
+            .filter_map(|shard| shard.chunk.as_ref().map(|chunk| chunk.transactions.iter()))
+            .flatten()
+            .map(TryInto::try_into)
+            .filter_map(|transactions| transactions.ok())
+            .collect();
+    }
+
     // Internal method to build the cache of actions on demand
     fn build_actions_cache(&mut self) {
         self.actions = self.actions_from_streamer_message().to_vec();
+        self.actions_index = HashMap::new();
+        for (index, action) in self.actions.iter().enumerate() {
+            self.actions_index
+                .entry(action.receipt_id())
+                .or_insert(index..index)
+                .end = index + 1;
+        }
     }
 
     // Internal method to build the cache of events on demand
@@ -235,15 +438,29 @@ impl From<StreamerMessage> for Block {
         Self {
             streamer_message,
             executed_receipts: vec![],
+            receipts_index: HashMap::new(),
             postponed_receipts: vec![],
             transactions: vec![],
             actions: vec![],
+            actions_index: HashMap::new(),
             events: HashMap::new(),
             state_changes: vec![],
+            shards: vec![],
+            extensions: Extensions::default(),
+            scope: BlockScope::default(),
         }
     }
 }
 
+/// The parent of the [Receipt](crate::receipts::Receipt) found with [Block::receipt_parent]
+#[derive(Debug, Clone, Copy)]
+pub enum ReceiptParent<'a> {
+    /// The receipt is produced by the conversion of the [Transaction](crate::transactions::Transaction)
+    Transaction(&'a transactions::Transaction),
+    /// The receipt is produced by the execution of another [Receipt](crate::receipts::Receipt)
+    Receipt(&'a receipts::Receipt),
+}
+
 /// Replacement for [`BlockHeaderView`](near_primitives::views::BlockHeaderView) from `near-primitives`. Shrank and simplified.
 /// We were trying to leave only the fields indexer developers might be interested in.
 ///
@@ -283,7 +500,12 @@ impl BlockHeader {
     }
 
     /// The [AccountId](crate::near_indexer_primitives::types::AccountId) of the author of the [Block]
-    pub fn author(&self) -> AccountId {
+    pub fn author(&self) -> &AccountId {
+        &self.author
+    }
+
+    /// The owned [AccountId](crate::near_indexer_primitives::types::AccountId) of the author of the [Block]
+    pub fn author_owned(&self) -> AccountId {
         self.author.clone()
     }
 
@@ -307,6 +529,11 @@ impl BlockHeader {
         self.gas_price
     }
 
+    /// The fee (in yoctoNEAR) for the given amount of gas at the gas price of the [Block]
+    pub fn fee_for_gas(&self, gas: u64) -> u128 {
+        self.gas_price.saturating_mul(u128::from(gas))
+    }
+
     /// The total supply of the [Block]
     pub fn total_supply(&self) -> u128 {
         self.total_supply
@@ -331,8 +558,8 @@ impl BlockHeader {
     ///
     /// **Heads up!** This methods returns types defined in the `near-primitives` crate as is.
     /// It is a subject of change in the future (once we define the corresponding Lake Primitives types)
-    pub fn validator_proposals(&self) -> Vec<views::validator_stake_view::ValidatorStakeView> {
-        self.validator_proposals.clone()
+    pub fn validator_proposals(&self) -> &[views::validator_stake_view::ValidatorStakeView] {
+        &self.validator_proposals
     }
 }
 
@@ -355,3 +582,63 @@ impl From<&StreamerMessage> for BlockHeader {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn receipt_id(value: &str) -> crate::ReceiptId {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn finds_receipt_parents() {
+        let mut block = crate::types::fixture_block();
+
+        match block.receipt_parent(&receipt_id("AQDQ9G4QpK7x2inV3GieVEbqeoCGF9nmvrViQ2UgEXDQ")) {
+            Some(ReceiptParent::Transaction(transaction)) => assert_eq!(
+                transaction.transaction_hash(),
+                receipt_id("EZnJpyJDnkwnadB1V8PqjVMx7oe2zLhUMtJ8v6EUh1NQ")
+            ),
+            parent => panic!("Unexpected parent: {:?}", parent),
+        }
+        // the refund produced by the failed receipt
+        match block.receipt_parent(&receipt_id("5rc8UEhD4hmNQ3pJJM5Xc3VHeLXpCQqkA3ep8ag4aaDA")) {
+            Some(ReceiptParent::Receipt(receipt)) => assert_eq!(
+                receipt.receipt_id(),
+                receipt_id("AQDQ9G4QpK7x2inV3GieVEbqeoCGF9nmvrViQ2UgEXDQ")
+            ),
+            parent => panic!("Unexpected parent: {:?}", parent),
+        }
+        assert!(block.receipt_parent(&CryptoHash::default()).is_none());
+    }
+
+    #[test]
+    fn verifies_chunk_outcome_roots() {
+        let block = crate::types::fixture_block();
+
+        assert_eq!(block.chunk_outcome_root(0), Some(CryptoHash::default()));
+        assert_eq!(block.chunk_outcome_root(1), None);
+        assert!(block.verify_chunk_outcome_roots());
+    }
+
+    #[test]
+    fn splits_block_by_shards() {
+        let mut block = crate::types::fixture_block();
+        let shards: Vec<_> = block.shards().cloned().collect();
+
+        assert_eq!(shards.len(), 1);
+        assert_eq!(shards[0].shard_id(), 0);
+        assert_eq!(
+            shards[0].chunk_hash(),
+            Some(
+                "7Ewp1AnL6o29UXLW2up9miQBdSaKxCnfRyhMGt9G4epN"
+                    .parse()
+                    .unwrap()
+            )
+        );
+        assert_eq!(shards[0].transactions().len(), 1);
+        assert_eq!(shards[0].receipts().len(), 1);
+        assert!(shards[0].postponed_receipts().is_empty());
+    }
+}
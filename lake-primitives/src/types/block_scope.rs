@@ -0,0 +1,92 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// A type map of the data shared within the processing of a single [Block](crate::block::Block):
+/// the before-hooks of the contexts populate it (e.g. with the resolved parent transaction hashes or the decoded events),
+/// the handler reads it with [Block::scope](crate::block::Block::scope) and the after-hooks of the contexts receive it too,
+/// so the intra-block data doesn't have to live in the long-lived fields of the contexts.
+///
+/// The scope is created per block and dropped once the block is processed. The clones share the same map,
+/// so it is mutated through the shared reference. Every type can be stored once, so wrap the values into your own types
+/// to avoid collisions. The closures are called with the map locked, so they must not access the scope themselves.
+/// ```
+/// use near_lake_primitives::block::BlockScope;
+///
+/// #[derive(Clone)]
+/// struct MintedTokens(Vec<String>);
+///
+/// let scope = BlockScope::default();
+/// scope.insert(MintedTokens(vec!["token-1".to_string()]));
+/// scope.with_mut(|minted: &mut MintedTokens| minted.0.push("token-2".to_string()));
+/// assert_eq!(scope.get::<MintedTokens>().map(|minted| minted.0.len()), Some(2));
+/// ```
+#[derive(Clone, Default)]
+pub struct BlockScope {
+    map: Arc<Mutex<HashMap<TypeId, Box<dyn Any + Send + Sync>>>>,
+}
+
+impl BlockScope {
+    /// Inserts the value, returning the previous value of the same type if any
+    pub fn insert<T: Send + Sync + 'static>(&self, value: T) -> Option<T> {
+        self.lock()
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|previous| previous.downcast().ok().map(|previous| *previous))
+    }
+
+    /// Returns a copy of the value of the type if any
+    pub fn get<T: Clone + Send + Sync + 'static>(&self) -> Option<T> {
+        self.with(T::clone)
+    }
+
+    /// Calls the closure with a reference to the value of the type if any
+    pub fn with<T: Send + Sync + 'static, R>(&self, f: impl FnOnce(&T) -> R) -> Option<R> {
+        self.lock()
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref())
+            .map(f)
+    }
+
+    /// Calls the closure with a mutable reference to the value of the type if any
+    pub fn with_mut<T: Send + Sync + 'static, R>(&self, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        self.lock()
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_mut())
+            .map(f)
+    }
+
+    /// Removes the value of the type and returns it if any
+    pub fn remove<T: Send + Sync + 'static>(&self) -> Option<T> {
+        self.lock()
+            .remove(&TypeId::of::<T>())
+            .and_then(|value| value.downcast().ok().map(|value| *value))
+    }
+
+    /// Whether the scope has a value of the type
+    pub fn contains<T: Send + Sync + 'static>(&self) -> bool {
+        self.lock().contains_key(&TypeId::of::<T>())
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// A panicked closure doesn't leave the map itself in an inconsistent state, so the poisoned lock is taken over
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<TypeId, Box<dyn Any + Send + Sync>>> {
+        self.map
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl std::fmt::Debug for BlockScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BlockScope")
+            .field("len", &self.len())
+            .finish_non_exhaustive()
+    }
+}
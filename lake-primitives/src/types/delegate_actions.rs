@@ -103,6 +103,16 @@ impl DelegateDeployContract {
     pub fn code(&self) -> &[u8] {
         &self.code
     }
+
+    /// Returns the size of the contract code in bytes.
+    pub fn code_size(&self) -> usize {
+        self.code.len()
+    }
+
+    /// Returns the bytes of the contract code if the code is not larger than `max_size` bytes, otherwise returns `None`.
+    pub fn code_with_limit(&self, max_size: usize) -> Option<&[u8]> {
+        (self.code.len() <= max_size).then_some(self.code.as_slice())
+    }
 }
 
 /// Similarly to [FunctionCall](super::actions::FunctionCall), this struct represents the `FunctionCall` action that is delegated.
@@ -125,6 +135,15 @@ impl DelegateFunctionCall {
         &self.args
     }
 
+    /// Deserializes the JSON arguments refusing the ones larger than `max_size` bytes,
+    /// see [FunctionCall::args_json_with_limit](super::actions::FunctionCall::args_json_with_limit).
+    pub fn args_json_with_limit<T: serde::de::DeserializeOwned>(
+        &self,
+        max_size: usize,
+    ) -> anyhow::Result<T> {
+        super::actions::args_json_with_limit(&self.args, max_size)
+    }
+
     /// Returns the amount of gas that is being used for the method call.
     pub fn gas(&self) -> Gas {
         self.gas
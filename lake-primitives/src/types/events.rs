@@ -93,8 +93,8 @@ impl EventsTrait<Receipt> for Receipt {
             .filter_map(|log| RawEvent::from_log(log).ok())
             .map(|raw_event| Event {
                 related_receipt_id: self.receipt_id(),
-                receiver_id: self.receiver_id(),
-                predecessor_id: self.predecessor_id(),
+                receiver_id: self.receiver_id().clone(),
+                predecessor_id: self.predecessor_id().clone(),
                 raw_event,
             })
             .collect()
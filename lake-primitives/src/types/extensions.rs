@@ -0,0 +1,66 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+
+/// A type map of the arbitrary data attached to the [Block](crate::block::Block), e.g. by the enrichment
+/// functions that run before the handler.
+///
+/// Every type can be stored once, so wrap the values into your own types to avoid collisions.
+/// ```
+/// use near_lake_primitives::block::Extensions;
+///
+/// struct TokenSymbol(String);
+///
+/// let mut extensions = Extensions::default();
+/// extensions.insert(TokenSymbol("wNEAR".to_string()));
+/// assert_eq!(extensions.get::<TokenSymbol>().map(|symbol| symbol.0.as_str()), Some("wNEAR"));
+/// ```
+#[derive(Default)]
+pub struct Extensions {
+    map: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+}
+
+impl Extensions {
+    /// Inserts the value, returning the previous value of the same type if any
+    pub fn insert<T: Send + Sync + 'static>(&mut self, value: T) -> Option<T> {
+        self.map
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|previous| previous.downcast().ok().map(|previous| *previous))
+    }
+
+    /// Returns a reference to the value of the type if any
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.map
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref())
+    }
+
+    /// Returns a mutable reference to the value of the type if any
+    pub fn get_mut<T: Send + Sync + 'static>(&mut self) -> Option<&mut T> {
+        self.map
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_mut())
+    }
+
+    /// Removes the value of the type and returns it if any
+    pub fn remove<T: Send + Sync + 'static>(&mut self) -> Option<T> {
+        self.map
+            .remove(&TypeId::of::<T>())
+            .and_then(|value| value.downcast().ok().map(|value| *value))
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+
+impl std::fmt::Debug for Extensions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Extensions")
+            .field("len", &self.map.len())
+            .finish_non_exhaustive()
+    }
+}
@@ -114,7 +114,7 @@ impl Action {
                 .execution_outcome
                 .outcome
                 .receipt_ids
-                .get(0)
+                .first()
                 .ok_or("Transaction conversion ReceiptId is missing")?,
             predecessor_id: transaction_with_outcome.transaction.signer_id.clone(),
             receiver_id: transaction_with_outcome.transaction.receiver_id.clone(),
@@ -1,12 +1,26 @@
 pub mod actions;
 pub mod block;
+mod block_scope;
 pub mod delegate_actions;
 pub mod events;
+mod extensions;
 mod impl_actions;
 pub mod receipts;
+pub mod shards;
 pub mod state_changes;
 pub mod transactions;
 
 /// Since both [transactions::Transaction] hash and [receipts::Receipt] id are the [crate::CryptoHash] type,
 /// we use this type alias to make the code more readable.
 pub type ReceiptId = near_indexer_primitives::CryptoHash;
+
+/// The [Block](block::Block) of the `fixtures/streamer_message.json`: a sandbox block with a single shard,
+/// a transaction of the expired delegate action and its local receipt executed in the same block
+#[cfg(test)]
+pub(crate) fn fixture_block() -> block::Block {
+    serde_json::from_str::<crate::StreamerMessage>(include_str!(
+        "../../fixtures/streamer_message.json"
+    ))
+    .unwrap()
+    .into()
+}
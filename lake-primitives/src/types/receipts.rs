@@ -1,5 +1,7 @@
 use crate::near_indexer_primitives::{
-    types::AccountId, views, CryptoHash, IndexerExecutionOutcomeWithReceipt,
+    near_primitives::merkle::{self, MerklePathItem},
+    types::AccountId,
+    views, CryptoHash, IndexerExecutionOutcomeWithReceipt,
 };
 
 /// Simplified representation of the `Receipt`.
@@ -21,8 +23,8 @@ pub struct Receipt {
     receiver_id: AccountId,
     predecessor_id: AccountId,
     status: ExecutionStatus,
-    execution_outcome_id: Option<CryptoHash>,
-    logs: Vec<String>,
+    execution_outcome: Option<ExecutionOutcome>,
+    gas_price: Option<u128>,
 }
 
 impl Receipt {
@@ -40,20 +42,68 @@ impl Receipt {
     }
 
     /// Returns the [AccountId] of the receiver of the receipt.
-    pub fn receiver_id(&self) -> AccountId {
+    pub fn receiver_id(&self) -> &AccountId {
+        &self.receiver_id
+    }
+
+    /// Returns the owned [AccountId] of the receiver of the receipt.
+    pub fn receiver_id_owned(&self) -> AccountId {
         self.receiver_id.clone()
     }
 
     /// Returns the [AccountId] of the predecessor of the receipt.
-    pub fn predecessor_id(&self) -> AccountId {
+    pub fn predecessor_id(&self) -> &AccountId {
+        &self.predecessor_id
+    }
+
+    /// Returns the owned [AccountId] of the predecessor of the receipt.
+    pub fn predecessor_id_owned(&self) -> AccountId {
         self.predecessor_id.clone()
     }
 
+    /// Returns an iterator of the [Actions](super::actions::Action) of the receipt. Empty for the Data receipts.
+    ///
+    /// The actions are looked up in the index of the actions of the [Block](super::block::Block) the receipt belongs to,
+    /// the same as [Block::actions_by_receipt_id](super::block::Block::actions_by_receipt_id) does
+    pub fn actions<'a>(
+        &self,
+        block: &'a mut super::block::Block,
+    ) -> impl Iterator<Item = &'a super::actions::Action> + 'a {
+        block.actions_by_receipt_id(&self.receipt_id)
+    }
+
+    /// Returns true if the receipt is the refund issued by the `system` account
+    /// (the unused prepaid gas, the pessimistic gas price surplus or the deposit of the failed receipt).
+    pub fn is_refund(&self) -> bool {
+        self.predecessor_id.as_str() == "system"
+    }
+
+    /// Returns the gas price the gas of the Action receipt was purchased at, `None` for the Data receipts.
+    ///
+    /// **Heads up!** This is the *pessimistic* gas price the signer has been charged upfront,
+    /// not the price the gas is actually burnt at. See [ExecutionOutcome::fee] for the actual fee.
+    pub fn gas_price(&self) -> Option<u128> {
+        self.gas_price
+    }
+
+    /// Returns the amount of the tokens (in yoctoNEAR) refunded to the signer because the gas was burnt
+    /// at the lower price than the pessimistic [Receipt::gas_price] it was purchased at.
+    ///
+    /// `None` for the Data and the postponed receipts. The unused prepaid gas is refunded on top of it.
+    pub fn gas_price_refund(&self) -> Option<u128> {
+        let execution_outcome = self.execution_outcome.as_ref()?;
+        Some(
+            self.gas_price?
+                .saturating_mul(u128::from(execution_outcome.gas_burnt()))
+                .saturating_sub(execution_outcome.tokens_burnt()),
+        )
+    }
+
     /// Returns the [ExecutionStatus] of the corresponding ExecutionOutcome.
     ///
     /// Note that the status will be `Postponed` for the receipts that are included in the block but not executed yet.
-    pub fn status(&self) -> ExecutionStatus {
-        self.status.clone()
+    pub fn status(&self) -> &ExecutionStatus {
+        &self.status
     }
 
     /// Returns the [CryptoHash] id of the corresponding ExecutionOutcome if it exists.
@@ -61,13 +111,25 @@ impl Receipt {
     /// Note that this is an optional field because the ExecutionOutcome might not be available
     /// if the [Receipt] is "postponed" (included in the block but not executed yet)
     pub fn execution_outcome_id(&self) -> Option<CryptoHash> {
-        self.execution_outcome_id
+        self.execution_outcome
+            .as_ref()
+            .map(|execution_outcome| execution_outcome.id())
+    }
+
+    /// Returns the corresponding [ExecutionOutcome] if it exists.
+    ///
+    /// Note that the ExecutionOutcome is not available if the [Receipt] is "postponed" (included in the block but not executed yet)
+    pub fn execution_outcome(&self) -> Option<&ExecutionOutcome> {
+        self.execution_outcome.as_ref()
     }
 
     /// Returns the logs of the corresponding ExecutionOutcome.
-    /// Might be an empty Vec if the ExecutionOutcome is not available.
-    pub fn logs(&self) -> Vec<String> {
-        self.logs.clone()
+    /// Might be empty if the ExecutionOutcome is not available.
+    pub fn logs(&self) -> &[String] {
+        self.execution_outcome
+            .as_ref()
+            .map(|execution_outcome| execution_outcome.logs())
+            .unwrap_or_default()
     }
 }
 
@@ -78,15 +140,9 @@ impl From<&IndexerExecutionOutcomeWithReceipt> for Receipt {
             receipt_id: outcome_with_receipt.receipt.receipt_id,
             receiver_id: outcome_with_receipt.receipt.receiver_id.clone(),
             predecessor_id: outcome_with_receipt.receipt.predecessor_id.clone(),
-            execution_outcome_id: Some(outcome_with_receipt.execution_outcome.id),
-            logs: outcome_with_receipt
-                .execution_outcome
-                .outcome
-                .logs
-                .iter()
-                .map(Clone::clone)
-                .collect(),
             status: (&outcome_with_receipt.execution_outcome.outcome.status).into(),
+            execution_outcome: Some((&outcome_with_receipt.execution_outcome).into()),
+            gas_price: action_gas_price(&outcome_with_receipt.receipt.receipt),
         }
     }
 }
@@ -99,8 +155,135 @@ impl From<&views::ReceiptView> for Receipt {
             receiver_id: receipt.receiver_id.clone(),
             predecessor_id: receipt.predecessor_id.clone(),
             status: ExecutionStatus::Postponed,
-            execution_outcome_id: None,
-            logs: vec![],
+            execution_outcome: None,
+            gas_price: action_gas_price(&receipt.receipt),
+        }
+    }
+}
+
+fn action_gas_price(receipt_enum: &views::ReceiptEnumView) -> Option<u128> {
+    match receipt_enum {
+        views::ReceiptEnumView::Action { gas_price, .. } => Some(*gas_price),
+        views::ReceiptEnumView::Data { .. } => None,
+    }
+}
+
+/// High-level representation of the `ExecutionOutcome` of the [Receipt] or the [Transaction](crate::transactions::Transaction).
+///
+/// This is a simplification from the [near_primitives::views::ExecutionOutcomeWithIdView]. Unlike the rest of the getters
+/// on the [Receipt] and the [Transaction](crate::transactions::Transaction), it exposes the ids of the receipts produced by the execution
+/// along with the burnt gas and tokens.
+#[derive(Debug, Clone)]
+pub struct ExecutionOutcome {
+    /// The copy of the original view without the gas profile, shared by the clones.
+    /// The hash of the outcome to verify the proof against is calculated out of it on demand
+    outcome_with_id: std::sync::Arc<views::ExecutionOutcomeWithIdView>,
+    status: ExecutionStatus,
+}
+
+impl ExecutionOutcome {
+    /// Returns the [CryptoHash] id of the ExecutionOutcome.
+    /// It is the id of the [Receipt] or the hash of the [Transaction](crate::transactions::Transaction) the outcome belongs to.
+    pub fn id(&self) -> CryptoHash {
+        self.outcome_with_id.id
+    }
+
+    /// Returns the [CryptoHash] of the block the ExecutionOutcome is included in.
+    pub fn block_hash(&self) -> CryptoHash {
+        self.outcome_with_id.block_hash
+    }
+
+    /// Returns the [AccountId] the execution has happened on.
+    pub fn executor_id(&self) -> &AccountId {
+        &self.outcome_with_id.outcome.executor_id
+    }
+
+    /// Returns the amount of the gas burnt by the execution.
+    pub fn gas_burnt(&self) -> u64 {
+        self.outcome_with_id.outcome.gas_burnt
+    }
+
+    /// Returns the amount of the tokens (in yoctoNEAR) burnt by the execution.
+    pub fn tokens_burnt(&self) -> u128 {
+        self.outcome_with_id.outcome.tokens_burnt
+    }
+
+    /// Returns the actual fee (in yoctoNEAR) paid for the execution.
+    ///
+    /// It is the burnt gas charged at the gas price of the block the execution has happened in. Don't multiply the
+    /// [ExecutionOutcome::gas_burnt] by the [Receipt::gas_price], the signer gets the difference back
+    /// (see [Receipt::gas_price_refund]). The fee of the whole transaction is the sum of the fees of its
+    /// [Transaction](crate::transactions::Transaction) outcome and the outcomes of all the receipts it has produced, except the refunds.
+    pub fn fee(&self) -> u128 {
+        self.outcome_with_id.outcome.tokens_burnt
+    }
+
+    /// Returns the ids of the [Receipts](Receipt) produced by the execution.
+    pub fn receipt_ids(&self) -> &[CryptoHash] {
+        &self.outcome_with_id.outcome.receipt_ids
+    }
+
+    /// Returns the logs emitted during the execution.
+    pub fn logs(&self) -> &[String] {
+        &self.outcome_with_id.outcome.logs
+    }
+
+    /// Returns the owned logs of the corresponding ExecutionOutcome.
+    pub fn logs_owned(&self) -> Vec<String> {
+        self.outcome_with_id.outcome.logs.clone()
+    }
+
+    /// Returns the [ExecutionStatus] of the execution.
+    pub fn status(&self) -> &ExecutionStatus {
+        &self.status
+    }
+
+    /// Returns the Merkle proof of the inclusion of the ExecutionOutcome into the `outcome_root` of the chunk.
+    ///
+    /// **Heads up!** This methods returns types defined in the `near-primitives` crate as is.
+    /// It is a subject of change in the future (once we define the corresponding Lake Primitives types)
+    pub fn proof(&self) -> &[MerklePathItem] {
+        &self.outcome_with_id.proof
+    }
+
+    /// Verifies the [ExecutionOutcome::proof] against the `outcome_root` of the chunk.
+    ///
+    /// **Heads up!** The outcomes of the chunk execution are committed to by the *next* chunk of the same shard,
+    /// so the root to verify the outcomes of the [Block](crate::block::Block) against is
+    /// [Block::chunk_outcome_root](crate::block::Block::chunk_outcome_root) of the next block with the chunk of the shard included.
+    pub fn verify(&self, chunk_outcome_root: CryptoHash) -> bool {
+        merkle::verify_hash(
+            chunk_outcome_root,
+            &self.outcome_with_id.proof,
+            self.outcome_hash(),
+        )
+    }
+
+    /// The hash of the outcome as it is merklized into the `outcome_root` of the chunk
+    fn outcome_hash(&self) -> CryptoHash {
+        CryptoHash::hash_borsh(self.outcome_with_id.to_hashes())
+    }
+}
+
+impl From<&views::ExecutionOutcomeWithIdView> for ExecutionOutcome {
+    fn from(outcome_with_id: &views::ExecutionOutcomeWithIdView) -> Self {
+        Self {
+            outcome_with_id: std::sync::Arc::new(views::ExecutionOutcomeWithIdView {
+                proof: outcome_with_id.proof.clone(),
+                block_hash: outcome_with_id.block_hash,
+                id: outcome_with_id.id,
+                outcome: views::ExecutionOutcomeView {
+                    logs: outcome_with_id.outcome.logs.clone(),
+                    receipt_ids: outcome_with_id.outcome.receipt_ids.clone(),
+                    gas_burnt: outcome_with_id.outcome.gas_burnt,
+                    tokens_burnt: outcome_with_id.outcome.tokens_burnt,
+                    executor_id: outcome_with_id.outcome.executor_id.clone(),
+                    status: outcome_with_id.outcome.status.clone(),
+                    // the gas profile is not a part of the outcome hash
+                    metadata: views::ExecutionMetadataView::default(),
+                },
+            }),
+            status: (&outcome_with_id.outcome.status).into(),
         }
     }
 }
@@ -156,3 +339,63 @@ impl From<&views::ExecutionStatusView> for ExecutionStatus {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::actions::ActionMetaDataExt;
+
+    #[test]
+    fn verifies_outcome_proofs() {
+        let mut block = crate::types::fixture_block();
+        let transaction = block.transactions().next().unwrap().clone();
+        let receipt = block.receipts().next().unwrap().clone();
+        let transaction_outcome = transaction.execution_outcome();
+        let receipt_outcome = receipt.execution_outcome().unwrap();
+
+        // the chunk outcomes are merklized in the order of execution: the transactions, then the receipts
+        let chunk_outcome_root = merkle::combine_hash(
+            &transaction_outcome.outcome_hash(),
+            &receipt_outcome.outcome_hash(),
+        );
+        assert!(transaction_outcome.verify(chunk_outcome_root));
+        assert!(receipt_outcome.verify(chunk_outcome_root));
+        assert!(!receipt_outcome.verify(CryptoHash::default()));
+    }
+
+    #[test]
+    fn resolves_receipt_actions() {
+        let mut block = crate::types::fixture_block();
+        let receipt = block.receipts().next().unwrap().clone();
+
+        let actions: Vec<_> = receipt.actions(&mut block).cloned().collect();
+        assert!(!actions.is_empty());
+        assert!(actions
+            .iter()
+            .all(|action| action.receipt_id() == receipt.receipt_id()));
+    }
+
+    #[test]
+    fn calculates_fees() {
+        let mut block = crate::types::fixture_block();
+        let gas_price = block.header().gas_price();
+        let transaction_fee = block
+            .transactions()
+            .next()
+            .unwrap()
+            .execution_outcome()
+            .fee();
+        let receipt = block.receipts().next().unwrap();
+        let receipt_outcome = receipt.execution_outcome().unwrap();
+
+        assert_eq!(transaction_fee, 40982462500000000000);
+        assert_eq!(receipt_outcome.fee(), 30805950000000000000);
+        assert_eq!(
+            receipt_outcome.fee(),
+            gas_price * u128::from(receipt_outcome.gas_burnt())
+        );
+        // the gas has been purchased at the gas price of the block, so there is nothing to refund
+        assert_eq!(receipt.gas_price(), Some(gas_price));
+        assert_eq!(receipt.gas_price_refund(), Some(0));
+    }
+}
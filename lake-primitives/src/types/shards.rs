@@ -0,0 +1,110 @@
+use super::receipts::Receipt;
+use super::state_changes::StateChange;
+use super::transactions::Transaction;
+use crate::near_indexer_primitives::{
+    types::{AccountId, ShardId},
+    CryptoHash, IndexerShard,
+};
+
+/// A high-level representation of the part of the [Block](crate::block::Block) that belongs to a single shard.
+///
+/// The [Block](crate::block::Block) merges the entities from all the shards into single lists, which is what most of the indexers need.
+/// Those who do care about the shard topology (validators, infrastructure teams) get the same data split by the shards
+/// via [Block::shards](crate::block::Block::shards) without parsing the [IndexerShard] manually.
+#[derive(Debug, Clone)]
+pub struct Shard {
+    shard_id: ShardId,
+    chunk: Option<ChunkInfo>,
+    receipts: Vec<Receipt>,
+    postponed_receipts: Vec<Receipt>,
+    transactions: Vec<Transaction>,
+    state_changes: Vec<StateChange>,
+}
+
+#[derive(Debug, Clone)]
+struct ChunkInfo {
+    hash: CryptoHash,
+    author: AccountId,
+}
+
+impl Shard {
+    /// The id of the shard
+    pub fn shard_id(&self) -> ShardId {
+        self.shard_id
+    }
+
+    /// Whether the chunk of the shard is included in the [Block](crate::block::Block).
+    /// If it is not, the shard has no [Transactions](crate::transactions::Transaction) and postponed [Receipts](crate::receipts::Receipt)
+    pub fn has_chunk(&self) -> bool {
+        self.chunk.is_some()
+    }
+
+    /// The hash of the chunk, if the chunk is included in the [Block](crate::block::Block)
+    pub fn chunk_hash(&self) -> Option<CryptoHash> {
+        self.chunk.as_ref().map(|chunk| chunk.hash)
+    }
+
+    /// The [AccountId] of the author of the chunk, if the chunk is included in the [Block](crate::block::Block)
+    pub fn chunk_author(&self) -> Option<&AccountId> {
+        self.chunk.as_ref().map(|chunk| &chunk.author)
+    }
+
+    /// Returns the [Receipts](crate::receipts::Receipt) executed in the shard
+    pub fn receipts(&self) -> &[Receipt] {
+        &self.receipts
+    }
+
+    /// Returns the [Receipts](crate::receipts::Receipt) included in the chunk of the shard yet not executed
+    pub fn postponed_receipts(&self) -> &[Receipt] {
+        &self.postponed_receipts
+    }
+
+    /// Returns the [Transactions](crate::transactions::Transaction) included in the chunk of the shard
+    pub fn transactions(&self) -> &[Transaction] {
+        &self.transactions
+    }
+
+    /// Returns the [StateChanges](crate::state_changes::StateChange) happened in the shard
+    pub fn state_changes(&self) -> &[StateChange] {
+        &self.state_changes
+    }
+}
+
+impl From<&IndexerShard> for Shard {
+    fn from(shard: &IndexerShard) -> Self {
+        let receipts: Vec<Receipt> = shard
+            .receipt_execution_outcomes
+            .iter()
+            .map(Into::into)
+            .collect();
+        let executed_receipts_ids: Vec<_> = receipts
+            .iter()
+            .map(|receipt| receipt.receipt_id())
+            .collect();
+
+        Self {
+            shard_id: shard.shard_id,
+            chunk: shard.chunk.as_ref().map(|chunk| ChunkInfo {
+                hash: chunk.header.chunk_hash,
+                author: chunk.author.clone(),
+            }),
+            postponed_receipts: shard
+                .chunk
+                .iter()
+                .flat_map(|chunk| chunk.receipts.iter())
+                // exclude receipts that are already executed
+                .filter(|receipt| !executed_receipts_ids.contains(&receipt.receipt_id))
+                .map(Into::into)
+                .collect(),
+            transactions: shard
+                .chunk
+                .iter()
+                .flat_map(|chunk| chunk.transactions.iter())
+                .map(TryInto::try_into)
+                .filter_map(|transaction| transaction.ok())
+                .collect(),
+            state_changes: shard.state_changes.iter().map(Into::into).collect(),
+            receipts,
+        }
+    }
+}
@@ -19,17 +19,22 @@ pub struct StateChange {
 
 impl StateChange {
     /// Returns the [AccountId] of the account that was affected by the state change.
-    pub fn affected_account_id(&self) -> AccountId {
-        self.affected_account_id.clone()
+    pub fn affected_account_id(&self) -> &AccountId {
+        &self.affected_account_id
     }
 
     /// Returns the [StateChangeCause] of the state change.
-    pub fn cause(&self) -> StateChangeCause {
-        self.cause.clone()
+    pub fn cause(&self) -> &StateChangeCause {
+        &self.cause
     }
 
     /// Returns the [StateChangeValue] of the state change.
-    pub fn value(&self) -> StateChangeValue {
+    pub fn value(&self) -> &StateChangeValue {
+        &self.value
+    }
+
+    /// Returns the owned [StateChangeValue] of the state change.
+    pub fn value_owned(&self) -> StateChangeValue {
         self.value.clone()
     }
 }
@@ -39,7 +44,7 @@ impl From<&StateChangeWithCauseView> for StateChange {
         let cause: StateChangeCause = (&state_change_with_cause_view.cause).into();
         let value: StateChangeValue = (&state_change_with_cause_view.value).into();
         Self {
-            affected_account_id: value.affected_account_id(),
+            affected_account_id: value.affected_account_id().clone(),
             cause,
             value,
         }
@@ -130,16 +135,16 @@ pub enum StateChangeValue {
 }
 
 impl StateChangeValue {
-    pub fn affected_account_id(&self) -> AccountId {
+    pub fn affected_account_id(&self) -> &AccountId {
         match self {
-            Self::AccountUpdate { account_id, .. } => account_id.clone(),
-            Self::AccountDeletion { account_id } => account_id.clone(),
-            Self::AccessKeyUpdate { account_id, .. } => account_id.clone(),
-            Self::AccessKeyDeletion { account_id, .. } => account_id.clone(),
-            Self::DataUpdate { account_id, .. } => account_id.clone(),
-            Self::DataDeletion { account_id, .. } => account_id.clone(),
-            Self::ContractCodeUpdate { account_id, .. } => account_id.clone(),
-            Self::ContractCodeDeletion { account_id } => account_id.clone(),
+            Self::AccountUpdate { account_id, .. } => account_id,
+            Self::AccountDeletion { account_id } => account_id,
+            Self::AccessKeyUpdate { account_id, .. } => account_id,
+            Self::AccessKeyDeletion { account_id, .. } => account_id,
+            Self::DataUpdate { account_id, .. } => account_id,
+            Self::DataDeletion { account_id, .. } => account_id,
+            Self::ContractCodeUpdate { account_id, .. } => account_id,
+            Self::ContractCodeDeletion { account_id } => account_id,
         }
     }
 }
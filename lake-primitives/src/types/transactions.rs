@@ -1,6 +1,6 @@
 use near_crypto::{PublicKey, Signature};
 
-use super::receipts::ExecutionStatus;
+use super::receipts::{ExecutionOutcome, ExecutionStatus};
 use crate::near_indexer_primitives::{types::AccountId, CryptoHash, IndexerTransactionWithOutcome};
 
 /// High-level representation of the `Transaction`.
@@ -21,8 +21,7 @@ pub struct Transaction {
     signer_public_key: PublicKey,
     signature: Signature,
     receiver_id: AccountId,
-    status: ExecutionStatus,
-    execution_outcome_id: CryptoHash,
+    execution_outcome: ExecutionOutcome,
     actions: Vec<super::actions::Action>,
 }
 
@@ -54,12 +53,20 @@ impl Transaction {
 
     /// Returns the [ExecutionStatus] of the corresponding ExecutionOutcome.
     pub fn status(&self) -> &ExecutionStatus {
-        &self.status
+        self.execution_outcome.status()
     }
 
     /// Returns the [CryptoHash] id of the corresponding ExecutionOutcome.
     pub fn execution_outcome_id(&self) -> CryptoHash {
-        self.execution_outcome_id
+        self.execution_outcome.id()
+    }
+
+    /// Returns the corresponding [ExecutionOutcome].
+    ///
+    /// **Reminder**: the transaction execution produces a single [Receipt](super::receipts::Receipt), its id is in the
+    /// [ExecutionOutcome::receipt_ids]
+    pub fn execution_outcome(&self) -> &ExecutionOutcome {
+        &self.execution_outcome
     }
 
     /// Returns the [Action](super::actions::Action) of the transaction.
@@ -78,8 +85,7 @@ impl TryFrom<&IndexerTransactionWithOutcome> for Transaction {
             signer_public_key: tx_with_outcome.transaction.public_key.clone(),
             signature: tx_with_outcome.transaction.signature.clone(),
             receiver_id: tx_with_outcome.transaction.receiver_id.clone(),
-            execution_outcome_id: tx_with_outcome.outcome.execution_outcome.id,
-            status: (&tx_with_outcome.outcome.execution_outcome.outcome.status).into(),
+            execution_outcome: (&tx_with_outcome.outcome.execution_outcome).into(),
             actions: super::actions::Action::try_vec_from_transaction_outcome(tx_with_outcome)?,
         })
     }